@@ -1,6 +1,7 @@
 use criterion::{criterion_group, criterion_main, Criterion};
+use tokio::io::AsyncReadExt;
 
-use tunneler_core::message::{Message, MessageHeader, MessageType};
+use tunneler_core::message::{Message, MessageHeader, MessageType, ReadBuffer};
 
 pub fn criterion_benchmark(c: &mut Criterion) {
     let mut data = [0; 13];
@@ -14,10 +15,54 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         b.iter(|| header.serialize(&mut serialize_out))
     });
 
-    let msg = Message::new(header, vec![0; 4092]);
+    let msg = Message::new(header.clone(), vec![0; 4092]);
     c.bench_function("Serialize-Message", |b| {
         b.iter(|| msg.serialize(&mut serialize_out))
     });
+    // Keeping a copy of a Message, like it is done for every Message of a
+    // Session until it was acknowledged
+    c.bench_function("Copy-Message", |b| {
+        b.iter(|| Message::new(header.clone(), msg.get_data().to_vec()))
+    });
+    c.bench_function("Clone-Message", |b| b.iter(|| msg.clone()));
+
+    // Reading the Data of Messages, like it is done for every Read from a
+    // User-Connection, once with a new Allocation for every Read, which
+    // used to be the Data of the Message as is, and once using a reusable
+    // Buffer
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    for read_size in [512, 4096] {
+        let source = vec![7; 64 * read_size];
+
+        c.bench_function(&format!("Read-Message-Data-Vec-{}", read_size), |b| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    for chunk in source.chunks(read_size) {
+                        let mut reader = chunk;
+                        let mut buf = vec![0; 4096];
+                        let n = reader.read(&mut buf).await.unwrap();
+                        buf.truncate(n);
+                        drop(buf);
+                    }
+                })
+            })
+        });
+
+        let mut read_buffer = ReadBuffer::new();
+        c.bench_function(&format!("Read-Message-Data-Buffer-{}", read_size), |b| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    for chunk in source.chunks(read_size) {
+                        let mut reader = chunk;
+                        let data = read_buffer.read(&mut reader, 4096).await.unwrap();
+                        drop(Message::new(header.clone(), data));
+                    }
+                })
+            })
+        });
+    }
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,109 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use criterion::{criterion_group, criterion_main, Criterion};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use tunneler_core::client::{Handler, Receiver, Sender, UserCon};
+use tunneler_core::{server::Strategy, Destination, Details};
+
+const LISTEN_PORT: u32 = 18401;
+const USER_PORT: u16 = 18400;
+
+/// The Number of User-Connections, that are used at the same Time
+const USERS: usize = 32;
+/// The Number of Round-Trips every User-Connection does per Iteration
+const ROUND_TRIPS: usize = 50;
+
+/// Sends all the Data it receives back to the User
+struct Echo;
+
+#[async_trait]
+impl Handler for Echo {
+    async fn new_con(self: Arc<Self>, _id: u32, _details: Details, con: UserCon) {
+        let (mut rx, tx) = con.into_split();
+        while let Ok(msg) = rx.recv_msg().await {
+            if msg.is_eof() {
+                break;
+            }
+            let length = msg.get_header().get_length();
+            if tx.send_msg(msg.into_data(), length).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+async fn round_trips(mut con: TcpStream) -> TcpStream {
+    let out = [7; 64];
+    let mut back = [0; 64];
+    for _ in 0..ROUND_TRIPS {
+        con.write_all(&out).await.unwrap();
+        con.read_exact(&mut back).await.unwrap();
+    }
+    con
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let mut users = runtime.block_on(async {
+        let key = b"benchmark".to_vec();
+        let server = tunneler_core::server::builder()
+            .listen_port(LISTEN_PORT)
+            .port_strategy(Strategy::Single(USER_PORT))
+            .key(key.clone())
+            .empty_metrics()
+            .build();
+        tokio::spawn(server.listen());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let client = tunneler_core::client::builder()
+            .destination(Destination::new("127.0.0.1".to_owned(), LISTEN_PORT))
+            .external_port(USER_PORT)
+            .key(key)
+            .empty_metrics()
+            .build();
+        tokio::spawn(client.start(Arc::new(Echo)));
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let mut users = Vec::with_capacity(USERS);
+        for _ in 0..USERS {
+            let con = TcpStream::connect(("127.0.0.1", USER_PORT)).await.unwrap();
+            con.set_nodelay(true).unwrap();
+            users.push(con);
+        }
+        users
+    });
+
+    // Many small Messages from many User-Connections at the same Time, which
+    // all have to go over the single Connection between Server and Client
+    c.bench_function("End-to-End-Small-Messages", |b| {
+        b.iter(|| {
+            users = runtime.block_on(async {
+                let tasks: Vec<_> = users
+                    .drain(..)
+                    .map(|con| tokio::spawn(round_trips(con)))
+                    .collect();
+                let mut done = Vec::with_capacity(USERS);
+                for task in tasks {
+                    done.push(task.await.unwrap());
+                }
+                done
+            });
+        })
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = criterion_benchmark
+}
+criterion_main!(benches);
@@ -11,7 +11,9 @@ pub struct ExampleHandler;
 impl Handler for ExampleHandler {
     async fn new_con(self: Arc<Self>, id: u32, _details: Details, con: UserCon) {
         println!("Handling: {}", id);
-        con.send_msg(vec![b't', b'e', b's', b't'], 4).await.unwrap();
+        con.send_msg(vec![b't', b'e', b's', b't'].into(), 4)
+            .await
+            .unwrap();
     }
 }
 
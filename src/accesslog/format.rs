@@ -0,0 +1,245 @@
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::AccessRecord;
+use crate::message::CloseCode;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// The Format in which the Access-Records are written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Every Record is a single JSON-Object on its own Line
+    Json,
+    /// Every Record is written in a Style similar to the Common Log Format
+    ///
+    /// `{user} - {client-id} [{end}] "TUNNEL {port} {connection-id}" {close-code}
+    /// {send-bytes} {recv-bytes} {duration-ms}`
+    Common,
+}
+
+/// The Date and Time, in UTC, split into its Parts
+struct DateTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u64,
+    minute: u64,
+    second: u64,
+    millis: u32,
+}
+
+impl DateTime {
+    fn new(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = since_epoch.as_secs();
+        let days = (secs / 86400) as i64;
+        let secs_of_day = secs % 86400;
+
+        // Converts the Days since the Epoch to the civil Date, based on
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Self {
+            year,
+            month,
+            day,
+            hour: secs_of_day / 3600,
+            minute: (secs_of_day % 3600) / 60,
+            second: secs_of_day % 60,
+            millis: since_epoch.subsec_millis(),
+        }
+    }
+
+    /// Formats the Time according to RFC 3339
+    fn rfc3339(&self) -> String {
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.millis
+        )
+    }
+
+    /// Formats the Time like it is used in the Common Log Format
+    fn common(&self) -> String {
+        format!(
+            "{:02}/{}/{:04}:{:02}:{:02}:{:02} +0000",
+            self.day,
+            MONTHS[(self.month - 1) as usize],
+            self.year,
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+}
+
+fn code_name(code: CloseCode) -> &'static str {
+    match code {
+        CloseCode::Normal => "normal",
+        CloseCode::ConnectFailed => "connect_failed",
+        CloseCode::Timeout => "timeout",
+        CloseCode::Policy => "policy",
+        CloseCode::Error => "error",
+    }
+}
+
+/// Writes the String as a quoted and escaped JSON-String
+fn json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn json_optional<T>(value: Option<T>) -> String
+where
+    T: std::fmt::Display,
+{
+    match value {
+        Some(v) => v.to_string(),
+        None => "null".to_string(),
+    }
+}
+
+impl Format {
+    /// Formats the Record into a single Line, without the trailing Newline
+    pub fn format(&self, record: &AccessRecord) -> String {
+        match self {
+            Self::Json => Self::json(record),
+            Self::Common => Self::common(record),
+        }
+    }
+
+    fn json(record: &AccessRecord) -> String {
+        let mut out = String::with_capacity(256);
+
+        let _ = write!(
+            out,
+            "{{\"port\":{},\"user_ip\":\"{}\",\"user_port\":{},\"client_id\":{},\"connection_id\":{}",
+            record.port,
+            record.user_ip,
+            json_optional(record.user_port),
+            json_optional(record.client_id),
+            record.connection_id
+        );
+        let _ = write!(
+            out,
+            ",\"start\":\"{}\",\"end\":\"{}\",\"duration_ms\":{}",
+            DateTime::new(record.start).rfc3339(),
+            DateTime::new(record.end).rfc3339(),
+            record.duration().as_millis()
+        );
+        let _ = write!(
+            out,
+            ",\"recv_bytes\":{},\"send_bytes\":{},\"close_code\":\"{}\",\"close_text\":",
+            record.recv_bytes,
+            record.send_bytes,
+            code_name(record.reason.code())
+        );
+        match record.reason.text() {
+            Some(text) => json_string(&mut out, text),
+            None => out.push_str("null"),
+        };
+        out.push('}');
+
+        out
+    }
+
+    fn common(record: &AccessRecord) -> String {
+        let user = match record.user_port {
+            Some(port) => SocketAddr::new(record.user_ip, port).to_string(),
+            None => record.user_ip.to_string(),
+        };
+        let client = match record.client_id {
+            Some(id) => id.to_string(),
+            None => "-".to_string(),
+        };
+
+        format!(
+            "{} - {} [{}] \"TUNNEL {} {}\" {} {} {} {}",
+            user,
+            client,
+            DateTime::new(record.end).common(),
+            record.port,
+            record.connection_id,
+            record.reason.code().serialize(),
+            record.send_bytes,
+            record.recv_bytes,
+            record.duration().as_millis()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::message::CloseReason;
+    use std::time::Duration;
+
+    fn record() -> AccessRecord {
+        AccessRecord {
+            port: 8080,
+            user_ip: "10.0.0.1".parse().unwrap(),
+            user_port: Some(51234),
+            client_id: Some(13),
+            connection_id: 5,
+            // 2021-03-04T05:06:07.250Z
+            start: UNIX_EPOCH + Duration::from_millis(1_614_834_367_250),
+            end: UNIX_EPOCH + Duration::from_millis(1_614_834_369_750),
+            recv_bytes: 100,
+            send_bytes: 2000,
+            reason: CloseReason::with_text(CloseCode::Error, "broken \"pipe\""),
+        }
+    }
+
+    #[test]
+    fn format_json() {
+        assert_eq!(
+            concat!(
+                "{\"port\":8080,\"user_ip\":\"10.0.0.1\",\"user_port\":51234,\"client_id\":13,",
+                "\"connection_id\":5,\"start\":\"2021-03-04T05:06:07.250Z\",",
+                "\"end\":\"2021-03-04T05:06:09.750Z\",\"duration_ms\":2500,\"recv_bytes\":100,",
+                "\"send_bytes\":2000,\"close_code\":\"error\",\"close_text\":\"broken \\\"pipe\\\"\"}"
+            ),
+            Format::Json.format(&record())
+        );
+    }
+
+    #[test]
+    fn format_common() {
+        assert_eq!(
+            "10.0.0.1:51234 - 13 [04/Mar/2021:05:06:09 +0000] \"TUNNEL 8080 5\" 4 2000 100 2500",
+            Format::Common.format(&record())
+        );
+    }
+
+    #[test]
+    fn datetime_leap_year() {
+        // 2024-02-29T23:59:59Z
+        let time = DateTime::new(UNIX_EPOCH + Duration::from_secs(1_709_251_199));
+        assert_eq!("2024-02-29T23:59:59.000Z", time.rfc3339());
+    }
+}
@@ -0,0 +1,82 @@
+//! Structured Records for every tunneled User-Connection
+//!
+//! Once a User-Connection is closed, an [`AccessRecord`] is created for it,
+//! formatted using the configured [`Format`] and then passed to the
+//! [`AccessLogWriter`] of the [`AccessLog`]
+
+mod record;
+pub use record::AccessRecord;
+
+mod format;
+pub use format::Format;
+
+mod writer;
+pub use writer::AccessLogWriter;
+
+use std::sync::Arc;
+
+/// The Access-Log, that formats the Records and writes them using a Writer
+#[derive(Clone)]
+pub struct AccessLog {
+    format: Format,
+    writer: Arc<dyn AccessLogWriter>,
+}
+
+impl std::fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessLog")
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+impl AccessLog {
+    /// Creates a new Access-Log, that writes all the Records in the given
+    /// Format to the Writer
+    pub fn new<W>(format: Format, writer: W) -> Self
+    where
+        W: AccessLogWriter + 'static,
+    {
+        Self {
+            format,
+            writer: Arc::new(writer),
+        }
+    }
+
+    /// Formats and writes the Record
+    pub fn log(&self, record: &AccessRecord) {
+        self.writer.write(&self.format.format(record));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::message::{CloseCode, CloseReason};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn log_record() {
+        let output = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = AccessLog::new(Format::Common, output.clone());
+
+        log.log(&AccessRecord {
+            port: 8080,
+            user_ip: "127.0.0.1".parse().unwrap(),
+            user_port: None,
+            client_id: None,
+            connection_id: 5,
+            start: UNIX_EPOCH,
+            end: UNIX_EPOCH + Duration::from_secs(1),
+            recv_bytes: 10,
+            send_bytes: 20,
+            reason: CloseReason::new(CloseCode::Normal),
+        });
+
+        assert_eq!(
+            "127.0.0.1 - - [01/Jan/1970:00:00:01 +0000] \"TUNNEL 8080 5\" 0 20 10 1000\n",
+            String::from_utf8(output.lock().unwrap().clone()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime};
+
+use crate::message::CloseReason;
+
+/// The Record for a single User-Connection, once it was closed
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessRecord {
+    /// The external Port on which the User connected
+    pub port: u16,
+    /// The IP from which the User connected
+    pub user_ip: IpAddr,
+    /// The Port from which the User connected, which is only known on the
+    /// Server
+    pub user_port: Option<u16>,
+    /// The ID of the Client that handled the Connection, which is only known
+    /// on the Server
+    pub client_id: Option<u32>,
+    /// The ID of the User-Connection
+    pub connection_id: u32,
+    /// When the Connection was opened
+    pub start: SystemTime,
+    /// When the Connection was closed
+    pub end: SystemTime,
+    /// The Number of Bytes received from the User
+    pub recv_bytes: u64,
+    /// The Number of Bytes send to the User
+    pub send_bytes: u64,
+    /// Why the Connection was closed
+    pub reason: CloseReason,
+}
+
+impl AccessRecord {
+    /// How long the Connection was open
+    pub fn duration(&self) -> Duration {
+        self.end.duration_since(self.start).unwrap_or_default()
+    }
+}
@@ -0,0 +1,45 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// The Destination for the formatted Access-Records
+pub trait AccessLogWriter: Send + Sync {
+    /// Writes a single formatted Record, which does not contain the trailing
+    /// Newline
+    fn write(&self, line: &str);
+}
+
+impl AccessLogWriter for std::io::Stdout {
+    fn write(&self, line: &str) {
+        let _ = writeln!(self.lock(), "{}", line);
+    }
+}
+
+impl AccessLogWriter for std::io::Stderr {
+    fn write(&self, line: &str) {
+        let _ = writeln!(self.lock(), "{}", line);
+    }
+}
+
+impl<W> AccessLogWriter for Mutex<W>
+where
+    W: Write + Send,
+{
+    fn write(&self, line: &str) {
+        let mut writer = match self.lock() {
+            Ok(w) => w,
+            Err(e) => e.into_inner(),
+        };
+        if let Err(e) = writeln!(writer, "{}", line) {
+            error!("Writing Access-Record: {}", e);
+        }
+    }
+}
+
+impl<W> AccessLogWriter for Arc<W>
+where
+    W: AccessLogWriter + ?Sized,
+{
+    fn write(&self, line: &str) {
+        self.as_ref().write(line)
+    }
+}
@@ -7,11 +7,14 @@
 //! halfes
 
 use crate::{
-    connections::{Connections, Destination},
+    accesslog::AccessLog,
+    connections::Destination,
+    general::{
+        ChunkSize, PingError, Pinger, RateLimit, Throttle, TokenBucket, PING_INTERVAL, PING_TIMEOUT,
+    },
     handshake,
-    message::Message,
+    message::{Compression, HeaderFormat, MessageCodec},
     metrics::Metrics,
-    streams::mpsc,
 };
 
 #[cfg(test)]
@@ -20,7 +23,7 @@ pub(crate) mod mocks;
 mod traits;
 pub use traits::*;
 
-use rand::RngCore;
+use rand::{Rng, RngCore};
 use std::sync::Arc;
 
 mod builder;
@@ -28,6 +31,8 @@ pub use builder::ClientBuilder;
 
 mod connections;
 mod heartbeat;
+mod session;
+use session::{Session, SessionSlot};
 
 pub use connections::{
     user_con::{OwnedReceiver, OwnedSender},
@@ -38,6 +43,28 @@ pub use connections::{
 pub(crate) enum ConnectError {
     IO(std::io::Error),
     Handshake(handshake::HandshakeError),
+    /// The Server did not respond to our Pings in time
+    Timeout,
+    /// The Server resumed a Session the Client does not know
+    UnknownSession,
+    /// The Handshake of an additional Connection negotiated a different
+    /// Protocol-Version or Compression than the first one
+    MismatchedConnections,
+}
+
+impl std::fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectError::IO(e) => write!(f, "IO-Error: {}", e),
+            ConnectError::Handshake(e) => write!(f, "Handshake: {}", e),
+            ConnectError::Timeout => write!(f, "The Server stopped responding to Pings"),
+            ConnectError::UnknownSession => write!(f, "The Server resumed an unknown Session"),
+            ConnectError::MismatchedConnections => write!(
+                f,
+                "The Connections negotiated different Protocol-Versions or Compressions"
+            ),
+        }
+    }
 }
 
 impl From<std::io::Error> for ConnectError {
@@ -58,6 +85,24 @@ pub struct Client<M> {
     external_port: u16,
     key: Vec<u8>,
     metrics: Arc<M>,
+    weight: u16,
+    max_connections: Option<usize>,
+    access_log: Option<AccessLog>,
+    rate_limit: Option<RateLimit>,
+    idle_timeout: Option<std::time::Duration>,
+    /// The Number of Connections to open to the Server
+    connections: usize,
+    chunk_size: ChunkSize,
+    /// The Compression-Algorithms the Client offers to the Server
+    compression: Vec<Compression>,
+    /// The maximum Size of the Data of a single Message
+    max_message_size: usize,
+    /// The maximum Number of Bytes kept for resuming the Session
+    session_buffer: usize,
+    /// The Session granted by the Server, while the Connection is lost
+    session: SessionSlot,
+    #[cfg(feature = "prometheus")]
+    metrics_endpoint: Option<crate::metrics::PrometheusEndpoint>,
 }
 
 /// Creates a new Builder to create a Client
@@ -91,10 +136,11 @@ where
     /// Establishes and then also runs a new Connection
     ///
     /// # Behaviour
-    /// This starts 2 more tasks needed for the Client to work
-    /// properly and then blocks on the 3. function.
-    /// Therefore this function should only return once the
-    /// Connection is being terminated
+    /// This starts the tasks needed for every Connection to the
+    /// Server and then blocks until any of them is lost.
+    /// Therefore this function should only return once a
+    /// Connection is being terminated or the Server stopped
+    /// responding to our Pings
     async fn start_con<H>(&self, handler: Arc<H>) -> Result<(), ConnectError>
     where
         H: Handler + Send + Sync + 'static,
@@ -103,49 +149,205 @@ where
 
         let target_addr = self.server_destination.get_full_address();
         debug!("Conneting to server: {}", target_addr);
-        let mut connection = tokio::net::TcpStream::connect(target_addr).await?;
+        let mut connection = tokio::net::TcpStream::connect(&target_addr).await?;
         debug!("Connected to Server");
 
-        let handshake_conf = handshake::Config::new(self.external_port);
+        let mut handshake_conf = handshake::Config::new(self.external_port)
+            .with_weight(self.weight)
+            .with_compression(&self.compression);
+        if let Some(limit) = self.rate_limit {
+            handshake_conf = handshake_conf.with_rate_limit(limit.bytes_per_second());
+        }
+        // Every Attempt uses a new Group, so that the Connections are not
+        // added to a previous Client the Server did not remove yet. The Server
+        // never grants Sessions to grouped Connections, so none is resumed
+        if self.connections > 1 {
+            let group = rand::thread_rng().gen::<u128>().max(1);
+            handshake_conf = handshake_conf.with_group(group);
+        } else {
+            let token = self
+                .session
+                .lock()
+                .unwrap()
+                .as_ref()
+                .and_then(|s| s.token());
+            if let Some(token) = token {
+                handshake_conf = handshake_conf.with_session(token);
+            }
+        }
 
         debug!("Starting Handshake...");
-        handshake::client::perform(&mut connection, &self.key, handshake_conf).await?;
+        let handshake_start = tokio::time::Instant::now();
+        let (server_version, granted, compression) =
+            handshake::client::perform(&mut connection, &self.key, handshake_conf.clone()).await?;
+        self.metrics
+            .handshake_latency(self.external_port, handshake_start.elapsed());
         debug!("Performed Handshake");
 
-        let (read_con, write_con) = connection.into_split();
+        let mut connections = vec![connection];
+        // Older Servers would treat every Connection as a separate Client
+        let parallel = match server_version >= 6 {
+            true => self.connections,
+            false => 1,
+        };
+        for _ in 1..parallel {
+            let mut connection = tokio::net::TcpStream::connect(&target_addr).await?;
+            let (other_version, _, other_compression) =
+                handshake::client::perform(&mut connection, &self.key, handshake_conf.clone())
+                    .await?;
+            // All the Connections share the same Codec and Compression
+            if other_version != server_version || other_compression != compression {
+                return Err(ConnectError::MismatchedConnections);
+            }
+            connections.push(connection);
+        }
+        debug!("Opened {} Connections", connections.len());
+
+        let previous = self.session.lock().unwrap().take();
+        let (session, mut resumed) = match (previous, granted.as_ref()) {
+            (Some(mut previous), Some(granted))
+                if granted.resumed() && previous.token() == Some(granted.token()) =>
+            {
+                info!("Resumed Session");
+                let resumed = previous.resume();
+                (previous, resumed)
+            }
+            (previous, _) => {
+                // The Session could not be resumed, so all of its
+                // User-Connections are lost
+                if let Some(previous) = previous {
+                    previous.close();
+                }
+                if granted.as_ref().map(|g| g.resumed()).unwrap_or(false) {
+                    return Err(ConnectError::UnknownSession);
+                }
+                (Session::new(granted.as_ref(), self.session_buffer), None)
+            }
+        };
 
         info!("Established Conection");
 
-        let (queue_tx, queue_rx) = tokio::sync::mpsc::unbounded_channel();
-        let outgoing = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
-
-        // The Heartbeat loop used to keep the Connection open and verify that it
-        // is still working
-        tokio::task::spawn(heartbeat::keep_alive(
-            queue_tx.clone(),
-            std::time::Duration::from_secs(15),
-        ));
-
-        // Runs the Sender in the Background
-        // This task is responsible for sending out all the Queued up Messages
-        tokio::task::spawn(connections::tx::sender(
-            write_con,
-            queue_rx,
-            self.metrics.clone(),
-        ));
-
-        // This task is responsible for receiving all the Messages by the Server
-        // and adds them to the fitting Queue
-        connections::rx::receiver(
-            read_con,
-            queue_tx.clone(),
-            outgoing,
-            handler,
-            self.metrics.clone(),
-        )
-        .await;
-
-        Ok(())
+        let queue_tx = session.queue_tx.clone();
+        let outgoing = session.outgoing.clone();
+
+        // Older Servers dont support Pings, so we just use the Heartbeat to keep
+        // the Connection open
+        let supports_ping = server_version >= 2;
+        let heartbeat = match supports_ping {
+            true => None,
+            false => Some(tokio::task::spawn(heartbeat::keep_alive(
+                queue_tx.clone(),
+                PING_INTERVAL,
+            ))),
+        };
+
+        // The Limit is shared by all the Connections
+        let bucket = self.rate_limit.map(|l| Arc::new(TokenBucket::new(l)));
+        let settings = connections::rx::Settings {
+            server_version,
+            max_connections: self.max_connections,
+            port: self.external_port,
+            access_log: self.access_log.clone(),
+            idle_timeout: self.idle_timeout,
+            chunk_size: self.chunk_size,
+            compression,
+            max_message_size: self.max_message_size,
+            sequencer: session.sequencer.clone(),
+        };
+
+        // Every Connection reports here once it is lost, which then stops all
+        // the others as well
+        let (lost_tx, mut lost_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut tasks = Vec::new();
+        for (index, connection) in connections.into_iter().enumerate() {
+            // The first Connection uses the Queue of the Session, while every
+            // other one gets its own Queue for the User-Connections it receives
+            let (queue_tx, queue_rx) = match index {
+                0 => (session.queue_tx.clone(), session.queue_rx.clone()),
+                _ => {
+                    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+                    (tx, Arc::new(tokio::sync::Mutex::new(rx)))
+                }
+            };
+            let (read_con, write_con) = connection.into_split();
+            let pinger = Arc::new(Pinger::new());
+
+            // The Sender and Receiver for the Connection share the same Span
+            #[cfg(feature = "trace")]
+            let span = tracing::info_span!(
+                "connection",
+                port = self.external_port,
+                server = %target_addr,
+                index
+            );
+
+            // Runs the Sender in the Background
+            // This task is responsible for sending out all the Queued up Messages
+            tasks.push(tokio::task::spawn(instrument!(
+                connections::tx::sender(
+                    write_con,
+                    queue_rx,
+                    outgoing.clone(),
+                    self.metrics.clone(),
+                    Throttle::new().with(bucket.clone()),
+                    session.sequencer.clone(),
+                    compression,
+                    MessageCodec::new(HeaderFormat::for_version(server_version))
+                        .with_max_size(self.max_message_size),
+                    resumed.take(),
+                ),
+                span.clone()
+            )));
+
+            // This task is responsible for receiving all the Messages by the Server
+            // and adds them to the fitting Queue
+            let receiver = instrument!(
+                connections::rx::receiver(
+                    read_con,
+                    queue_tx.clone(),
+                    outgoing.clone(),
+                    pinger.clone(),
+                    settings.clone(),
+                    handler.clone(),
+                    self.metrics.clone(),
+                ),
+                span
+            );
+
+            let lost_tx = lost_tx.clone();
+            tasks.push(tokio::task::spawn(async move {
+                let result = if supports_ping {
+                    // The Ping loop used to keep the Connection open and verify that the
+                    // Server is still responding
+                    tokio::select! {
+                        _ = receiver => Ok(()),
+                        result = pinger.run(&queue_tx, PING_INTERVAL, PING_TIMEOUT) => match result {
+                            PingError::Timeout => Err(ConnectError::Timeout),
+                            PingError::Sending => Ok(()),
+                        },
+                    }
+                } else {
+                    receiver.await;
+                    Ok(())
+                };
+                let _ = lost_tx.send(result);
+            }));
+        }
+        drop(lost_tx);
+
+        let result = lost_rx.recv().await.unwrap_or(Ok(()));
+
+        // The Connection is gone at this Point, so the User-Connections can
+        // only be used again if the Session is resumed in time
+        session.suspend(&self.session);
+        for task in tasks {
+            task.abort();
+        }
+        if let Some(heartbeat) = heartbeat {
+            heartbeat.abort();
+        }
+
+        result
     }
 
     /// This starts up the Client to receive new Connections from the Server.
@@ -158,6 +360,16 @@ where
     {
         info!("Starting...");
 
+        #[cfg(feature = "prometheus")]
+        if let Some(endpoint) = self.metrics_endpoint.clone() {
+            tokio::task::spawn(async move {
+                let addr = endpoint.addr();
+                if let Err(e) = endpoint.serve().await {
+                    error!("Serving Metrics on {}: {}", addr, e);
+                }
+            });
+        }
+
         let mut attempts = 0;
 
         loop {
@@ -166,9 +378,10 @@ where
                     attempts = 0;
                 }
                 Err(e) => {
-                    error!("Connecting: {:?}", e);
+                    error!("Connecting: {}", e);
 
                     attempts += 1;
+                    self.metrics.reconnect_attempt(self.external_port, attempts);
                     let wait_time = Self::exponential_backoff(
                         attempts,
                         Some(std::time::Duration::from_secs(60)),
@@ -1,4 +1,8 @@
-use crate::{metrics, Destination};
+use crate::{
+    accesslog::AccessLog,
+    message::{Compression, MessageCodec},
+    metrics, ChunkSize, Destination, RateLimit,
+};
 
 use super::Client;
 
@@ -17,6 +21,18 @@ pub struct BuilderKey {
 pub struct BuilderMetrics<M> {
     prev: BuilderKey,
     metrics: M,
+    weight: u16,
+    max_connections: Option<usize>,
+    access_log: Option<AccessLog>,
+    rate_limit: Option<RateLimit>,
+    idle_timeout: Option<std::time::Duration>,
+    connections: usize,
+    chunk_size: ChunkSize,
+    compression: Vec<Compression>,
+    max_message_size: usize,
+    session_buffer: usize,
+    #[cfg(feature = "prometheus")]
+    metrics_endpoint: Option<metrics::PrometheusEndpoint>,
 }
 
 /// The Builder used to create a new Client in a compile-time checked way
@@ -84,6 +100,18 @@ impl ClientBuilder<BuilderKey> {
             state: BuilderMetrics {
                 prev: self.state,
                 metrics,
+                weight: 1,
+                max_connections: None,
+                access_log: None,
+                rate_limit: None,
+                idle_timeout: None,
+                connections: 1,
+                chunk_size: ChunkSize::default(),
+                compression: Vec::new(),
+                max_message_size: MessageCodec::DEFAULT_MAX_SIZE,
+                session_buffer: crate::general::DEFAULT_MAX_BUFFERED,
+                #[cfg(feature = "prometheus")]
+                metrics_endpoint: None,
             },
         }
     }
@@ -92,9 +120,134 @@ impl ClientBuilder<BuilderKey> {
     pub fn empty_metrics(self) -> ClientBuilder<BuilderMetrics<metrics::Empty>> {
         self.metrics(metrics::Empty::new())
     }
+
+    /// Uses the builtin Prometheus Metrics Collector for the Client
+    #[cfg(feature = "prometheus")]
+    pub fn prometheus_metrics(self) -> ClientBuilder<BuilderMetrics<metrics::Prometheus>> {
+        self.metrics(metrics::Prometheus::new())
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl ClientBuilder<BuilderMetrics<metrics::Prometheus>> {
+    /// Serves the Metrics over HTTP on the given Address, under `/metrics`
+    pub fn metrics_endpoint(mut self, addr: std::net::SocketAddr) -> Self {
+        self.state.metrics_endpoint = Some(metrics::PrometheusEndpoint::new(
+            addr,
+            self.state.metrics.clone(),
+        ));
+        self
+    }
 }
 
 impl<M> ClientBuilder<BuilderMetrics<M>> {
+    /// Sets the Weight of the Client
+    ///
+    /// The Weight describes the Capacity of this Client in relation to the other Clients for the
+    /// same External Port and is used by the Server when balancing the User-Connections between
+    /// them. Defaults to 1
+    pub fn weight(mut self, weight: u16) -> Self {
+        self.state.weight = weight;
+        self
+    }
+
+    /// Sets the maximum Number of User-Connections the Client handles at the same time
+    ///
+    /// Any further Connections are rejected, which allows the Server to hand them to another
+    /// Client for the same External Port
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.state.max_connections = Some(max);
+        self
+    }
+
+    /// Writes an Access-Record for every User-Connection, once it is closed, to the given Log
+    pub fn access_log(mut self, log: AccessLog) -> Self {
+        self.state.access_log = Some(log);
+        self
+    }
+
+    /// Limits the Bandwidth used for sending Data to the Server
+    ///
+    /// The Limit is also send to the Server, which then limits the Data it
+    /// sends to this Client in the same way. A Limit of 0 Bytes per Second
+    /// disables it
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.state.rate_limit = Some(limit);
+        self
+    }
+
+    /// Closes User-Connections once no Data was send or received over them for the given
+    /// Timeout
+    ///
+    /// Both the Handler and the Server are notified with a Timeout-Reason
+    pub fn idle_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.state.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the Number of Connections the Client opens to the Server
+    ///
+    /// The Server handles all of them as a single Client and spreads the
+    /// User-Connections across them, which avoids a single slow
+    /// User-Connection holding up all the others. Losing any of the
+    /// Connections closes all of them. Servers that dont support this only
+    /// get a single Connection. Defaults to 1
+    ///
+    /// Sessions are only used with a single Connection, so with more than
+    /// one Connection all the User-Connections are closed once any of them
+    /// is lost, even if the Server enabled Sessions
+    pub fn connections(mut self, count: usize) -> Self {
+        self.state.connections = count.max(1);
+        self
+    }
+
+    /// Sets the Size of the Chunks in which Data is read, when forwarding a
+    /// Connection to a User using `OwnedSender::forward`
+    ///
+    /// Every Chunk is send to the Server as a single Message, so larger Chunks
+    /// reduce the Overhead for bulk Transfers. Defaults to a fixed Size of
+    /// 4 KiB
+    pub fn read_chunk_size(mut self, size: ChunkSize) -> Self {
+        self.state.chunk_size = size;
+        self
+    }
+
+    /// Sets the Compression-Algorithms the Client supports
+    ///
+    /// The Server picks one of them, that it supports as well, which is then
+    /// used to compress the Data send over the Connection. Only Servers that
+    /// support Protocol-Version 7 use Compression and by default it is
+    /// disabled
+    pub fn compression(mut self, algorithms: &[Compression]) -> Self {
+        self.state.compression = algorithms.to_vec();
+        self
+    }
+
+    /// Sets the maximum Size of the Data of a single Message exchanged with
+    /// the Server
+    ///
+    /// Data send by the Handlers is split into Messages of at most this Size.
+    /// A Message that still exceeds it is not send and only closes its
+    /// User-Connection with an Error, while receiving such a Message fails
+    /// the Connection to the Server. Defaults to 16 MiB
+    pub fn max_message_size(mut self, bytes: usize) -> Self {
+        self.state.max_message_size = bytes;
+        self
+    }
+
+    /// Sets the maximum Number of Bytes that are kept for the Session granted
+    /// by the Server, to send them again after resuming it
+    ///
+    /// Every Message send to the Server is kept until the Server acknowledged
+    /// it. Once the kept Messages would exceed this Limit, they are dropped
+    /// and the Session fails, so the User-Connections are closed when the
+    /// Connection to the Server is lost instead of being resumed. Defaults to
+    /// 16 MiB
+    pub fn session_buffer(mut self, bytes: usize) -> Self {
+        self.state.session_buffer = bytes;
+        self
+    }
+
     /// Actually builds the Client from the Configuration
     pub fn build(self) -> Client<M> {
         Client {
@@ -102,6 +255,19 @@ impl<M> ClientBuilder<BuilderMetrics<M>> {
             external_port: self.state.prev.prev.port,
             key: self.state.prev.key,
             metrics: std::sync::Arc::new(self.state.metrics),
+            weight: self.state.weight,
+            max_connections: self.state.max_connections,
+            access_log: self.state.access_log,
+            rate_limit: self.state.rate_limit,
+            idle_timeout: self.state.idle_timeout,
+            connections: self.state.connections,
+            chunk_size: self.state.chunk_size,
+            compression: self.state.compression,
+            max_message_size: self.state.max_message_size,
+            session_buffer: self.state.session_buffer,
+            session: Default::default(),
+            #[cfg(feature = "prometheus")]
+            metrics_endpoint: self.state.metrics_endpoint,
         }
     }
 }
@@ -6,6 +6,7 @@ use crate::{
 use super::{Receiver, Sender};
 
 use async_trait::async_trait;
+use bytes::Bytes;
 
 pub mod rx;
 pub mod tx;
@@ -47,7 +48,7 @@ impl Receiver for UserCon {
 impl Sender for UserCon {
     type SendingError = tokio::sync::mpsc::error::SendError<Message>;
 
-    async fn send_msg(&self, data: Vec<u8>, length: u64) -> Result<(), Self::SendingError> {
+    async fn send_msg(&self, data: Bytes, length: u64) -> Result<(), Self::SendingError> {
         self.sender.send_msg(data, length).await
     }
 }
@@ -1,11 +1,16 @@
+use crate::accesslog::AccessLog;
+use crate::client::connections::user_con::{AccessTracker, IdleWatch};
 use crate::client::connections::UserCon;
 use crate::client::{OwnedReceiver, OwnedSender};
-use crate::general::ConnectionReader;
+use crate::general::{ChunkSize, ConnectionReader, IdleTimer, Pinger, Sequencer};
 use crate::streams::mpsc;
 use crate::Details;
 use crate::{
     client::Handler,
-    message::{Message, MessageHeader, MessageType},
+    message::{
+        decompress, CloseCode, CloseReason, CodecError, Compression, HeaderFormat, Message,
+        MessageCodec, MessageHeader, MessageType, ReadBuffer,
+    },
 };
 use crate::{connections::Connections, metrics::Metrics};
 
@@ -13,8 +18,79 @@ use std::sync::Arc;
 
 #[derive(Debug)]
 enum ReceiveError {
-    DeserializingHeader,
-    ReceivingMessage(std::io::Error),
+    ReceivingMessage(CodecError),
+}
+
+impl std::fmt::Display for ReceiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReceiveError::ReceivingMessage(e) => write!(f, "Reading the Message: {}", e),
+        }
+    }
+}
+
+/// The Settings for the Connection to the external Server
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// The Protocol-Version of the external Server
+    pub server_version: u16,
+    /// The maximum Number of User-Connections that are handled at the same
+    /// time, any further Connections are rejected
+    pub max_connections: Option<usize>,
+    /// The external Port of the Client
+    pub port: u16,
+    /// The Log to write an Access-Record for every User-Connection to
+    pub access_log: Option<AccessLog>,
+    /// The Time after which idle User-Connections are closed
+    pub idle_timeout: Option<std::time::Duration>,
+    /// The Size of the Chunks in which the Handlers forward Data to the Users
+    pub chunk_size: ChunkSize,
+    /// The Compression negotiated with the Server, if any
+    pub compression: Option<Compression>,
+    /// The maximum Size of the Data of a single Message
+    pub max_message_size: usize,
+    /// Keeps track of the Messages of the Session, if the Server granted one
+    pub sequencer: Option<Arc<Sequencer>>,
+}
+
+impl Settings {
+    /// The Format of the Headers on the Connection to the Server
+    fn header_format(&self) -> HeaderFormat {
+        HeaderFormat::for_version(self.server_version)
+    }
+
+    /// The Codec for the Messages on the Connection to the Server
+    fn codec(&self) -> MessageCodec {
+        MessageCodec::new(self.header_format()).with_max_size(self.max_message_size)
+    }
+
+    /// If the Server expects an Accept or Reject for every new Connection
+    fn acknowledges(&self) -> bool {
+        self.server_version >= 3
+    }
+
+    /// If the Server supports Reasons in Close-Messages
+    fn close_reasons(&self) -> bool {
+        self.server_version >= 4
+    }
+
+    /// Records a Message of a User-Connection, once it was read completely,
+    /// and sends an Ack for it if one is due
+    fn acknowledge(
+        &self,
+        header: &MessageHeader,
+        send_queue: &tokio::sync::mpsc::UnboundedSender<Message>,
+    ) {
+        let sequencer = match self.sequencer.as_ref() {
+            Some(s) => s,
+            None => return,
+        };
+        if let Some(ack) = sequencer.received(header) {
+            if let Err(e) = send_queue.send(ack) {
+                error!("Sending Ack: {}", e);
+            }
+        }
+    }
 }
 
 /// All the Options needed to receive a single Message
@@ -25,9 +101,53 @@ struct SingleOptions<'a, R> {
     send_queue: &'a tokio::sync::mpsc::UnboundedSender<Message>,
     /// A Collection of all current Connections
     client_cons: &'a Arc<Connections<mpsc::StreamWriter<Message>>>,
-    /// The Buffer that should be used for Deserializing the Header
-    /// into it
-    head_buf: &'a mut [u8; 13],
+    /// The Pinger used for the Connection to the external Server
+    pinger: &'a Pinger,
+    /// The Settings for the Connection
+    settings: &'a Settings,
+    /// The Codec for the Messages on the Connection
+    codec: &'a mut MessageCodec,
+    /// The Buffer that the Messages are read into
+    buf: &'a mut ReadBuffer,
+}
+
+/// Rejects the Connection, or simply closes it if the Server does not
+/// support rejecting Connections
+fn reject_con(sender: OwnedSender, reason: &str, acknowledge: bool) {
+    if acknowledge {
+        sender.reject(reason);
+    } else {
+        sender.close();
+    }
+}
+
+/// Asks the Handler if it wants to handle the new Connection and then either
+/// lets it handle the Connection or rejects it
+async fn accept_con<H>(
+    handler: Arc<H>,
+    id: u32,
+    details: Details,
+    con: UserCon,
+    send_queue: tokio::sync::mpsc::UnboundedSender<Message>,
+    acknowledge: bool,
+) where
+    H: Handler + Send + Sync + 'static,
+{
+    if let Err(reason) = handler.accept(id, &details).await {
+        let (_, sender) = con.into_split();
+        reject_con(sender, &reason, acknowledge);
+        return;
+    }
+
+    if acknowledge {
+        let accept_msg = Message::new(MessageHeader::new(id, MessageType::Accept, 0), vec![]);
+        if let Err(e) = send_queue.send(accept_msg) {
+            error!("Sending Accept for {}: {}", id, e);
+            return;
+        }
+    }
+
+    H::new_con(handler, id, details, con).await;
 }
 
 /// Receives a single Message from the external Server and processes
@@ -36,89 +156,200 @@ struct SingleOptions<'a, R> {
 /// # Returns:
 /// * Ok(_) if everything went alright and as expected
 /// * Err(e) if any sort of Problem was encountered, more Details
-/// are then provided using the Error-Type
+///   are then provided using the Error-Type
 async fn receive_single<R, H, M>(
     opts: SingleOptions<'_, R>,
     handler: Arc<H>,
-    metrics: &M,
+    metrics: &Arc<M>,
 ) -> Result<(), ReceiveError>
 where
     R: ConnectionReader + Sized + Send + Sync,
     H: Handler + Send + Sync + 'static,
-    M: Metrics + Send + Sync,
+    M: Metrics + Send + Sync + 'static,
 {
-    let header = match opts.server_con.read_full(opts.head_buf).await {
-        Ok(_) => match MessageHeader::deserialize(opts.head_buf) {
-            Some(s) => s,
-            None => return Err(ReceiveError::DeserializingHeader),
-        },
+    let msg = match opts.server_con.read_msg(opts.codec, opts.buf).await {
+        Ok(m) => m,
         Err(e) => return Err(ReceiveError::ReceivingMessage(e)),
     };
+    let header = msg.get_header().clone();
 
     let id = header.get_id();
     let kind = header.get_kind();
     match kind {
         MessageType::Close => {
+            opts.settings.acknowledge(&header, opts.send_queue);
+
+            // The Close is forwarded to the Receiver of the Handler, which
+            // returns its Reason as an Error, so that the Handler knows why the
+            // Connection was closed
+            match msg.close_reason() {
+                Some(reason) => {
+                    debug!("Closing Connection {}: {}", id, reason);
+                    metrics.received_close(&reason);
+
+                    if let Some(stream) = opts.client_cons.get_clone(id) {
+                        let _ = stream.send(msg);
+                    }
+                }
+                None => {
+                    debug!("Closing Connection: {}", id);
+                }
+            };
             opts.client_cons.remove(id);
-            debug!("Closing Connection: {}", id);
 
             return Ok(());
         }
         MessageType::Data | MessageType::EOF => {}
         // A new connection should be established for the given ID
         MessageType::Connect => {
-            let mut details_buf = vec![0; header.get_length() as usize];
-            let details = match opts.server_con.read_full(&mut details_buf).await {
-                Ok(_) => match Details::deserialize(&mut details_buf) {
-                    Ok(d) => d,
-                    Err(e) => {
-                        error!("Parsing Connection-Details: {:?}", e);
-                        return Ok(());
-                    }
-                },
+            let details = match Details::deserialize(&mut msg.get_data().to_vec()) {
+                Ok(d) => d,
                 Err(e) => {
-                    error!("Reading Connection-Details: {:?}", e);
+                    error!("Parsing Connection-Details: {}", e);
                     return Ok(());
                 }
             };
 
+            let limit_reached = match opts.settings.max_connections {
+                Some(max) => opts.client_cons.len() >= max,
+                None => false,
+            };
+
             // Setup the send channel for requests for this user
             let (tx, stream_rx) = mpsc::stream();
             // Add the Connection to the current map of user-connection
             opts.client_cons.set(id, tx);
 
-            let handle_rx = OwnedReceiver::new(stream_rx);
-            let handle_tx = OwnedSender::new(id, opts.send_queue.clone(), opts.client_cons.clone());
+            let access = opts.settings.access_log.clone().map(|log| {
+                Arc::new(AccessTracker::new(
+                    log,
+                    opts.settings.port,
+                    id,
+                    *details.ip(),
+                ))
+            });
+            let idle = opts
+                .settings
+                .idle_timeout
+                .map(|_| Arc::new(IdleTimer::new()));
+            let handle_rx = OwnedReceiver::new(stream_rx)
+                .with_access(access.clone())
+                .with_idle(idle.clone());
+            let handle_tx = OwnedSender::new(
+                id,
+                opts.send_queue.clone(),
+                opts.client_cons.clone(),
+                opts.settings.close_reasons(),
+            )
+            .with_access(access.clone())
+            .with_idle(idle.clone())
+            .with_chunk_size(opts.settings.chunk_size)
+            .with_max_size(opts.settings.max_message_size);
+
+            if limit_reached {
+                reject_con(
+                    handle_tx,
+                    "Reached the maximum Number of Connections",
+                    opts.settings.acknowledges(),
+                );
+                return Ok(());
+            }
+
+            if let (Some(timeout), Some(idle)) = (opts.settings.idle_timeout, idle) {
+                let watch = IdleWatch::new(
+                    id,
+                    idle,
+                    opts.send_queue.clone(),
+                    opts.client_cons.clone(),
+                    opts.settings.close_reasons(),
+                )
+                .with_access(access);
+                let metrics = metrics.clone();
+                let port = opts.settings.port;
+                tokio::task::spawn(async move {
+                    if watch.run(timeout).await {
+                        metrics.user_timeout(port);
+                    }
+                });
+            }
 
             let handle_con = UserCon::new(handle_rx, handle_tx);
-            tokio::task::spawn(H::new_con(handler, id, details, handle_con));
+            tokio::task::spawn(instrument!(
+                accept_con(
+                    handler,
+                    id,
+                    details,
+                    handle_con,
+                    opts.send_queue.clone(),
+                    opts.settings.acknowledges(),
+                ),
+                tracing::info_span!("user", user_id = id, peer = %details.ip())
+            ));
 
             debug!("Established new Connection: {}", id);
 
             return Ok(());
         }
+        MessageType::Ping => {
+            if let Err(e) = opts.send_queue.send(Pinger::pong(msg.into_data())) {
+                error!("Sending Pong: {}", e);
+            }
+            return Ok(());
+        }
+        MessageType::Pong => {
+            if let Some(rtt) = opts.pinger.received_pong(msg.get_data()) {
+                metrics.rtt(rtt);
+            }
+            return Ok(());
+        }
+        MessageType::Ack => {
+            if let Some(sequencer) = opts.settings.sequencer.as_ref() {
+                sequencer.acked(id, msg.get_data());
+            }
+            return Ok(());
+        }
+        MessageType::Resume => {
+            if let Some(sequencer) = opts.settings.sequencer.as_ref() {
+                sequencer.resumed();
+            }
+            return Ok(());
+        }
         _ => {
             error!("Unexpected Message-Type: {:?}", kind);
             return Ok(());
         }
     };
 
-    // Handle all the metrics related stuff
-    metrics.received_msg();
-    metrics.recv_bytes(header.get_length());
-
-    let data_length = header.get_length() as usize;
-    let mut buf = vec![0; data_length];
+    opts.settings.acknowledge(&header, opts.send_queue);
 
-    let msg = match opts.server_con.read_full(&mut buf).await {
-        Ok(_) => Message::new(header, buf),
+    let wire_length = opts.codec.size(&header);
+    let msg = match decompress(
+        opts.settings.compression,
+        header,
+        msg.into_data(),
+        opts.codec.max_size(),
+    ) {
+        Ok(m) => m,
         Err(e) => {
-            error!("Receiving Data: {}", e);
-            opts.client_cons.remove(id);
+            // Only the single User-Connection is closed, as the Connection to
+            // the Server itself is still intact
+            error!("Decompressing Message for {}: {}", id, e);
+            let reason = CloseReason::with_text(CloseCode::Error, e.to_string());
+            if let Some((_, stream)) = opts.client_cons.remove(id) {
+                let _ = stream.send(reason.clone().into_message(id));
+            }
+            if let Err(e) = opts.send_queue.send(reason.into_message(id)) {
+                error!("Sending Close for {}: {}", id, e);
+            }
             return Ok(());
         }
     };
 
+    // Handle all the metrics related stuff
+    metrics.received_msg();
+    metrics.recv_bytes(msg.get_header().get_length());
+    metrics.recv_wire_bytes(wire_length as u64);
+
     let con_queue = match opts.client_cons.get_clone(id) {
         Some(q) => q,
         None => {
@@ -135,6 +366,19 @@ where
     Ok(())
 }
 
+/// Closes all the User-Connections with an Error, once the Connection to the
+/// Server was lost
+///
+/// The Receivers of the Handlers then return the Error-Reason, instead of
+/// any further Messages
+pub fn close_all(client_cons: &Connections<mpsc::StreamWriter<Message>>) {
+    let reason = CloseReason::with_text(CloseCode::Error, "Lost the Connection to the Server");
+    for (id, con) in client_cons.entries() {
+        client_cons.remove(id);
+        let _ = con.send(reason.clone().into_message(id));
+    }
+}
+
 /// Receives all the messages from the server
 ///
 /// Then adds the message to the matching connection queue.
@@ -146,30 +390,38 @@ where
 /// * `server_con`: The Connection to the external Server
 /// * `send_queue`: The Queue of messages that should be send to the Server
 /// * `client_cons`: A Collection of Clients that are all listening on this Connection
+/// * `pinger`: The Pinger used for the Connection to the external Server
+/// * `settings`: The Settings for the Connection
 /// * `start_handler`: The Function used to start a new Handler when a new Connection is received
 /// * `handler_data`: The Data that will be passed to the `start_handler` function
 pub async fn receiver<R, H, M>(
     mut server_con: R,
     send_queue: tokio::sync::mpsc::UnboundedSender<Message>,
     client_cons: std::sync::Arc<Connections<mpsc::StreamWriter<Message>>>,
+    pinger: Arc<Pinger>,
+    settings: Settings,
     handler: Arc<H>,
     metrics: Arc<M>,
 ) where
     R: ConnectionReader + Sized + Send + Sync,
     H: Handler + Send + Sync + 'static,
-    M: Metrics + Send + Sync,
+    M: Metrics + Send + Sync + 'static,
 {
-    let mut head_buf = [0; 13];
+    let mut codec = settings.codec();
+    let mut buf = ReadBuffer::new();
 
     loop {
         let opts = SingleOptions {
             server_con: &mut server_con,
             send_queue: &send_queue,
             client_cons: &client_cons,
-            head_buf: &mut head_buf,
+            pinger: &pinger,
+            settings: &settings,
+            codec: &mut codec,
+            buf: &mut buf,
         };
-        if let Err(e) = receive_single(opts, handler.clone(), metrics.as_ref()).await {
-            error!("Receiving: {:?}", e);
+        if let Err(e) = receive_single(opts, handler.clone(), &metrics).await {
+            error!("Receiving: {}", e);
             break;
         }
     }
@@ -183,16 +435,31 @@ mod tests {
     use crate::client::mocks as client_mocks;
     use crate::general::mocks;
     use crate::metrics::Empty;
+    use crate::streams::error::RecvError;
+
+    fn settings() -> Settings {
+        Settings {
+            server_version: crate::PROTOCOL_VERSION,
+            max_connections: None,
+            port: 8080,
+            access_log: None,
+            idle_timeout: None,
+            chunk_size: ChunkSize::default(),
+            compression: None,
+            max_message_size: MessageCodec::DEFAULT_MAX_SIZE,
+            sequencer: None,
+        }
+    }
 
     #[tokio::test]
     async fn valid_sends_data_to_correct_handler() {
         let id = 13;
 
         let mut tmp_reader = mocks::MockReader::new();
-        tmp_reader.add_message(Message::new(
-            MessageHeader::new(id, MessageType::Data, 10),
-            vec![3; 10],
-        ));
+        tmp_reader.add_formatted(
+            Message::new(MessageHeader::new(id, MessageType::Data, 10), vec![3; 10]),
+            HeaderFormat::V2,
+        );
 
         let (queue_tx, _) = tokio::sync::mpsc::unbounded_channel();
 
@@ -201,17 +468,20 @@ mod tests {
         let (client_tx, mut client_rx) = mpsc::stream();
         client_cons.set(id, client_tx);
 
-        let mut head_buf = [0; 13];
+        let mut codec = MessageCodec::new(HeaderFormat::V2);
 
         let result = receive_single(
             SingleOptions {
                 server_con: &mut tmp_reader,
                 send_queue: &queue_tx,
                 client_cons: &client_cons,
-                head_buf: &mut head_buf,
+                pinger: &Pinger::new(),
+                settings: &settings(),
+                codec: &mut codec,
+                buf: &mut ReadBuffer::new(),
             },
             Arc::new(client_mocks::EmptyHandler::new()),
-            &Empty::new(),
+            &Arc::new(Empty::new()),
         )
         .await;
 
@@ -226,6 +496,102 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "zstd")]
+    #[tokio::test]
+    async fn corrupt_compressed_message() {
+        let valid = Message::new(MessageHeader::new(16, MessageType::Data, 256), vec![7; 256]);
+
+        let mut tmp_reader = mocks::MockReader::new();
+        tmp_reader.add_formatted(
+            Message::new(
+                MessageHeader::new(15, MessageType::Data, 16).with_compressed(true),
+                vec![0xff; 16],
+            ),
+            HeaderFormat::V2,
+        );
+        tmp_reader.add_formatted(
+            Compression::Zstd.compress(valid.clone(), MessageCodec::DEFAULT_MAX_SIZE),
+            HeaderFormat::V2,
+        );
+
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        let client_cons = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        let (corrupt_tx, mut corrupt_rx) = mpsc::stream();
+        client_cons.set(15, corrupt_tx);
+        let (valid_tx, mut valid_rx) = mpsc::stream();
+        client_cons.set(16, valid_tx);
+
+        let settings = Settings {
+            compression: Some(Compression::Zstd),
+            ..settings()
+        };
+        let mut codec = MessageCodec::new(HeaderFormat::V2);
+        for _ in 0..2 {
+            let result = receive_single(
+                SingleOptions {
+                    server_con: &mut tmp_reader,
+                    send_queue: &queue_tx,
+                    client_cons: &client_cons,
+                    pinger: &Pinger::new(),
+                    settings: &settings,
+                    codec: &mut codec,
+                    buf: &mut ReadBuffer::new(),
+                },
+                Arc::new(client_mocks::EmptyHandler::new()),
+                &Arc::new(Empty::new()),
+            )
+            .await;
+            assert_eq!(true, result.is_ok());
+        }
+
+        // Only the User-Connection of the corrupt Message is closed on both
+        // Sides
+        let close = |msg: Option<Message>| msg.and_then(|m| m.close_reason()).map(|r| r.code());
+        assert_eq!(Some(CloseCode::Error), close(corrupt_rx.recv().await.ok()));
+        assert_eq!(Some(CloseCode::Error), close(queue_rx.recv().await));
+        assert_eq!(true, client_cons.get_clone(15).is_none());
+
+        assert_eq!(Ok(valid), valid_rx.recv().await);
+        assert_eq!(true, client_cons.get_clone(16).is_some());
+    }
+
+    #[tokio::test]
+    async fn old_server_headers() {
+        let id = 13;
+        let msg = || Message::new(MessageHeader::new(id, MessageType::Data, 10), vec![3; 10]);
+
+        // Servers before Version 8 still use the original Headers
+        let mut tmp_reader = mocks::MockReader::new();
+        tmp_reader.add_formatted(msg(), HeaderFormat::V1);
+
+        let (queue_tx, _) = tokio::sync::mpsc::unbounded_channel();
+        let client_cons = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        let (client_tx, mut client_rx) = mpsc::stream();
+        client_cons.set(id, client_tx);
+
+        let settings = Settings {
+            server_version: 7,
+            ..settings()
+        };
+        let result = receive_single(
+            SingleOptions {
+                server_con: &mut tmp_reader,
+                send_queue: &queue_tx,
+                client_cons: &client_cons,
+                pinger: &Pinger::new(),
+                codec: &mut MessageCodec::new(settings.header_format()),
+                settings: &settings,
+                buf: &mut ReadBuffer::new(),
+            },
+            Arc::new(client_mocks::EmptyHandler::new()),
+            &Arc::new(Empty::new()),
+        )
+        .await;
+
+        assert_eq!(true, result.is_ok());
+        assert_eq!(Ok(msg()), client_rx.recv().await);
+    }
+
     #[tokio::test]
     async fn valid_establish_connection() {
         let id = 13;
@@ -233,26 +599,32 @@ mod tests {
         let details = Details::new(IpAddr::V4(Ipv4Addr::from([0, 0, 0, 0]))).serialize();
 
         let mut tmp_reader = mocks::MockReader::new();
-        tmp_reader.add_message(Message::new(
-            MessageHeader::new(id, MessageType::Connect, details.len() as u64),
-            details,
-        ));
+        tmp_reader.add_formatted(
+            Message::new(
+                MessageHeader::new(id, MessageType::Connect, details.len() as u64),
+                details,
+            ),
+            HeaderFormat::V2,
+        );
 
         let (queue_tx, _) = tokio::sync::mpsc::unbounded_channel();
 
         let client_cons = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
 
-        let mut head_buf = [0; 13];
+        let mut codec = MessageCodec::new(HeaderFormat::V2);
 
         let result = receive_single(
             SingleOptions {
                 server_con: &mut tmp_reader,
                 send_queue: &queue_tx,
                 client_cons: &client_cons,
-                head_buf: &mut head_buf,
+                pinger: &Pinger::new(),
+                settings: &settings(),
+                codec: &mut codec,
+                buf: &mut ReadBuffer::new(),
             },
             Arc::new(client_mocks::EmptyHandler::new()),
-            &Empty::new(),
+            &Arc::new(Empty::new()),
         )
         .await;
 
@@ -261,4 +633,248 @@ mod tests {
         let connection_queue = client_cons.get_clone(id);
         assert_eq!(true, connection_queue.is_some());
     }
+
+    #[tokio::test]
+    async fn ping_responds_with_pong() {
+        let mut tmp_reader = mocks::MockReader::new();
+        tmp_reader.add_formatted(
+            Message::new(MessageHeader::new(0, MessageType::Ping, 8), vec![4; 8]),
+            HeaderFormat::V2,
+        );
+
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let client_cons = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+
+        let mut codec = MessageCodec::new(HeaderFormat::V2);
+
+        let result = receive_single(
+            SingleOptions {
+                server_con: &mut tmp_reader,
+                send_queue: &queue_tx,
+                client_cons: &client_cons,
+                pinger: &Pinger::new(),
+                settings: &settings(),
+                codec: &mut codec,
+                buf: &mut ReadBuffer::new(),
+            },
+            Arc::new(client_mocks::EmptyHandler::new()),
+            &Arc::new(Empty::new()),
+        )
+        .await;
+
+        assert_eq!(true, result.is_ok());
+
+        assert_eq!(
+            Some(Message::new(
+                MessageHeader::new(0, MessageType::Pong, 8),
+                vec![4; 8],
+            )),
+            queue_rx.recv().await
+        );
+    }
+
+    #[tokio::test]
+    async fn establish_connection_sends_accept() {
+        let id = 13;
+
+        let details = Details::new(IpAddr::V4(Ipv4Addr::from([0, 0, 0, 0]))).serialize();
+
+        let mut tmp_reader = mocks::MockReader::new();
+        tmp_reader.add_formatted(
+            Message::new(
+                MessageHeader::new(id, MessageType::Connect, details.len() as u64),
+                details,
+            ),
+            HeaderFormat::V2,
+        );
+
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let client_cons = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+
+        let mut codec = MessageCodec::new(HeaderFormat::V2);
+
+        let result = receive_single(
+            SingleOptions {
+                server_con: &mut tmp_reader,
+                send_queue: &queue_tx,
+                client_cons: &client_cons,
+                pinger: &Pinger::new(),
+                settings: &settings(),
+                codec: &mut codec,
+                buf: &mut ReadBuffer::new(),
+            },
+            Arc::new(client_mocks::EmptyHandler::new()),
+            &Arc::new(Empty::new()),
+        )
+        .await;
+
+        assert_eq!(true, result.is_ok());
+        assert_eq!(
+            Some(Message::new(
+                MessageHeader::new(id, MessageType::Accept, 0),
+                vec![],
+            )),
+            queue_rx.recv().await
+        );
+    }
+
+    #[tokio::test]
+    async fn establish_connection_over_limit() {
+        let id = 13;
+
+        let details = Details::new(IpAddr::V4(Ipv4Addr::from([0, 0, 0, 0]))).serialize();
+
+        let mut tmp_reader = mocks::MockReader::new();
+        tmp_reader.add_formatted(
+            Message::new(
+                MessageHeader::new(id, MessageType::Connect, details.len() as u64),
+                details,
+            ),
+            HeaderFormat::V2,
+        );
+
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let client_cons = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        let (other_tx, _other_rx) = mpsc::stream();
+        client_cons.set(12, other_tx);
+
+        let mut codec = MessageCodec::new(HeaderFormat::V2);
+
+        let result = receive_single(
+            SingleOptions {
+                server_con: &mut tmp_reader,
+                send_queue: &queue_tx,
+                client_cons: &client_cons,
+                pinger: &Pinger::new(),
+                settings: &Settings {
+                    server_version: crate::PROTOCOL_VERSION,
+                    max_connections: Some(1),
+                    port: 8080,
+                    access_log: None,
+                    idle_timeout: None,
+                    chunk_size: ChunkSize::default(),
+                    compression: None,
+                    max_message_size: MessageCodec::DEFAULT_MAX_SIZE,
+                    sequencer: None,
+                },
+                codec: &mut codec,
+                buf: &mut ReadBuffer::new(),
+            },
+            Arc::new(client_mocks::EmptyHandler::new()),
+            &Arc::new(Empty::new()),
+        )
+        .await;
+
+        assert_eq!(true, result.is_ok());
+
+        let response = queue_rx.recv().await.unwrap();
+        assert_eq!(&MessageType::Reject, response.get_header().get_kind());
+        assert_eq!(id, response.get_header().get_id());
+        assert_eq!(true, client_cons.get_clone(id).is_none());
+    }
+
+    #[tokio::test]
+    async fn close_with_reason_forwarded() {
+        let id = 13;
+        let close =
+            || CloseReason::with_text(crate::message::CloseCode::Timeout, "idle").into_message(id);
+
+        let mut tmp_reader = mocks::MockReader::new();
+        tmp_reader.add_formatted(close(), HeaderFormat::V2);
+
+        let (queue_tx, _) = tokio::sync::mpsc::unbounded_channel();
+
+        let client_cons = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+
+        let (client_tx, mut client_rx) = mpsc::stream();
+        client_cons.set(id, client_tx);
+
+        let mut codec = MessageCodec::new(HeaderFormat::V2);
+
+        let result = receive_single(
+            SingleOptions {
+                server_con: &mut tmp_reader,
+                send_queue: &queue_tx,
+                client_cons: &client_cons,
+                pinger: &Pinger::new(),
+                settings: &settings(),
+                codec: &mut codec,
+                buf: &mut ReadBuffer::new(),
+            },
+            Arc::new(client_mocks::EmptyHandler::new()),
+            &Arc::new(Empty::new()),
+        )
+        .await;
+
+        assert_eq!(true, result.is_ok());
+        assert_eq!(true, client_cons.get_clone(id).is_none());
+
+        let received = client_rx.recv().await.unwrap();
+        assert_eq!(close(), received);
+        assert_eq!(
+            Some(CloseReason::with_text(
+                crate::message::CloseCode::Timeout,
+                "idle"
+            )),
+            received.close_reason()
+        );
+        assert_eq!(true, client_rx.recv().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn close_all_stops_handlers() {
+        let id = 13;
+
+        let details = Details::new(IpAddr::V4(Ipv4Addr::from([0, 0, 0, 0]))).serialize();
+
+        let mut tmp_reader = mocks::MockReader::new();
+        tmp_reader.add_formatted(
+            Message::new(
+                MessageHeader::new(id, MessageType::Connect, details.len() as u64),
+                details,
+            ),
+            HeaderFormat::V2,
+        );
+
+        let (queue_tx, _queue_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let client_cons = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        let handler = Arc::new(client_mocks::RecordingHandler::default());
+
+        let mut codec = MessageCodec::new(HeaderFormat::V2);
+
+        let result = receive_single(
+            SingleOptions {
+                server_con: &mut tmp_reader,
+                send_queue: &queue_tx,
+                client_cons: &client_cons,
+                pinger: &Pinger::new(),
+                settings: &settings(),
+                codec: &mut codec,
+                buf: &mut ReadBuffer::new(),
+            },
+            handler.clone(),
+            &Arc::new(Empty::new()),
+        )
+        .await;
+        assert_eq!(true, result.is_ok());
+
+        close_all(&client_cons);
+        assert_eq!(0, client_cons.len());
+
+        tokio::time::timeout(std::time::Duration::from_secs(5), handler.done.notified())
+            .await
+            .unwrap();
+        assert_eq!(true, handler.messages.lock().unwrap().is_empty());
+        assert_eq!(
+            Some(RecvError::ClosedWith(CloseReason::with_text(
+                CloseCode::Error,
+                "Lost the Connection to the Server"
+            ))),
+            handler.error.lock().unwrap().take()
+        );
+    }
 }
@@ -1,52 +1,167 @@
 use std::sync::Arc;
 
-use crate::{general::ConnectionWriter, message::Message, metrics::Metrics};
+use tokio::sync::oneshot;
+
+use crate::{
+    connections::Connections,
+    general::{
+        recv_batch, reject_invalid, ConnectionWriter, ResumeError, Sequencer, Throttle, MAX_BATCH,
+    },
+    message::{original_length, CodecError, Compression, Message, MessageCodec, MessageType},
+    metrics::Metrics,
+    streams::mpsc,
+};
+
+/// The Queue of Messages that should be send to the Server, which is shared
+/// by all the Connections of the same Session
+pub type SendQueue = Arc<tokio::sync::Mutex<tokio::sync::mpsc::UnboundedReceiver<Message>>>;
 
 #[derive(Debug)]
 enum SendError {
     ReceivingMessage,
-    Sending(std::io::Error),
+    Sending(CodecError),
+    Resuming(ResumeError),
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::ReceivingMessage => write!(f, "The Queue has been closed"),
+            SendError::Sending(e) => write!(f, "Writing the Messages: {}", e),
+            SendError::Resuming(e) => write!(f, "Resuming the Session: {}", e),
+        }
+    }
 }
 
-async fn send_single<C, M>(
+/// Sends all the Messages, that are waiting in the Queue, to the Server at
+/// once, except that User-Data which has to wait for the Throttle is written
+/// separately, so that the Messages before it are not delayed
+#[allow(clippy::too_many_arguments)]
+async fn send_batch<C, M>(
     con: &mut C,
     queue: &mut tokio::sync::mpsc::UnboundedReceiver<Message>,
-    head_buf: &mut [u8; 13],
+    user_cons: &Connections<mpsc::StreamWriter<Message>>,
+    batch: &mut Vec<Message>,
+    codec: &MessageCodec,
+    head_buf: &mut Vec<u8>,
     metrics: &M,
+    throttle: &Throttle,
+    sequencer: Option<&Sequencer>,
+    compression: Option<Compression>,
 ) -> Result<(), SendError>
 where
     C: ConnectionWriter + Send,
     M: Metrics + Send + Sync,
 {
-    let msg = match queue.recv().await {
-        Some(m) => m,
-        None => return Err(SendError::ReceivingMessage),
-    };
+    batch.clear();
+    if !recv_batch(queue, batch).await {
+        return Err(SendError::ReceivingMessage);
+    }
+    reject_invalid(batch, codec, user_cons);
+    // This needs to be recorded right away, as the Messages would be lost if
+    // the Task is stopped before they were written
+    if let Some(sequencer) = sequencer {
+        for msg in batch.iter() {
+            sequencer.sent(msg);
+        }
+    }
 
-    if let Err(e) = con.write_msg(&msg, head_buf).await {
+    let mut written = 0;
+    for index in 0..batch.len() {
+        // Only the actual User-Data is limited, so that Control-Messages like
+        // Pings are not delayed by it
+        let header = batch[index].get_header();
+        if *header.get_kind() != MessageType::Data {
+            continue;
+        }
+        if throttle.is_limited() && index > written {
+            if let Err(e) = con
+                .write_msgs(&batch[written..index], codec, head_buf)
+                .await
+            {
+                return Err(SendError::Sending(e));
+            }
+            written = index;
+        }
+        // The Limit applies to the Data of the Users, regardless of how much
+        // Space it takes up once it is compressed
+        throttle.acquire(header.get_length()).await;
+        if let Some(compression) = compression {
+            batch[index] = compression.compress(batch[index].clone(), codec.max_size());
+        }
+    }
+    if let Err(e) = con.write_msgs(&batch[written..], codec, head_buf).await {
         return Err(SendError::Sending(e));
     }
 
-    metrics.send_msg();
-    metrics.send_bytes(msg.get_header().get_length());
+    for msg in batch.drain(..) {
+        metrics.send_msg();
+        metrics.send_bytes(original_length(&msg));
+        metrics.send_wire_bytes(codec.size(msg.get_header()) as u64);
+    }
 
     Ok(())
 }
 
 /// Sends all the messages to the server
+///
+/// Params:
+/// * server_con: The Connection to the Server
+/// * queue: The Queue of Messages that should be send to the Server
+/// * user_cons: The User-Connections, which are closed if their Messages can
+///   not be send
+/// * metrics: The Metrics-Collector to use
+/// * throttle: Limits the Rate at which User-Data is send to the Server
+/// * sequencer: Keeps track of the Messages of the Session, if there is one
+/// * compression: The Compression negotiated with the Server, if any
+/// * codec: The Codec for the Messages on the Connection
+/// * resumed: Notified once the Server resumed its side of the Session, if
+///   this Connection resumes a Session
+#[allow(clippy::too_many_arguments)]
 pub async fn sender<M>(
     mut server_con: tokio::net::tcp::OwnedWriteHalf,
-    mut queue: tokio::sync::mpsc::UnboundedReceiver<Message>,
+    queue: SendQueue,
+    user_cons: Arc<Connections<mpsc::StreamWriter<Message>>>,
     metrics: Arc<M>,
+    throttle: Throttle,
+    sequencer: Option<Arc<Sequencer>>,
+    compression: Option<Compression>,
+    codec: MessageCodec,
+    resumed: Option<oneshot::Receiver<()>>,
 ) where
     M: Metrics + Send + Sync,
 {
-    let mut h_data = [0; 13];
+    let mut queue = queue.lock().await;
+    let mut batch = Vec::with_capacity(MAX_BATCH);
+    let mut head_buf = Vec::new();
+
+    if let (Some(sequencer), Some(resumed)) = (sequencer.as_ref(), resumed) {
+        if let Err(e) = sequencer
+            .resume(&mut server_con, resumed, &codec, &mut head_buf)
+            .await
+        {
+            let e = SendError::Resuming(e);
+            error!("Resuming Session: {}", e);
+            return;
+        }
+    }
+
     loop {
-        if let Err(e) =
-            send_single(&mut server_con, &mut queue, &mut h_data, metrics.as_ref()).await
+        if let Err(e) = send_batch(
+            &mut server_con,
+            &mut queue,
+            &user_cons,
+            &mut batch,
+            &codec,
+            &mut head_buf,
+            metrics.as_ref(),
+            &throttle,
+            sequencer.as_deref(),
+            compression,
+        )
+        .await
         {
-            error!("Sending-Single: {:?}", e);
+            error!("Sending-Batch: {}", e);
             return;
         }
     }
@@ -56,14 +171,15 @@ pub async fn sender<M>(
 mod tests {
     use super::*;
     use crate::general::mocks;
-    use crate::message::{MessageHeader, MessageType};
+    use crate::message::{HeaderFormat, MessageHeader};
     use crate::metrics::Empty;
 
     #[tokio::test]
-    async fn valid_send_single() {
+    async fn valid_send_batch() {
         let mut mock_connection = mocks::MockWriter::new();
         let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
-        let mut head_buf = [0; 13];
+        let mut batch = Vec::new();
+        let mut head_buf = Vec::new();
 
         let id = 12;
         queue_tx
@@ -75,11 +191,17 @@ mod tests {
 
         assert_eq!(
             true,
-            send_single(
+            send_batch(
                 &mut mock_connection,
                 &mut queue_rx,
+                &Connections::new(),
+                &mut batch,
+                &MessageCodec::new(HeaderFormat::V1),
                 &mut head_buf,
-                &Empty::new()
+                &Empty::new(),
+                &Throttle::new(),
+                None,
+                None
             )
             .await
             .is_ok()
@@ -90,4 +212,134 @@ mod tests {
             mock_connection.chunks()
         );
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn throttled_send_batch() {
+        let mut mock_connection = mocks::MockWriter::new();
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut batch = Vec::new();
+        let mut head_buf = Vec::new();
+        let throttle = Throttle::new().with(Some(Arc::new(crate::general::TokenBucket::new(
+            crate::RateLimit::new(10),
+        ))));
+
+        for _ in 0..2 {
+            queue_tx
+                .send(Message::new(
+                    MessageHeader::new(12, MessageType::Data, 10),
+                    vec![2; 10],
+                ))
+                .unwrap();
+        }
+        queue_tx
+            .send(Message::new(
+                MessageHeader::new(0, MessageType::Ping, 0),
+                vec![],
+            ))
+            .unwrap();
+
+        let start = tokio::time::Instant::now();
+        assert_eq!(
+            true,
+            send_batch(
+                &mut mock_connection,
+                &mut queue_rx,
+                &Connections::new(),
+                &mut batch,
+                &MessageCodec::new(HeaderFormat::V1),
+                &mut head_buf,
+                &Empty::new(),
+                &throttle,
+                None,
+                None
+            )
+            .await
+            .is_ok()
+        );
+
+        assert_eq!(std::time::Duration::from_secs(1), start.elapsed());
+        assert_eq!(6, mock_connection.chunks().len());
+    }
+
+    #[tokio::test]
+    async fn sequenced_send_batch() {
+        let mut mock_connection = mocks::MockWriter::new();
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut batch = Vec::new();
+        let mut head_buf = Vec::new();
+        let sequencer = Sequencer::new();
+
+        let data = || Message::new(MessageHeader::new(12, MessageType::Data, 5), vec![2; 5]);
+        queue_tx.send(data()).unwrap();
+        queue_tx
+            .send(Message::new(
+                MessageHeader::new(0, MessageType::Ping, 0),
+                vec![],
+            ))
+            .unwrap();
+
+        assert_eq!(
+            true,
+            send_batch(
+                &mut mock_connection,
+                &mut queue_rx,
+                &Connections::new(),
+                &mut batch,
+                &MessageCodec::new(HeaderFormat::V1),
+                &mut head_buf,
+                &Empty::new(),
+                &Throttle::new(),
+                Some(&sequencer),
+                None
+            )
+            .await
+            .is_ok()
+        );
+
+        assert_eq!(4, mock_connection.chunks().len());
+        assert_eq!(vec![data()], sequencer.unacked());
+    }
+
+    #[cfg(feature = "flate2")]
+    #[tokio::test]
+    async fn compressed_send_batch() {
+        let mut mock_connection = mocks::MockWriter::new();
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        let sequencer = Sequencer::new();
+
+        let data = || Message::new(MessageHeader::new(12, MessageType::Data, 256), vec![2; 256]);
+        queue_tx.send(data()).unwrap();
+        queue_tx
+            .send(Message::new(
+                MessageHeader::new(0, MessageType::Ping, 0),
+                vec![],
+            ))
+            .unwrap();
+
+        assert_eq!(
+            true,
+            send_batch(
+                &mut mock_connection,
+                &mut queue_rx,
+                &Connections::new(),
+                &mut Vec::new(),
+                &MessageCodec::new(HeaderFormat::V2),
+                &mut Vec::new(),
+                &Empty::new(),
+                &Throttle::new(),
+                Some(&sequencer),
+                Some(Compression::Deflate)
+            )
+            .await
+            .is_ok()
+        );
+
+        let chunks = mock_connection.chunks();
+        assert_eq!(4, chunks.len());
+        // The Type of the Data-Message is marked as compressed
+        assert_eq!(0x80 | 3, chunks[0][0]);
+        assert_eq!(true, chunks[1].len() < 256);
+        assert_eq!(vec![11, 0, 0], chunks[2]);
+        assert_eq!(vec![data()], sequencer.unacked());
+    }
 }
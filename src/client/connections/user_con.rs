@@ -1,25 +1,226 @@
 //! Contains some more specific Details for regarding the User-Connections
 
-use std::sync::Arc;
+use std::net::IpAddr;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, SystemTime};
 
 use super::mpsc;
 use crate::{
+    accesslog::{AccessLog, AccessRecord},
     client::{Receiver, Sender},
     connections::Connections,
-    message::{Message, MessageHeader, MessageType},
+    general::{ChunkSize, ChunkSizer, IdleTimer},
+    message::{
+        CloseCode, CloseReason, Message, MessageCodec, MessageHeader, MessageType, ReadBuffer,
+    },
     streams::error::RecvError,
 };
 
 use async_trait::async_trait;
+use bytes::Bytes;
+use tokio::io::AsyncRead;
+
+/// Keeps track of a single User-Connection, to write its Access-Record once
+/// the Connection is closed
+pub(crate) struct AccessTracker {
+    log: AccessLog,
+    port: u16,
+    user_ip: IpAddr,
+    connection_id: u32,
+    start: tokio::time::Instant,
+    started_at: SystemTime,
+    recv_bytes: AtomicU64,
+    send_bytes: AtomicU64,
+    reason: Mutex<Option<CloseReason>>,
+    finished: AtomicBool,
+}
+
+impl AccessTracker {
+    /// Creates a new Tracker for a Connection that was just opened
+    ///
+    /// Params:
+    /// * log: The Access-Log to write the Record to
+    /// * port: The external Port of the Client
+    /// * connection_id: The ID of the User-Connection
+    /// * user_ip: The IP of the User
+    pub fn new(log: AccessLog, port: u16, connection_id: u32, user_ip: IpAddr) -> Self {
+        Self {
+            log,
+            port,
+            user_ip,
+            connection_id,
+            start: tokio::time::Instant::now(),
+            started_at: SystemTime::now(),
+            recv_bytes: AtomicU64::new(0),
+            send_bytes: AtomicU64::new(0),
+            reason: Mutex::new(None),
+            finished: AtomicBool::new(false),
+        }
+    }
+
+    /// Records Data that was received from the User
+    fn received(&self, bytes: u64) {
+        self.recv_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+    /// Records Data that was send to the User
+    fn sent(&self, bytes: u64) {
+        self.send_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Records the Reason for closing the Connection, only the first Reason
+    /// is kept
+    fn close(&self, reason: CloseReason) {
+        let mut current = self.reason.lock().unwrap();
+        if current.is_none() {
+            *current = Some(reason);
+        }
+    }
+
+    /// Marks the Connection as done, without writing a Record for it
+    fn discard(&self) {
+        self.finished.store(true, Ordering::SeqCst);
+    }
+
+    /// Writes the Record for the Connection, unless it was already written
+    /// or discarded before
+    fn finish(&self) {
+        if self.finished.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let reason = self
+            .reason
+            .lock()
+            .unwrap()
+            .take()
+            .unwrap_or_else(|| CloseReason::new(CloseCode::Normal));
+        self.log.log(&AccessRecord {
+            port: self.port,
+            user_ip: self.user_ip,
+            user_port: None,
+            client_id: None,
+            connection_id: self.connection_id,
+            start: self.started_at,
+            end: self.started_at + self.start.elapsed(),
+            recv_bytes: self.recv_bytes.load(Ordering::Relaxed),
+            send_bytes: self.send_bytes.load(Ordering::Relaxed),
+            reason,
+        });
+    }
+}
+
+/// Creates the Close-Message for the given Connection, which only contains
+/// the Reason if the Server supports it
+fn close_message(id: u32, close_reasons: bool, reason: CloseReason) -> Message {
+    if !close_reasons {
+        return Message::new(MessageHeader::new(id, MessageType::Close, 0), vec![]);
+    }
+    reason.into_message(id)
+}
+
+/// Closes a single User-Connection, once it was idle for too long
+pub(crate) struct IdleWatch {
+    id: u32,
+    timer: Arc<IdleTimer>,
+    tx: tokio::sync::mpsc::UnboundedSender<Message>,
+    all_client_cons: Arc<Connections<mpsc::StreamWriter<Message>>>,
+    close_reasons: bool,
+    access: Option<Arc<AccessTracker>>,
+}
+
+impl IdleWatch {
+    /// Creates a new Watch for the Connection
+    ///
+    /// Params:
+    /// * id: The ID of the User-Connection
+    /// * timer: The Timer shared with the Halves of the Connection
+    /// * tx: The Queue of Messages going to the Server
+    /// * cons: All the current User-Connections
+    /// * close_reasons: Whether or not the Server supports Close-Reasons
+    pub fn new(
+        id: u32,
+        timer: Arc<IdleTimer>,
+        tx: tokio::sync::mpsc::UnboundedSender<Message>,
+        cons: Arc<Connections<mpsc::StreamWriter<Message>>>,
+        close_reasons: bool,
+    ) -> Self {
+        Self {
+            id,
+            timer,
+            tx,
+            all_client_cons: cons,
+            close_reasons,
+            access: None,
+        }
+    }
+
+    /// Records the Reason in the Tracker, if the Connection is closed
+    pub fn with_access(mut self, access: Option<Arc<AccessTracker>>) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// Waits until the Connection was idle for the Timeout and then closes it,
+    /// by sending a Close to both the Handler and the Server
+    ///
+    /// Returns:
+    /// Whether or not the Connection was closed because of the Timeout
+    pub async fn run(self, timeout: Duration) -> bool {
+        if !self.timer.expired(timeout).await {
+            return false;
+        }
+
+        let (_, stream) = match self.all_client_cons.remove(self.id) {
+            Some(c) => c,
+            None => return false,
+        };
+        debug!("[{}] Connection was idle for {:?}", self.id, timeout);
+
+        let reason = CloseReason::with_text(CloseCode::Timeout, "Connection was idle");
+        if let Some(access) = self.access.as_ref() {
+            access.close(reason.clone());
+        }
+
+        if let Err(e) = stream.send(reason.clone().into_message(self.id)) {
+            error!("Closing Connection {} for Handler: {}", self.id, e);
+        }
+        let close_msg = close_message(self.id, self.close_reasons, reason);
+        if let Err(e) = self.tx.send(close_msg) {
+            error!("Sending Close-Message for {}: {}", self.id, e);
+        }
+        true
+    }
+}
 
 /// The owned Version of the Receiver-Half of a User-Connection
 pub struct OwnedReceiver {
     rx: mpsc::StreamReader<Message>,
+    access: Option<Arc<AccessTracker>>,
+    idle: Option<Arc<IdleTimer>>,
 }
 
 impl OwnedReceiver {
     pub(crate) fn new(rx: mpsc::StreamReader<Message>) -> Self {
-        Self { rx }
+        Self {
+            rx,
+            access: None,
+            idle: None,
+        }
+    }
+
+    /// Records all the received Data as Activity in the Timer
+    pub(crate) fn with_idle(mut self, idle: Option<Arc<IdleTimer>>) -> Self {
+        self.idle = idle;
+        self
+    }
+
+    /// Records the Data received over this Connection in the Tracker
+    pub(crate) fn with_access(mut self, access: Option<Arc<AccessTracker>>) -> Self {
+        self.access = access;
+        self
     }
 }
 
@@ -28,7 +229,28 @@ impl Receiver for OwnedReceiver {
     type ReceivingError = RecvError;
 
     async fn recv_msg(&mut self) -> Result<Message, Self::ReceivingError> {
-        self.rx.recv().await
+        let msg = self.rx.recv().await?;
+
+        // The Close is never handed to the Handler as Data, only its Reason
+        if msg.is_close() {
+            let reason = match msg.close_reason() {
+                Some(r) => r,
+                None => return Err(RecvError::Closed),
+            };
+            if let Some(access) = self.access.as_ref() {
+                access.close(reason.clone());
+            }
+            return Err(RecvError::ClosedWith(reason));
+        }
+
+        if let Some(idle) = self.idle.as_ref() {
+            idle.touch();
+        }
+        if let Some(access) = self.access.as_ref() {
+            access.received(msg.get_data().len() as u64);
+        }
+
+        Ok(msg)
     }
 }
 
@@ -37,6 +259,14 @@ pub struct OwnedSender {
     id: u32,
     tx: tokio::sync::mpsc::UnboundedSender<Message>,
     all_client_cons: Arc<Connections<mpsc::StreamWriter<Message>>>,
+    /// Whether or not the Server supports Reasons in Close-Messages
+    close_reasons: bool,
+    access: Option<Arc<AccessTracker>>,
+    idle: Option<Arc<IdleTimer>>,
+    /// The Size of the Chunks used when forwarding Data
+    chunk_size: ChunkSize,
+    /// The maximum Size of the Data of a single Message
+    max_size: usize,
 }
 
 impl OwnedSender {
@@ -44,20 +274,149 @@ impl OwnedSender {
         id: u32,
         tx: tokio::sync::mpsc::UnboundedSender<Message>,
         cons: std::sync::Arc<Connections<mpsc::StreamWriter<Message>>>,
+        close_reasons: bool,
     ) -> Self {
         Self {
             id,
             tx,
             all_client_cons: cons,
+            close_reasons,
+            access: None,
+            idle: None,
+            chunk_size: ChunkSize::default(),
+            max_size: MessageCodec::DEFAULT_MAX_SIZE,
+        }
+    }
+
+    /// Sets the Size of the Chunks in which Data is read, when forwarding it
+    /// using [`forward`](Self::forward)
+    pub(crate) fn with_chunk_size(mut self, size: ChunkSize) -> Self {
+        self.chunk_size = size;
+        self
+    }
+
+    /// Sets the maximum Size of the Data of a single Message, into which the
+    /// Data send to the Server is split
+    pub(crate) fn with_max_size(mut self, size: usize) -> Self {
+        self.max_size = size;
+        self
+    }
+
+    /// Records all the send Data as Activity in the Timer and stops it, once
+    /// this Sender is closed or dropped
+    pub(crate) fn with_idle(mut self, idle: Option<Arc<IdleTimer>>) -> Self {
+        self.idle = idle;
+        self
+    }
+
+    /// Writes an Access-Record using the Tracker, once this Sender is closed
+    /// or dropped
+    pub(crate) fn with_access(mut self, access: Option<Arc<AccessTracker>>) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// Sends the first `length` Bytes of the Data to the User, split into as
+    /// many Messages as needed to not exceed the maximum Size of a Message
+    ///
+    /// A Length larger than the Data fails this Connection with an Error,
+    /// instead of the entire Connection to the Server
+    fn send_data<D>(
+        &self,
+        data: D,
+        length: u64,
+    ) -> Result<(), tokio::sync::mpsc::error::SendError<Message>>
+    where
+        D: Into<Bytes>,
+    {
+        let mut data = data.into();
+        if length > data.len() as u64 {
+            error!(
+                "[Sender][{}] Length of {} exceeds the {} Bytes of Data",
+                self.id,
+                length,
+                data.len()
+            );
+            let header = MessageHeader::new(self.id, MessageType::Data, length);
+            self.fail(CloseReason::with_text(
+                CloseCode::Error,
+                "The Length exceeds the Data",
+            ));
+            return Err(tokio::sync::mpsc::error::SendError(Message::new(
+                header, data,
+            )));
         }
+        data.truncate(length as usize);
+
+        for msg in Message::split_data(self.id, data, self.max_size) {
+            self.tx.send(msg)?;
+        }
+        if let Some(idle) = self.idle.as_ref() {
+            idle.touch();
+        }
+        if let Some(access) = self.access.as_ref() {
+            access.sent(length);
+        }
+        Ok(())
+    }
+
+    /// Forwards all the Data read from the Reader to the User, until the
+    /// Reader reaches its End
+    ///
+    /// The Data is read in Chunks of the Size configured using
+    /// `ClientBuilder::read_chunk_size` and every Chunk is send as a single
+    /// Message, without copying it. The Sender is not closed afterwards.
+    ///
+    /// Returns:
+    /// The Number of Bytes that were forwarded
+    pub async fn forward<R>(&self, reader: &mut R) -> std::io::Result<u64>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut buf = ReadBuffer::new();
+        let mut sizer = ChunkSizer::new(self.chunk_size);
+        let mut total = 0;
+
+        loop {
+            let data = buf.read(reader, sizer.current()).await?;
+            if data.is_empty() {
+                return Ok(total);
+            }
+            sizer.record(data.len());
+
+            let length = data.len() as u64;
+            if self.send_data(data, length).is_err() {
+                return Err(std::io::ErrorKind::BrokenPipe.into());
+            }
+            total += length;
+        }
+    }
+
+    /// Creates the Close-Message for this Connection, which only contains the
+    /// Reason if the Server supports it
+    fn close_message(&self, reason: CloseReason) -> Message {
+        close_message(self.id, self.close_reasons, reason)
     }
 
     /// Closes the Sender and therefore consuming itself
     pub fn close(self) {
+        self.close_with(CloseReason::new(CloseCode::Normal));
+    }
+
+    /// Closes the Sender with the given Reason, which is forwarded to the
+    /// Server, and therefore consumes itself
+    ///
+    /// A Reason with an Error-Code causes the Server to reset the
+    /// User-Connection instead of closing it normally
+    pub fn close_with(self, reason: CloseReason) {
         self.all_client_cons.remove(self.id);
-        debug!("[Sender][{}] Removed Connection", self.id);
+        debug!("[Sender][{}] Removed Connection: {}", self.id, reason);
+
+        if let Some(access) = self.access.as_ref() {
+            access.close(reason.clone());
+        }
 
-        let close_msg = Message::new(MessageHeader::new(self.id, MessageType::Close, 0), vec![]);
+        let close_msg = self.close_message(reason);
         match self.tx.send(close_msg) {
             Ok(_) => {
                 debug!("[Sender][{}] Sent Close", self.id);
@@ -67,21 +426,63 @@ impl OwnedSender {
             }
         };
     }
+
+    /// Closes the Connection with the given Reason, if it was not closed yet,
+    /// without consuming the Sender
+    fn fail(&self, reason: CloseReason) {
+        if self.all_client_cons.remove(self.id).is_none() {
+            return;
+        }
+        debug!("[Sender][{}] Failed Connection: {}", self.id, reason);
+
+        if let Some(access) = self.access.as_ref() {
+            access.close(reason.clone());
+        }
+
+        let close_msg = self.close_message(reason);
+        if let Err(e) = self.tx.send(close_msg) {
+            error!("Sending Close-Message for {}: {}", self.id, e);
+        }
+    }
+
+    /// Rejects the Connection, instead of closing it, and therefore consumes
+    /// itself
+    pub(crate) fn reject(self, reason: &str) {
+        self.all_client_cons.remove(self.id);
+        debug!("[Sender][{}] Rejected Connection: {}", self.id, reason);
+
+        // Rejected Connections were never tunneled
+        if let Some(access) = self.access.as_ref() {
+            access.discard();
+        }
+
+        let body = reason.as_bytes().to_vec();
+        let reject_msg = Message::new(
+            MessageHeader::new(self.id, MessageType::Reject, body.len() as u64),
+            body,
+        );
+        if let Err(e) = self.tx.send(reject_msg) {
+            error!("Sending Reject-Message for {}: {}", self.id, e);
+        }
+    }
 }
 
 impl Drop for OwnedSender {
     fn drop(&mut self) {
-        match self.all_client_cons.remove(self.id) {
-            Some(_) => {}
-            None => {
-                return;
+        if self.all_client_cons.remove(self.id).is_some() {
+            debug!("[Sender][{}] Removed Connection", self.id);
+
+            let close_msg = self.close_message(CloseReason::new(CloseCode::Normal));
+            if let Err(e) = self.tx.send(close_msg) {
+                error!("Sending Close-Message for {}: {}", self.id, e);
             }
-        };
-        debug!("[Sender][{}] Removed Connection", self.id);
+        }
 
-        let close_msg = Message::new(MessageHeader::new(self.id, MessageType::Close, 0), vec![]);
-        if let Err(e) = self.tx.send(close_msg) {
-            error!("Sending Close-Message for {}: {}", self.id, e);
+        if let Some(access) = self.access.as_ref() {
+            access.finish();
+        }
+        if let Some(idle) = self.idle.as_ref() {
+            idle.stop();
         }
     }
 }
@@ -90,12 +491,8 @@ impl Drop for OwnedSender {
 impl Sender for OwnedSender {
     type SendingError = tokio::sync::mpsc::error::SendError<Message>;
 
-    async fn send_msg(&self, data: Vec<u8>, length: u64) -> Result<(), Self::SendingError> {
-        // Create the right Header and Message
-        let header = MessageHeader::new(self.id, MessageType::Data, length);
-        let msg = Message::new(header, data);
-
-        self.tx.send(msg)
+    async fn send_msg(&self, data: Bytes, length: u64) -> Result<(), Self::SendingError> {
+        self.send_data(data, length)
     }
 }
 
@@ -108,9 +505,9 @@ mod tests {
         let clients = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
         let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
 
-        let sender = OwnedSender::new(123, tx, clients);
+        let sender = OwnedSender::new(123, tx, clients, false);
 
-        sender.send_msg(vec![0, 1], 2).await.unwrap();
+        sender.send_msg(vec![0, 1].into(), 2).await.unwrap();
         let received = rx.recv().await;
         assert_eq!(true, received.is_some());
         assert_eq!(
@@ -119,12 +516,111 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn sender_send_bytes() {
+        let clients = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let sender = OwnedSender::new(123, tx, clients, false);
+
+        // The Data is send without copying it
+        let data = Bytes::from(vec![0, 1, 2]);
+        sender.send_msg(data.clone(), 3).await.unwrap();
+        let received = rx.recv().await.unwrap();
+        assert_eq!(data.as_ptr(), received.get_data().as_ptr());
+    }
+
+    #[tokio::test]
+    async fn sender_forward() {
+        let clients = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let sender =
+            OwnedSender::new(123, tx, clients, false).with_chunk_size(ChunkSize::adaptive(2, 8));
+        let mut reader: &[u8] = &[3; 14];
+
+        assert_eq!(14, sender.forward(&mut reader).await.unwrap());
+        for length in [2, 4, 8] {
+            assert_eq!(
+                Ok(Message::new(
+                    MessageHeader::new(123, MessageType::Data, length as u64),
+                    vec![3; length]
+                )),
+                rx.try_recv()
+            );
+        }
+        assert_eq!(true, rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn sender_splits_data() {
+        let clients = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let sender = OwnedSender::new(123, tx, clients, false);
+        let max = MessageCodec::DEFAULT_MAX_SIZE;
+        sender
+            .send_msg(vec![1; max + 11].into(), max as u64 + 10)
+            .await
+            .unwrap();
+
+        for length in [max, 10] {
+            let msg = rx.recv().await.unwrap();
+            assert_eq!(
+                &MessageHeader::new(123, MessageType::Data, length as u64),
+                msg.get_header()
+            );
+            assert_eq!(length, msg.get_data().len());
+        }
+        assert_eq!(true, rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn sender_splits_configured_size() {
+        let clients = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let sender = OwnedSender::new(123, tx, clients, false).with_max_size(4);
+        sender.send_msg(vec![1; 10].into(), 10).await.unwrap();
+
+        for length in [4, 4, 2] {
+            let msg = rx.recv().await.unwrap();
+            assert_eq!(length, msg.get_data().len());
+        }
+        assert_eq!(true, rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn sender_length_exceeds_data() {
+        let (stream_tx, mut stream_rx) = mpsc::stream();
+        let clients = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        clients.set(123, stream_tx);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let sender = OwnedSender::new(123, tx, clients.clone(), true);
+        assert_eq!(true, sender.send_msg(vec![0, 1].into(), 3).await.is_err());
+
+        // Only this Connection is closed with an Error
+        assert_eq!(
+            Some(
+                CloseReason::with_text(CloseCode::Error, "The Length exceeds the Data")
+                    .into_message(123)
+            ),
+            rx.recv().await
+        );
+        assert_eq!(true, clients.get_clone(123).is_none());
+        assert_eq!(true, stream_rx.recv().await.is_err());
+
+        drop(sender);
+        assert_eq!(true, rx.try_recv().is_err());
+    }
+
     #[tokio::test]
     async fn sender_close() {
         let clients = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
         let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
 
-        let sender = OwnedSender::new(123, tx, clients);
+        let sender = OwnedSender::new(123, tx, clients, false);
         sender.close();
 
         let received = rx.recv().await;
@@ -143,7 +639,7 @@ mod tests {
 
         let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
 
-        let sender = OwnedSender::new(123, tx, clients);
+        let sender = OwnedSender::new(123, tx, clients, false);
         drop(sender);
 
         let received = rx.recv().await;
@@ -153,4 +649,156 @@ mod tests {
             received.unwrap(),
         );
     }
+
+    #[tokio::test]
+    async fn sender_reject() {
+        let (tx, _rx) = mpsc::stream();
+        let clients = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        clients.set(123, tx);
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let sender = OwnedSender::new(123, tx, clients.clone(), false);
+        sender.reject("test");
+
+        assert_eq!(
+            Some(Message::new(
+                MessageHeader::new(123, MessageType::Reject, 4),
+                b"test".to_vec()
+            )),
+            rx.recv().await,
+        );
+        // No Close-Message should be send after the Reject
+        assert_eq!(true, rx.recv().await.is_none());
+        assert_eq!(true, clients.get_clone(123).is_none());
+    }
+
+    #[tokio::test]
+    async fn sender_close_with_reason() {
+        let clients = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let sender = OwnedSender::new(123, tx, clients, true);
+        sender.close_with(CloseReason::with_text(CloseCode::ConnectFailed, "refused"));
+
+        assert_eq!(
+            Some(CloseReason::with_text(CloseCode::ConnectFailed, "refused").into_message(123)),
+            rx.recv().await,
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn access_record_on_close() {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let access = Arc::new(AccessTracker::new(
+            AccessLog::new(crate::accesslog::Format::Common, output.clone()),
+            8080,
+            123,
+            "127.0.0.1".parse().unwrap(),
+        ));
+
+        let clients = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        let (stream_tx, stream_rx) = mpsc::stream();
+        clients.set(123, stream_tx.clone());
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let mut receiver = OwnedReceiver::new(stream_rx).with_access(Some(access.clone()));
+        let sender = OwnedSender::new(123, tx, clients, true).with_access(Some(access));
+
+        stream_tx
+            .send(Message::new(
+                MessageHeader::new(123, MessageType::Data, 3),
+                vec![0, 1, 2],
+            ))
+            .unwrap();
+        receiver.recv_msg().await.unwrap();
+        sender.send_msg(vec![0; 5].into(), 5).await.unwrap();
+
+        tokio::time::advance(std::time::Duration::from_millis(1500)).await;
+        sender.close_with(CloseReason::new(CloseCode::Timeout));
+
+        let written = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        assert_eq!(1, written.lines().count());
+        assert_eq!(
+            true,
+            written.ends_with("\"TUNNEL 8080 123\" 2 5 3 1500\n"),
+            "{}",
+            written
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_watch_closes() {
+        let clients = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        let (stream_tx, stream_rx) = mpsc::stream();
+        clients.set(123, stream_tx);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let idle = Arc::new(IdleTimer::new());
+        let mut receiver = OwnedReceiver::new(stream_rx).with_idle(Some(idle.clone()));
+        let sender =
+            OwnedSender::new(123, tx.clone(), clients.clone(), true).with_idle(Some(idle.clone()));
+        let watch = IdleWatch::new(123, idle, tx, clients.clone(), true);
+
+        let start = tokio::time::Instant::now();
+        let watch_task = tokio::spawn(watch.run(Duration::from_secs(30)));
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        sender.send_msg(vec![0].into(), 1).await.unwrap();
+
+        assert_eq!(true, watch_task.await.unwrap());
+        assert_eq!(Duration::from_secs(40), start.elapsed());
+
+        let expected = CloseReason::with_text(CloseCode::Timeout, "Connection was idle");
+        assert_eq!(
+            Err(RecvError::ClosedWith(expected.clone())),
+            receiver.recv_msg().await
+        );
+        assert_eq!(Err(RecvError::Closed), receiver.recv_msg().await);
+
+        assert_eq!(
+            Some(Message::new(
+                MessageHeader::new(123, MessageType::Data, 1),
+                vec![0]
+            )),
+            rx.recv().await
+        );
+        assert_eq!(Some(expected.into_message(123)), rx.recv().await);
+
+        // The Connection was already closed, so no further Close is send
+        drop(sender);
+        assert_eq!(true, rx.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_watch_stopped_by_sender() {
+        let clients = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        let (stream_tx, _stream_rx) = mpsc::stream();
+        clients.set(123, stream_tx);
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let idle = Arc::new(IdleTimer::new());
+        let sender =
+            OwnedSender::new(123, tx.clone(), clients.clone(), true).with_idle(Some(idle.clone()));
+        let watch = IdleWatch::new(123, idle, tx, clients, true);
+
+        drop(sender);
+        assert_eq!(false, watch.run(Duration::from_secs(30)).await);
+    }
+
+    #[tokio::test]
+    async fn sender_close_unsupported_reason() {
+        let clients = std::sync::Arc::new(Connections::<mpsc::StreamWriter<Message>>::new());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let sender = OwnedSender::new(123, tx, clients, false);
+        sender.close_with(CloseReason::new(CloseCode::Error));
+
+        assert_eq!(
+            Some(Message::new(
+                MessageHeader::new(123, MessageType::Close, 0),
+                vec![]
+            )),
+            rx.recv().await,
+        );
+    }
 }
@@ -1,7 +1,7 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use super::{connections::UserCon, Handler};
-use crate::Details;
+use super::{connections::UserCon, Handler, Receiver};
+use crate::{message::Message, streams::error::RecvError, Details};
 
 use async_trait::async_trait;
 
@@ -17,3 +17,27 @@ impl EmptyHandler {
 impl Handler for EmptyHandler {
     async fn new_con(self: Arc<Self>, _id: u32, _details: Details, _con: UserCon) {}
 }
+
+/// Receives all the Messages of a Connection, until its Receiver fails
+#[derive(Default)]
+pub struct RecordingHandler {
+    pub messages: Mutex<Vec<Message>>,
+    pub error: Mutex<Option<RecvError>>,
+    pub done: tokio::sync::Notify,
+}
+
+#[async_trait]
+impl Handler for RecordingHandler {
+    async fn new_con(self: Arc<Self>, _id: u32, _details: Details, mut con: UserCon) {
+        loop {
+            match con.recv_msg().await {
+                Ok(msg) => self.messages.lock().unwrap().push(msg),
+                Err(e) => {
+                    *self.error.lock().unwrap() = Some(e);
+                    break;
+                }
+            }
+        }
+        self.done.notify_one();
+    }
+}
@@ -0,0 +1,197 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::{
+    connections::Connections, general::Sequencer, handshake, message::Message, streams::mpsc,
+};
+
+use super::connections::{rx, tx::SendQueue};
+
+/// The Slot in which the Session is kept, while there is no Connection to the
+/// Server
+pub(crate) type SessionSlot = Arc<Mutex<Option<Session>>>;
+
+/// The State shared by all the Connections to the Server that belong to the
+/// same Session
+pub(crate) struct Session {
+    /// The Token of the Session, None if the Server did not grant one
+    token: Option<u128>,
+    /// How long the Server keeps the Session after losing the Connection
+    grace: Duration,
+    /// All the User-Connections of the Session
+    pub outgoing: Arc<Connections<mpsc::StreamWriter<Message>>>,
+    /// The Queue of Messages that should be send to the Server
+    pub queue_tx: tokio::sync::mpsc::UnboundedSender<Message>,
+    /// The Receiving side of the Queue, used by the Sender of the Connection
+    pub queue_rx: SendQueue,
+    /// Keeps track of the Messages send and received, if there is a Token
+    pub sequencer: Option<Arc<Sequencer>>,
+    /// Closes the Session once its Grace-Period runs out, while suspended
+    expiry: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Session {
+    /// Creates a new Session for the one granted by the Server, if any
+    ///
+    /// # Params:
+    /// * `granted`: The Session granted by the Server
+    /// * `max_buffered`: The maximum Number of Bytes of unacknowledged
+    ///   Messages that are kept, after which the Session can not be resumed
+    pub fn new(granted: Option<&handshake::Session>, max_buffered: usize) -> Self {
+        let (queue_tx, queue_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        Self {
+            token: granted.map(|g| g.token()),
+            grace: granted.map(|g| g.grace()).unwrap_or_default(),
+            outgoing: Arc::new(Connections::new()),
+            queue_tx,
+            queue_rx: Arc::new(tokio::sync::Mutex::new(queue_rx)),
+            sequencer: granted.map(|_| Arc::new(Sequencer::with_max_buffered(max_buffered))),
+            expiry: None,
+        }
+    }
+
+    /// The Token of the Session, if the Server granted one
+    pub fn token(&self) -> Option<u128> {
+        self.token
+    }
+
+    /// Prepares the Session for being resumed on a new Connection
+    ///
+    /// # Returns
+    /// A Receiver, that is notified once the Server resumed its side of the
+    /// Session
+    pub fn resume(&mut self) -> Option<tokio::sync::oneshot::Receiver<()>> {
+        if let Some(expiry) = self.expiry.take() {
+            expiry.abort();
+        }
+        self.sequencer.as_ref().map(|s| s.expect_resume())
+    }
+
+    /// Ends the Session, which closes all of its User-Connections
+    pub fn close(self) {
+        if let Some(expiry) = self.expiry {
+            expiry.abort();
+        }
+        rx::close_all(&self.outgoing);
+    }
+
+    /// Keeps the Session around after losing the Connection to the Server,
+    /// until it is either resumed or its Grace-Period runs out
+    ///
+    /// Sessions without a Token or with too much unacknowledged Data can not
+    /// be resumed and are closed right away
+    pub fn suspend(mut self, slot: &SessionSlot) {
+        let token = match self.token {
+            Some(t) => t,
+            None => return self.close(),
+        };
+        if self
+            .sequencer
+            .as_ref()
+            .map(|s| s.overflowed())
+            .unwrap_or(false)
+        {
+            info!("Session can not be resumed: Too much Data was not acknowledged");
+            return self.close();
+        }
+
+        let expiry_slot = slot.clone();
+        let grace = self.grace;
+        self.expiry = Some(tokio::task::spawn(async move {
+            tokio::time::sleep(grace).await;
+
+            let mut current = expiry_slot.lock().unwrap();
+            if current.as_ref().and_then(|s| s.token) != Some(token) {
+                return;
+            }
+            if let Some(session) = current.take() {
+                info!("Session expired");
+                rx::close_all(&session.outgoing);
+            }
+        }));
+
+        *slot.lock().unwrap() = Some(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        general::DEFAULT_MAX_BUFFERED,
+        message::{MessageHeader, MessageType},
+    };
+
+    #[tokio::test]
+    async fn without_token_closed_right_away() {
+        let slot: SessionSlot = Arc::new(Mutex::new(None));
+        let session = Session::new(None, DEFAULT_MAX_BUFFERED);
+
+        let (tx, mut rx) = mpsc::stream();
+        session.outgoing.set(3, tx);
+
+        session.suspend(&slot);
+
+        assert_eq!(true, slot.lock().unwrap().is_none());
+        assert_eq!(true, rx.recv().await.unwrap().is_close());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn suspended_until_expired() {
+        let slot: SessionSlot = Arc::new(Mutex::new(None));
+        let granted = handshake::Session::new(13, false, Duration::from_secs(10));
+        let session = Session::new(Some(&granted), DEFAULT_MAX_BUFFERED);
+
+        let (tx, mut rx) = mpsc::stream();
+        session.outgoing.set(3, tx);
+
+        session.suspend(&slot);
+        assert_eq!(
+            Some(13),
+            slot.lock().unwrap().as_ref().and_then(|s| s.token())
+        );
+
+        tokio::time::sleep(Duration::from_secs(11)).await;
+
+        assert_eq!(true, slot.lock().unwrap().is_none());
+        assert_eq!(true, rx.recv().await.unwrap().is_close());
+    }
+
+    #[tokio::test]
+    async fn overflowed_closed_right_away() {
+        let slot: SessionSlot = Arc::new(Mutex::new(None));
+        let granted = handshake::Session::new(13, false, Duration::from_secs(10));
+        let session = Session::new(Some(&granted), 4);
+
+        let (tx, mut rx) = mpsc::stream();
+        session.outgoing.set(3, tx);
+        session.sequencer.as_ref().unwrap().sent(&Message::new(
+            MessageHeader::new(3, MessageType::Data, 5),
+            vec![1; 5],
+        ));
+
+        session.suspend(&slot);
+
+        assert_eq!(true, slot.lock().unwrap().is_none());
+        assert_eq!(true, rx.recv().await.unwrap().is_close());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn resumed_before_expiry() {
+        let slot: SessionSlot = Arc::new(Mutex::new(None));
+        let granted = handshake::Session::new(13, false, Duration::from_secs(10));
+        let session = Session::new(Some(&granted), DEFAULT_MAX_BUFFERED);
+
+        let (tx, _rx) = mpsc::stream();
+        session.outgoing.set(3, tx);
+
+        session.suspend(&slot);
+
+        let mut session = slot.lock().unwrap().take().unwrap();
+        assert_eq!(true, session.resume().is_some());
+
+        tokio::time::sleep(Duration::from_secs(11)).await;
+        assert_eq!(1, session.outgoing.len());
+    }
+}
@@ -3,6 +3,7 @@ use std::sync::Arc;
 use crate::{message::Message, Details};
 
 use async_trait::async_trait;
+use bytes::Bytes;
 
 use super::connections::UserCon;
 
@@ -18,6 +19,11 @@ pub trait Receiver {
     type ReceivingError: std::fmt::Debug;
 
     /// Receives a single Message over the Connection
+    ///
+    /// Only Data is ever returned as a Message, once the Connection is
+    /// closed an Error is returned instead, which for the Client contains
+    /// the Reason of the Server, if it send one
+    /// (`RecvError::ClosedWith`)
     async fn recv_msg(&mut self) -> Result<Message, Self::ReceivingError>;
 }
 
@@ -31,13 +37,26 @@ pub trait Sender {
     type SendingError: std::fmt::Debug;
 
     /// Sends a single Message over the Connection
-    async fn send_msg(&self, data: Vec<u8>, length: u64) -> Result<(), Self::SendingError>;
+    ///
+    /// The Data is taken as `Bytes`, so Data that was received as a Message
+    /// can be send again without copying it
+    async fn send_msg(&self, data: Bytes, length: u64) -> Result<(), Self::SendingError>;
 }
 
 /// The Interface that every Handler needs to implement to be used by the
 /// Client.
 #[async_trait]
 pub trait Handler {
+    /// This method is called every time a new Connection is received, before
+    /// `new_con`, and decides if the Connection should be handled at all.
+    /// Returning an Error rejects the Connection with the given Reason, for
+    /// example because the Backend is currently not available.
+    ///
+    /// The default implementation accepts every Connection
+    async fn accept(&self, _id: u32, _details: &Details) -> Result<(), String> {
+        Ok(())
+    }
+
     /// This method is called every time a new Connection is received and
     /// should therefore handle all the initial stuff for dealing with
     /// the new Connection
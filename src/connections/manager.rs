@@ -41,6 +41,19 @@ where
     pub fn remove(&self, id: u32) -> Option<(u32, T)> {
         self.connections.remove(&id)
     }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Returns a Snapshot of all the Connections and their IDs
+    pub fn entries(&self) -> Vec<(u32, T)> {
+        self.connections
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect()
+    }
 }
 
 impl<T> Clone for Connections<T>
@@ -0,0 +1,150 @@
+use crate::{
+    connections::Connections,
+    message::{CloseCode, CloseReason, Message, MessageCodec},
+    streams::mpsc,
+};
+
+/// The maximum Number of Messages that are written to a Connection at once
+pub const MAX_BATCH: usize = 64;
+
+/// Waits for the next Message in the Queue and then also takes all the
+/// Messages, that are already waiting in the Queue, up to [`MAX_BATCH`]
+///
+/// # Params:
+/// * `queue`: The Queue to receive the Messages from
+/// * `batch`: The Batch the Messages are added to
+///
+/// Returns:
+/// False if the Queue was closed and no more Messages can be received
+pub async fn recv_batch(
+    queue: &mut tokio::sync::mpsc::UnboundedReceiver<Message>,
+    batch: &mut Vec<Message>,
+) -> bool {
+    match queue.recv().await {
+        Some(msg) => batch.push(msg),
+        None => return false,
+    };
+
+    while batch.len() < MAX_BATCH {
+        match queue.try_recv() {
+            Ok(msg) => batch.push(msg),
+            Err(_) => break,
+        };
+    }
+    true
+}
+
+/// Replaces every Message in the Batch, that the Codec can not encode, with
+/// an Error-Close for its Connection, which is also closed locally
+///
+/// This only fails the single User-Connection, instead of the entire
+/// Connection the Batch is written to, which would also fail again for every
+/// Attempt to resume it.
+///
+/// # Params:
+/// * `batch`: The Messages that should be written
+/// * `codec`: The Codec used for writing the Messages
+/// * `user_cons`: The local User-Connections, to which the Close is send as
+///   well
+pub fn reject_invalid(
+    batch: &mut [Message],
+    codec: &MessageCodec,
+    user_cons: &Connections<mpsc::StreamWriter<Message>>,
+) {
+    for msg in batch.iter_mut() {
+        if let Err(e) = codec.validate(msg) {
+            let id = msg.get_header().get_id();
+            error!("[{}] Dropping invalid Message: {}", id, e);
+            let reason = CloseReason::with_text(CloseCode::Error, e.to_string());
+            if let Some((_, user_con)) = user_cons.remove(id) {
+                let _ = user_con.send(reason.clone().into_message(id));
+            }
+            *msg = reason.into_message(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{HeaderFormat, MessageHeader, MessageType};
+
+    fn msg(id: u32) -> Message {
+        Message::new(MessageHeader::new(id, MessageType::EOF, 0), vec![])
+    }
+
+    #[tokio::test]
+    async fn takes_waiting_messages() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for id in 0..3 {
+            tx.send(msg(id)).unwrap();
+        }
+
+        let mut batch = Vec::new();
+        assert_eq!(true, recv_batch(&mut rx, &mut batch).await);
+        assert_eq!(vec![msg(0), msg(1), msg(2)], batch);
+    }
+
+    #[tokio::test]
+    async fn limited_batch() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for id in 0..(MAX_BATCH as u32 + 1) {
+            tx.send(msg(id)).unwrap();
+        }
+
+        let mut batch = Vec::new();
+        assert_eq!(true, recv_batch(&mut rx, &mut batch).await);
+        assert_eq!(MAX_BATCH, batch.len());
+
+        batch.clear();
+        assert_eq!(true, recv_batch(&mut rx, &mut batch).await);
+        assert_eq!(vec![msg(MAX_BATCH as u32)], batch);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_messages() {
+        let codec = MessageCodec::new(HeaderFormat::V2).with_max_size(4);
+        let valid = Message::new(MessageHeader::new(1, MessageType::Data, 4), vec![0; 4]);
+        let mut batch = vec![
+            valid.clone(),
+            Message::new(MessageHeader::new(2, MessageType::Data, 5), vec![0; 5]),
+            Message::new(MessageHeader::new(3, MessageType::Data, 3), vec![0; 2]),
+        ];
+
+        let user_cons = Connections::new();
+        let (valid_tx, _valid_rx) = mpsc::stream();
+        user_cons.set(1, valid_tx);
+        let (invalid_tx, mut invalid_rx) = mpsc::stream();
+        user_cons.set(2, invalid_tx);
+
+        reject_invalid(&mut batch, &codec, &user_cons);
+        assert_eq!(valid, batch[0]);
+        for (msg, id) in batch[1..].iter().zip([2, 3]) {
+            assert_eq!(id, msg.get_header().get_id());
+            assert_eq!(Some(CloseCode::Error), msg.close_reason().map(|r| r.code()));
+        }
+
+        // The User-Connection is closed locally as well
+        assert_eq!(true, user_cons.get_clone(1).is_some());
+        assert_eq!(true, user_cons.get_clone(2).is_none());
+        assert_eq!(
+            Some(CloseCode::Error),
+            invalid_rx
+                .recv()
+                .await
+                .ok()
+                .and_then(|m| m.close_reason())
+                .map(|r| r.code())
+        );
+    }
+
+    #[tokio::test]
+    async fn closed_queue() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        drop(tx);
+
+        let mut batch = Vec::new();
+        assert_eq!(false, recv_batch(&mut rx, &mut batch).await);
+        assert_eq!(true, batch.is_empty());
+    }
+}
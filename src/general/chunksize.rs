@@ -0,0 +1,142 @@
+use crate::message::MessageCodec;
+
+/// The Chunk-Size used, if none was configured
+const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+/// The largest Chunk-Size, as every Chunk has to fit into a single Message
+const MAX_CHUNK_SIZE: usize = MessageCodec::DEFAULT_MAX_SIZE;
+
+/// The Size of the Chunks in which Data is read from a Connection, where
+/// every Chunk is then forwarded as a single Message
+///
+/// Sizes larger than the maximum Size of a Message
+/// ([`MessageCodec::DEFAULT_MAX_SIZE`]) are limited to it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkSize {
+    initial: usize,
+    max: usize,
+}
+
+impl ChunkSize {
+    /// Always reads Chunks of the given Size
+    pub fn fixed(size: usize) -> Self {
+        let size = size.clamp(1, MAX_CHUNK_SIZE);
+        Self {
+            initial: size,
+            max: size,
+        }
+    }
+
+    /// Starts with Chunks of the initial Size and doubles it, whenever a Read
+    /// filled an entire Chunk, up to the maximum Size. Once Reads only fill a
+    /// small Part of the Chunk again, it shrinks back down towards the
+    /// initial Size
+    pub fn adaptive(initial: usize, max: usize) -> Self {
+        let initial = initial.clamp(1, MAX_CHUNK_SIZE);
+        Self {
+            initial,
+            max: max.clamp(initial, MAX_CHUNK_SIZE),
+        }
+    }
+
+    /// The Size of the first Chunk
+    pub fn initial(&self) -> usize {
+        self.initial
+    }
+    /// The largest Size a Chunk can grow to
+    pub fn max(&self) -> usize {
+        self.max
+    }
+}
+
+impl Default for ChunkSize {
+    fn default() -> Self {
+        Self::fixed(DEFAULT_CHUNK_SIZE)
+    }
+}
+
+/// Keeps track of the current Chunk-Size for a single Connection
+#[derive(Debug)]
+pub struct ChunkSizer {
+    size: ChunkSize,
+    current: usize,
+}
+
+impl ChunkSizer {
+    /// Creates a new Sizer, which starts at the initial Size
+    pub fn new(size: ChunkSize) -> Self {
+        Self {
+            size,
+            current: size.initial,
+        }
+    }
+
+    /// The Size of the next Chunk to read
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Adjusts the Size based on the Number of Bytes read into the last Chunk
+    pub fn record(&mut self, read: usize) {
+        if read >= self.current {
+            self.current = self.current.saturating_mul(2).min(self.size.max);
+        } else if read < self.current / 4 {
+            self.current = (self.current / 2).max(self.size.initial);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_size() {
+        let mut sizer = ChunkSizer::new(ChunkSize::fixed(16));
+
+        sizer.record(16);
+        assert_eq!(16, sizer.current());
+        sizer.record(1);
+        assert_eq!(16, sizer.current());
+    }
+
+    #[test]
+    fn grows_up_to_max() {
+        let mut sizer = ChunkSizer::new(ChunkSize::adaptive(16, 48));
+
+        sizer.record(16);
+        assert_eq!(32, sizer.current());
+        sizer.record(20);
+        assert_eq!(32, sizer.current());
+        sizer.record(32);
+        assert_eq!(48, sizer.current());
+        sizer.record(48);
+        assert_eq!(48, sizer.current());
+    }
+
+    #[test]
+    fn shrinks_down_to_initial() {
+        let mut sizer = ChunkSizer::new(ChunkSize::adaptive(16, 64));
+        sizer.record(16);
+        sizer.record(32);
+        assert_eq!(64, sizer.current());
+
+        sizer.record(15);
+        assert_eq!(32, sizer.current());
+        sizer.record(1);
+        assert_eq!(16, sizer.current());
+        sizer.record(1);
+        assert_eq!(16, sizer.current());
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        assert_eq!(ChunkSize::fixed(1), ChunkSize::fixed(0));
+        assert_eq!(ChunkSize::adaptive(32, 32), ChunkSize::adaptive(32, 8));
+
+        assert_eq!(MAX_CHUNK_SIZE, ChunkSize::fixed(usize::MAX).max());
+        let size = ChunkSize::adaptive(usize::MAX, usize::MAX);
+        assert_eq!(MAX_CHUNK_SIZE, size.initial());
+        assert_eq!(MAX_CHUNK_SIZE, size.max());
+    }
+}
@@ -11,6 +11,16 @@ pub enum DeserializeDetailsError {
     DeserializeError(Box<dyn std::fmt::Debug>),
 }
 
+impl std::fmt::Display for DeserializeDetailsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeserializeDetailsError::DeserializeError(e) => {
+                write!(f, "Deserializing the Details: {:?}", e)
+            }
+        }
+    }
+}
+
 impl Details {
     pub(crate) fn new(ip: IpAddr) -> Self {
         Self { ip }
@@ -77,7 +87,7 @@ impl SerializeDetails for IpAddr {
         }
     }
     fn deserialize(buffer: &mut Vec<u8>) -> Result<Self, Self::DeserializeError> {
-        match buffer.get(0) {
+        match buffer.first() {
             Some(4) => {
                 if buffer.len() < 5 {
                     return Err(DeserializeIPError::Incomplete);
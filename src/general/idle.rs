@@ -0,0 +1,114 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// Keeps track of the last Activity on a single Connection to detect when it
+/// has been idle for too long
+#[derive(Debug)]
+pub struct IdleTimer {
+    last_activity: Mutex<Instant>,
+    stopped: AtomicBool,
+    stop_notify: Notify,
+}
+
+impl IdleTimer {
+    /// Creates a new Timer, which treats the Creation as the first Activity
+    pub fn new() -> Self {
+        Self {
+            last_activity: Mutex::new(Instant::now()),
+            stopped: AtomicBool::new(false),
+            stop_notify: Notify::new(),
+        }
+    }
+
+    /// Records some Activity on the Connection
+    pub fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    /// Stops the Timer, once the Connection is closed, which causes
+    /// [`expired`](Self::expired) to return right away
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.stop_notify.notify_one();
+    }
+
+    /// Waits until there was no Activity for the given Timeout or until the
+    /// Timer is stopped
+    ///
+    /// Returns:
+    /// * true if the Connection was idle for the Timeout
+    /// * false if the Timer was stopped
+    pub async fn expired(&self, timeout: Duration) -> bool {
+        loop {
+            if self.stopped.load(Ordering::SeqCst) {
+                return false;
+            }
+
+            let deadline = *self.last_activity.lock().unwrap() + timeout;
+            if Instant::now() >= deadline {
+                return true;
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => {},
+                _ = self.stop_notify.notified() => {},
+            };
+        }
+    }
+}
+
+impl Default for IdleTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn expires_without_activity() {
+        let timer = IdleTimer::new();
+
+        let start = Instant::now();
+        assert_eq!(true, timer.expired(Duration::from_secs(10)).await);
+        assert_eq!(Duration::from_secs(10), start.elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn activity_delays_expiry() {
+        let timer = std::sync::Arc::new(IdleTimer::new());
+
+        let start = Instant::now();
+        let cloned = timer.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(6)).await;
+            cloned.touch();
+        });
+
+        assert_eq!(true, timer.expired(Duration::from_secs(10)).await);
+        assert_eq!(Duration::from_secs(16), start.elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stopped() {
+        let timer = std::sync::Arc::new(IdleTimer::new());
+
+        let start = Instant::now();
+        let cloned = timer.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            cloned.stop();
+        });
+
+        assert_eq!(false, timer.expired(Duration::from_secs(10)).await);
+        assert_eq!(Duration::from_secs(2), start.elapsed());
+    }
+}
@@ -1,11 +1,16 @@
 use std::vec;
 
-use crate::{general::ConnectionReader, message::Message};
+use crate::{
+    general::ConnectionReader,
+    message::{HeaderFormat, Message, MessageCodec},
+};
 
 #[cfg(test)]
-use crate::message::{MessageHeader, MessageType};
+use crate::message::{MessageHeader, MessageType, ReadBuffer};
 
 use async_trait::async_trait;
+use bytes::BytesMut;
+use tokio_util::codec::Encoder;
 
 #[derive(Debug)]
 pub struct Reader {
@@ -27,11 +32,17 @@ impl Reader {
     /// Adds the serialized Message to the internal chunks
     /// that will be returned when reading from it
     pub fn add_message(&mut self, msg: Message) {
-        let mut head: [u8; 13] = [0; 13];
-        let body = msg.serialize(&mut head);
+        self.add_formatted(msg, HeaderFormat::V1);
+    }
 
-        self.data.extend_from_slice(&head[..]);
-        self.data.extend_from_slice(body);
+    /// Adds the Message, with its Header serialized in the given Format, to
+    /// the internal chunks that will be returned when reading from it
+    pub fn add_formatted(&mut self, msg: Message, format: HeaderFormat) {
+        let mut raw = BytesMut::new();
+        MessageCodec::new(format)
+            .encode(msg, &mut raw)
+            .expect("Encoding the Message");
+        self.data.extend_from_slice(&raw);
     }
 
     /// Adds the raw bytes to the internal buffer
@@ -112,34 +123,23 @@ async fn read_message() {
     let mut tmp = Reader::new();
 
     let msg = Message::new(MessageHeader::new(13, MessageType::Data, 10), vec![1; 10]);
-    tmp.add_message(msg);
+    tmp.add_message(msg.clone());
 
-    let mut head_read = [0; 13];
-    let head_result = tmp.read_full(&mut head_read).await;
-    assert_eq!(true, head_result.is_ok());
-    assert_eq!(13, head_result.unwrap());
-    assert_eq!(
-        &vec![13u8, 0, 0, 0, 3, 10, 0, 0, 0, 0, 0, 0, 0],
-        &head_read[..]
-    );
-
-    let mut body_read = [0; 10];
-    let body_result = tmp.read_full(&mut body_read).await;
-    assert_eq!(true, body_result.is_ok());
-    assert_eq!(10, body_result.unwrap());
-    assert_eq!(&vec![1; 10], &body_read[..]);
-
-    let mut empty = [0; 10];
-    let eof_result = tmp.read_full(&mut empty).await;
-    assert_eq!(true, eof_result.is_ok());
-    assert_eq!(0, eof_result.unwrap());
+    let mut codec = MessageCodec::new(HeaderFormat::V1);
+    let mut buf = ReadBuffer::new();
+    let result = tmp.read_msg(&mut codec, &mut buf).await;
+    assert_eq!(true, result.is_ok());
+    assert_eq!(msg, result.unwrap());
+
+    let eof_result = tmp.read_msg(&mut codec, &mut buf).await;
+    assert_eq!(true, eof_result.is_err());
 }
 
 #[tokio::test]
 async fn read_fits() {
     let mut tmp = Reader::new();
 
-    tmp.add_bytes(&vec![0, 1, 2, 3, 4, 5]);
+    tmp.add_bytes(&[0, 1, 2, 3, 4, 5]);
 
     let mut buffer = [0u8; 6];
     let read_result = tmp.read(&mut buffer).await;
@@ -155,7 +155,7 @@ async fn read_fits() {
 async fn read_buffer_is_bigger() {
     let mut tmp = Reader::new();
 
-    tmp.add_bytes(&vec![0, 1, 2, 3, 4, 5]);
+    tmp.add_bytes(&[0, 1, 2, 3, 4, 5]);
 
     let mut buffer = [0u8; 8];
     let read_result = tmp.read(&mut buffer).await;
@@ -171,7 +171,7 @@ async fn read_buffer_is_bigger() {
 async fn read_buffer_is_smaller_multiple_reads() {
     let mut tmp = Reader::new();
 
-    tmp.add_bytes(&vec![0, 1, 2, 3, 4, 5]);
+    tmp.add_bytes(&[0, 1, 2, 3, 4, 5]);
 
     let mut buffer = [0u8; 3];
     let read_result = tmp.read(&mut buffer).await;
@@ -193,7 +193,7 @@ async fn read_buffer_is_smaller_multiple_reads() {
 async fn read_fits_with_close() {
     let mut tmp = Reader::new();
 
-    tmp.add_bytes(&vec![0, 1, 2, 3, 4, 5]);
+    tmp.add_bytes(&[0, 1, 2, 3, 4, 5]);
     tmp.close();
 
     let mut buffer = [0u8; 6];
@@ -34,6 +34,6 @@ fn new_writer() {
 async fn valid_write() {
     let mut tmp = Writer::new();
 
-    assert_eq!(true, tmp.write_full(&vec![1, 2, 3, 4]).await.is_ok());
+    assert_eq!(true, tmp.write_full(&[1, 2, 3, 4]).await.is_ok());
     assert_eq!(vec![vec![1, 2, 3, 4]], tmp.chunks());
 }
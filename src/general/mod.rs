@@ -1,8 +1,26 @@
 mod traits;
 pub use traits::*;
 
+mod batch;
+pub use batch::{recv_batch, reject_invalid, MAX_BATCH};
+
+mod chunksize;
+pub use chunksize::{ChunkSize, ChunkSizer};
+
 mod connection_details;
 pub use connection_details::*;
 
+mod idle;
+pub use idle::IdleTimer;
+
+mod ratelimit;
+pub use ratelimit::{RateLimit, Throttle, TokenBucket};
+
+mod sequencer;
+pub use sequencer::{ResumeError, Sequencer, DEFAULT_MAX_BUFFERED};
+
+mod ping;
+pub use ping::{PingError, Pinger, PING_INTERVAL, PING_TIMEOUT};
+
 #[cfg(test)]
 pub(crate) mod mocks;
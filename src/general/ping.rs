@@ -0,0 +1,182 @@
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::time::Instant;
+
+use crate::message::{Message, MessageHeader, MessageType};
+
+/// The Interval in which Pings are send over a Connection
+pub const PING_INTERVAL: Duration = Duration::from_secs(15);
+/// The Time after which a Connection is considered dead, if no Pong was
+/// received in the mean time
+pub const PING_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// The Reason why the Ping-Loop stopped
+#[derive(Debug, PartialEq)]
+pub enum PingError {
+    /// No Pong was received from the other side within the Timeout
+    Timeout,
+    /// The Ping could not be added to the Send-Queue
+    Sending,
+}
+
+/// Keeps track of the Pings send over a single Connection and the Pongs
+/// received in response to them
+///
+/// The Body of every Ping is the Time, in Micro-Seconds, since this Pinger was
+/// created, which the other side simply echos back in its Pong. This means
+/// that no state has to be kept for the individual Pings that are still
+/// in flight.
+#[derive(Debug)]
+pub struct Pinger {
+    start: Instant,
+    /// The Time, since `start`, when the last Pong was received
+    last_pong: AtomicU64,
+}
+
+impl Default for Pinger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pinger {
+    /// Creates a new Pinger, which treats the Connection as having just
+    /// received a Pong
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            last_pong: AtomicU64::new(0),
+        }
+    }
+
+    fn elapsed(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+
+    /// Creates a new Ping-Message for the current Point in Time
+    pub fn ping(&self) -> Message {
+        let body = self.elapsed().to_le_bytes().to_vec();
+        Message::new(
+            MessageHeader::new(0, MessageType::Ping, body.len() as u64),
+            body,
+        )
+    }
+
+    /// Creates the Pong-Message in response to a Ping with the given Body
+    pub fn pong(ping_body: Bytes) -> Message {
+        Message::new(
+            MessageHeader::new(0, MessageType::Pong, ping_body.len() as u64),
+            ping_body,
+        )
+    }
+
+    /// Records that a Pong with the given Body was received
+    ///
+    /// # Returns
+    /// The Round-Trip-Time of the Ping that this Pong was a response to or
+    /// None if the Body is not a valid Timestamp created by this Pinger
+    pub fn received_pong(&self, body: &[u8]) -> Option<Duration> {
+        let raw_timestamp: [u8; 8] = body.try_into().ok()?;
+        let send_time = u64::from_le_bytes(raw_timestamp);
+
+        let now = self.elapsed();
+        self.last_pong.store(now, Ordering::Relaxed);
+
+        now.checked_sub(send_time).map(Duration::from_micros)
+    }
+
+    /// The Time that has passed since the last Pong was received
+    pub fn since_last_pong(&self) -> Duration {
+        let last = self.last_pong.load(Ordering::Relaxed);
+        Duration::from_micros(self.elapsed().saturating_sub(last))
+    }
+
+    /// Sends a Ping every `interval` over the given Queue
+    ///
+    /// # Behaviour
+    /// This only returns once no Pong has been received for longer than the
+    /// given `timeout` or the Ping could not be added to the Queue
+    pub async fn run(
+        &self,
+        send_queue: &tokio::sync::mpsc::UnboundedSender<Message>,
+        interval: Duration,
+        timeout: Duration,
+    ) -> PingError {
+        loop {
+            if self.since_last_pong() > timeout {
+                return PingError::Timeout;
+            }
+
+            if send_queue.send(self.ping()).is_err() {
+                return PingError::Sending;
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn ping_pong_rtt() {
+        let pinger = Pinger::new();
+
+        let ping = pinger.ping();
+        assert_eq!(&MessageType::Ping, ping.get_header().get_kind());
+
+        tokio::time::advance(Duration::from_millis(20)).await;
+
+        let pong = Pinger::pong(ping.into_data());
+        assert_eq!(&MessageType::Pong, pong.get_header().get_kind());
+
+        assert_eq!(
+            Some(Duration::from_millis(20)),
+            pinger.received_pong(pong.get_data())
+        );
+        assert_eq!(Duration::from_millis(0), pinger.since_last_pong());
+    }
+
+    #[tokio::test]
+    async fn invalid_pong_body() {
+        let pinger = Pinger::new();
+
+        assert_eq!(None, pinger.received_pong(&[0, 1, 2]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn run_times_out() {
+        let pinger = Pinger::new();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let result = pinger
+            .run(&tx, Duration::from_secs(5), Duration::from_secs(12))
+            .await;
+        assert_eq!(PingError::Timeout, result);
+
+        // Pings at 0s, 5s and 10s before noticing the Timeout at 15s
+        let mut count = 0;
+        while let Ok(msg) = rx.try_recv() {
+            assert_eq!(&MessageType::Ping, msg.get_header().get_kind());
+            count += 1;
+        }
+        assert_eq!(3, count);
+    }
+
+    #[tokio::test]
+    async fn run_closed_queue() {
+        let pinger = Pinger::new();
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        drop(rx);
+
+        let result = pinger
+            .run(&tx, Duration::from_secs(5), Duration::from_secs(12))
+            .await;
+        assert_eq!(PingError::Sending, result);
+    }
+}
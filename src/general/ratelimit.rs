@@ -0,0 +1,240 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// A Limit for the Number of Bytes transferred per Second
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    bytes_per_second: u64,
+    burst: u64,
+}
+
+impl RateLimit {
+    /// Creates a new Limit with the given Rate, which allows Bursts of up to
+    /// one Second worth of Data
+    ///
+    /// A Rate of 0 Bytes per Second disables the Limit
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second,
+            burst: bytes_per_second,
+        }
+    }
+
+    /// Sets the maximum Number of Bytes that can be transferred at once,
+    /// after nothing was transferred for a while
+    ///
+    /// The Burst is at least a single Byte, as nothing could ever be
+    /// transferred otherwise
+    pub fn with_burst(mut self, burst: u64) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    /// The Number of Bytes per Second
+    pub fn bytes_per_second(&self) -> u64 {
+        self.bytes_per_second
+    }
+    /// The maximum Number of Bytes that can be transferred at once
+    pub fn burst(&self) -> u64 {
+        self.burst
+    }
+
+    /// Returns the stricter of the two Limits, if any, where disabled Limits
+    /// are ignored
+    pub(crate) fn strictest(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        let enabled = |limit: &Self| limit.bytes_per_second > 0;
+        match (first.filter(enabled), second.filter(enabled)) {
+            (Some(f), Some(s)) if s.bytes_per_second < f.bytes_per_second => Some(s),
+            (Some(f), _) => Some(f),
+            (None, s) => s,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// A Token-Bucket that enforces a single Rate-Limit
+#[derive(Debug)]
+pub struct TokenBucket {
+    limit: RateLimit,
+    state: Mutex<BucketState>,
+}
+
+impl TokenBucket {
+    /// Creates a new full Bucket for the Limit
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            state: Mutex::new(BucketState {
+                tokens: limit.burst as f64,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Tries to take the given Number of Tokens out of the Bucket
+    ///
+    /// Returns:
+    /// * Ok if the Tokens were taken
+    /// * Err with the Time to wait until enough Tokens are available
+    fn try_take(&self, amount: u64) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.limit.bytes_per_second as f64)
+            .min(self.limit.burst as f64);
+        state.last_refill = now;
+
+        let amount = amount as f64;
+        if state.tokens >= amount {
+            state.tokens -= amount;
+            return Ok(());
+        }
+
+        let missing = amount - state.tokens;
+        Err(Duration::from_secs_f64(
+            missing / self.limit.bytes_per_second as f64,
+        ))
+    }
+
+    /// Waits until the given Number of Bytes can be transferred
+    pub async fn acquire(&self, bytes: u64) {
+        if self.limit.bytes_per_second == 0 {
+            return;
+        }
+
+        // Requests that are larger than the Bucket itself are split up, as
+        // they could otherwise never be fulfilled
+        let chunk_size = self.limit.burst;
+        let mut remaining = bytes;
+        while remaining > 0 {
+            let chunk = remaining.min(chunk_size);
+            while let Err(wait) = self.try_take(chunk) {
+                tokio::time::sleep(wait).await;
+            }
+            remaining -= chunk;
+        }
+    }
+}
+
+/// Combines all the Token-Buckets that apply to a single Direction of a
+/// Connection
+#[derive(Debug, Clone, Default)]
+pub struct Throttle {
+    buckets: Vec<Arc<TokenBucket>>,
+}
+
+impl Throttle {
+    /// Creates a new Throttle without any Limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the Bucket to the Throttle, if there is one
+    pub fn with(mut self, bucket: Option<Arc<TokenBucket>>) -> Self {
+        self.buckets.extend(bucket);
+        self
+    }
+
+    /// Whether the Throttle enforces any Limit at all
+    pub fn is_limited(&self) -> bool {
+        !self.buckets.is_empty()
+    }
+
+    /// Waits until the given Number of Bytes can be transferred according to
+    /// all the Buckets
+    pub async fn acquire(&self, bytes: u64) {
+        for bucket in self.buckets.iter() {
+            bucket.acquire(bytes).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_without_waiting() {
+        let bucket = TokenBucket::new(RateLimit::new(100));
+
+        let start = Instant::now();
+        bucket.acquire(100).await;
+        assert_eq!(Duration::from_secs(0), start.elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_for_tokens() {
+        let bucket = TokenBucket::new(RateLimit::new(100));
+
+        let start = Instant::now();
+        bucket.acquire(100).await;
+        bucket.acquire(50).await;
+        assert_eq!(Duration::from_millis(500), start.elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn larger_than_burst() {
+        let bucket = TokenBucket::new(RateLimit::new(100).with_burst(10));
+
+        let start = Instant::now();
+        bucket.acquire(110).await;
+        assert_eq!(Duration::from_secs(1), start.elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn zero_burst() {
+        let limit = RateLimit::new(100).with_burst(0);
+        assert_eq!(1, limit.burst());
+
+        let bucket = TokenBucket::new(limit);
+        let start = Instant::now();
+        tokio::time::timeout(Duration::from_secs(5), bucket.acquire(100))
+            .await
+            .unwrap();
+        assert_eq!(Duration::from_millis(990), start.elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn throttle_shared_bucket() {
+        let shared = Arc::new(TokenBucket::new(RateLimit::new(100)));
+        let first = Throttle::new().with(Some(shared.clone()));
+        let second = Throttle::new()
+            .with(Some(shared))
+            .with(Some(Arc::new(TokenBucket::new(RateLimit::new(1000)))));
+
+        let start = Instant::now();
+        first.acquire(100).await;
+        second.acquire(100).await;
+        assert_eq!(Duration::from_secs(1), start.elapsed());
+    }
+
+    #[test]
+    fn strictest_limit() {
+        assert_eq!(None, RateLimit::strictest(None, None));
+        assert_eq!(
+            Some(RateLimit::new(10)),
+            RateLimit::strictest(Some(RateLimit::new(10)), None)
+        );
+        assert_eq!(
+            Some(RateLimit::new(10)),
+            RateLimit::strictest(Some(RateLimit::new(20)), Some(RateLimit::new(10)))
+        );
+        assert_eq!(
+            Some(RateLimit::new(10)),
+            RateLimit::strictest(None, Some(RateLimit::new(10)))
+        );
+        assert_eq!(
+            Some(RateLimit::new(10)),
+            RateLimit::strictest(Some(RateLimit::new(0)), Some(RateLimit::new(10)))
+        );
+        assert_eq!(None, RateLimit::strictest(Some(RateLimit::new(0)), None));
+    }
+}
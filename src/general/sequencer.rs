@@ -0,0 +1,483 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use tokio::sync::oneshot;
+
+use crate::general::ConnectionWriter;
+use crate::message::{CodecError, Message, MessageCodec, MessageHeader, MessageType};
+
+/// The Number of Messages received for a single Connection, after which an
+/// Ack is send for them
+pub const ACK_INTERVAL: u64 = 32;
+
+/// The default Number of Bytes of unacknowledged Messages that are kept for a
+/// single Session, after which it can no longer be resumed
+pub const DEFAULT_MAX_BUFFERED: usize = 16 * 1024 * 1024;
+
+/// The Error returned when resuming a Session fails
+#[derive(Debug)]
+pub enum ResumeError {
+    /// Sending one of the Messages failed
+    Sending(CodecError),
+    /// The Resume of the other side will never be received
+    Aborted,
+}
+
+impl std::fmt::Display for ResumeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResumeError::Sending(e) => write!(f, "Sending the Messages: {}", e),
+            ResumeError::Aborted => write!(f, "The Session will not be resumed"),
+        }
+    }
+}
+
+impl From<CodecError> for ResumeError {
+    fn from(other: CodecError) -> Self {
+        Self::Sending(other)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Outgoing {
+    /// The Sequence-Number of the last Message send for the Connection
+    sent: u64,
+    /// All the Messages that were send but not yet acknowledged, together
+    /// with their Sequence-Number
+    unacked: VecDeque<(u64, Message)>,
+    /// Whether or not the Connection was closed by either side
+    closed: bool,
+}
+
+/// Keeps track of the Messages send and received for every User-Connection
+/// of a single Session, so that the Messages lost with a Control-Connection
+/// can be send again after resuming the Session
+///
+/// Every Data, EOF and Close Message gets an implicit Sequence-Number, which
+/// simply counts the Messages send for its Connection. The receiving side
+/// periodically sends an Ack with the Number of Messages it received for a
+/// Connection, after which those no longer need to be kept around.
+///
+/// Once the unacknowledged Messages would exceed the maximum Number of
+/// Bytes, all of them are dropped and the Session can no longer be resumed.
+#[derive(Debug)]
+pub struct Sequencer {
+    outgoing: Mutex<HashMap<u32, Outgoing>>,
+    incoming: Mutex<HashMap<u32, u64>>,
+    resume: Mutex<Option<oneshot::Sender<()>>>,
+    /// The Number of Bytes of all the unacknowledged Messages
+    ///
+    /// This is only changed while holding the Lock of `outgoing`, so reading
+    /// it and updating it afterwards can not race with another Message
+    buffered: AtomicUsize,
+    max_buffered: usize,
+    overflowed: AtomicBool,
+}
+
+impl Default for Sequencer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sequencer {
+    /// Creates a new empty Sequencer, that keeps at most
+    /// [`DEFAULT_MAX_BUFFERED`] Bytes
+    pub fn new() -> Self {
+        Self::with_max_buffered(DEFAULT_MAX_BUFFERED)
+    }
+
+    /// Creates a new empty Sequencer, that keeps at most the given Number of
+    /// Bytes of unacknowledged Messages
+    pub fn with_max_buffered(max_buffered: usize) -> Self {
+        Self {
+            outgoing: Mutex::new(HashMap::new()),
+            incoming: Mutex::new(HashMap::new()),
+            resume: Mutex::new(None),
+            buffered: AtomicUsize::new(0),
+            max_buffered,
+            overflowed: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether or not the unacknowledged Messages exceeded the maximum Number
+    /// of Bytes, in which case the Session can no longer be resumed
+    pub fn overflowed(&self) -> bool {
+        self.overflowed.load(Ordering::Acquire)
+    }
+
+    /// Whether or not the Message belongs to the Stream of a User-Connection
+    /// and therefore has to be tracked
+    pub fn is_sequenced(header: &MessageHeader) -> bool {
+        matches!(
+            header.get_kind(),
+            MessageType::Data | MessageType::EOF | MessageType::Close
+        )
+    }
+
+    /// Creates the Ack for the given Number of Messages received for the
+    /// Connection
+    pub fn ack(id: u32, count: u64) -> Message {
+        Message::new(
+            MessageHeader::new(id, MessageType::Ack, 8),
+            count.to_be_bytes().to_vec(),
+        )
+    }
+
+    /// Records a Message right before it is send, so it can be send again
+    /// until it was acknowledged
+    pub fn sent(&self, msg: &Message) {
+        let header = msg.get_header();
+        if !Self::is_sequenced(header) {
+            return;
+        }
+
+        if msg.is_close() {
+            self.incoming.lock().unwrap().remove(&header.get_id());
+        }
+        if self.overflowed() {
+            return;
+        }
+
+        // Holding the Lock of `outgoing` keeps `buffered` from changing until
+        // it is updated below
+        let mut outgoing = self.outgoing.lock().unwrap();
+        let size = msg.get_data().len();
+        if self.buffered.load(Ordering::Acquire) + size > self.max_buffered {
+            // Nothing has to be kept anymore, as the Session can not be
+            // resumed without this Message
+            outgoing.clear();
+            self.buffered.store(0, Ordering::Release);
+            self.overflowed.store(true, Ordering::Release);
+            return;
+        }
+
+        let entry = outgoing.entry(header.get_id()).or_default();
+        entry.sent += 1;
+        // This only copies the Header, as the Data is shared
+        entry.unacked.push_back((entry.sent, msg.clone()));
+        entry.closed |= msg.is_close();
+        self.buffered.fetch_add(size, Ordering::AcqRel);
+    }
+
+    /// Records a received Message
+    ///
+    /// # Returns
+    /// The Ack that should be send back, if one is due
+    pub fn received(&self, header: &MessageHeader) -> Option<Message> {
+        if !Self::is_sequenced(header) {
+            return None;
+        }
+        let id = header.get_id();
+
+        if *header.get_kind() == MessageType::Close {
+            let mut outgoing = self.outgoing.lock().unwrap();
+            if let Some(entry) = outgoing.get_mut(&id) {
+                entry.closed = true;
+                if entry.unacked.is_empty() {
+                    outgoing.remove(&id);
+                }
+            }
+            drop(outgoing);
+
+            // The Close is acknowledged right away, as no further Messages
+            // are expected for the Connection
+            let count = self.incoming.lock().unwrap().remove(&id).unwrap_or(0) + 1;
+            return Some(Self::ack(id, count));
+        }
+
+        let mut incoming = self.incoming.lock().unwrap();
+        let count = incoming.entry(id).or_insert(0);
+        *count += 1;
+        match *count % ACK_INTERVAL {
+            0 => Some(Self::ack(id, *count)),
+            _ => None,
+        }
+    }
+
+    /// Records a received Ack with the given Body for the Connection, which
+    /// means that all the Messages up to it no longer have to be kept
+    pub fn acked(&self, id: u32, body: &[u8]) {
+        let count = match body.get(0..8) {
+            Some(raw) => u64::from_be_bytes(raw.try_into().unwrap()),
+            None => return,
+        };
+
+        let mut outgoing = self.outgoing.lock().unwrap();
+        let entry = match outgoing.get_mut(&id) {
+            Some(e) => e,
+            None => return,
+        };
+        while matches!(entry.unacked.front(), Some((seq, _)) if *seq <= count) {
+            if let Some((_, msg)) = entry.unacked.pop_front() {
+                self.buffered
+                    .fetch_sub(msg.get_data().len(), Ordering::AcqRel);
+            }
+        }
+        if entry.closed && entry.unacked.is_empty() {
+            outgoing.remove(&id);
+        }
+    }
+
+    /// Creates the Acks for all the Messages received so far, which are send
+    /// when resuming the Session
+    pub fn acks(&self) -> Vec<Message> {
+        let incoming = self.incoming.lock().unwrap();
+        incoming
+            .iter()
+            .map(|(id, count)| Self::ack(*id, *count))
+            .collect()
+    }
+
+    /// Takes all the Messages that were send but not yet acknowledged, which
+    /// are send again after resuming the Session
+    ///
+    /// The Messages stay recorded as unacknowledged, but are removed from
+    /// here, as they will be recorded again once they are send
+    pub fn unacked(&self) -> Vec<Message> {
+        let mut outgoing = self.outgoing.lock().unwrap();
+        let mut result = Vec::new();
+        for entry in outgoing.values_mut() {
+            entry.sent -= entry.unacked.len() as u64;
+            result.extend(entry.unacked.drain(..).map(|(_, msg)| msg));
+        }
+        self.buffered.store(0, Ordering::Release);
+        result
+    }
+
+    /// Prepares the Sequencer for resuming the Session
+    ///
+    /// # Returns
+    /// A Receiver that is notified once the other side sent its Resume and
+    /// with that all of its Acks
+    pub fn expect_resume(&self) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        *self.resume.lock().unwrap() = Some(tx);
+        rx
+    }
+
+    /// Records that a Resume was received from the other side
+    pub fn resumed(&self) {
+        if let Some(tx) = self.resume.lock().unwrap().take() {
+            let _ = tx.send(());
+        }
+    }
+
+    /// Resumes the Session on a new Connection
+    ///
+    /// This first sends the Acks for all the Messages received so far,
+    /// followed by a Resume. Once the other side did the same, all the
+    /// Messages it did not acknowledge are send again.
+    ///
+    /// # Params:
+    /// * `con`: The new Connection to the other side
+    /// * `resumed`: The Receiver obtained from [`expect_resume`](Self::expect_resume)
+    /// * `codec`: The Codec for the Messages on the new Connection
+    /// * `head_buf`: The Buffer used for encoding the Headers
+    pub async fn resume<C>(
+        &self,
+        con: &mut C,
+        resumed: oneshot::Receiver<()>,
+        codec: &MessageCodec,
+        head_buf: &mut Vec<u8>,
+    ) -> Result<(), ResumeError>
+    where
+        C: ConnectionWriter + Send,
+    {
+        let mut msgs = self.acks();
+        msgs.push(Message::new(
+            MessageHeader::new(0, MessageType::Resume, 0),
+            vec![],
+        ));
+        con.write_msgs(&msgs, codec, head_buf).await?;
+
+        if resumed.await.is_err() {
+            return Err(ResumeError::Aborted);
+        }
+
+        let unacked = self.unacked();
+        for msg in unacked.iter() {
+            self.sent(msg);
+        }
+        con.write_msgs(&unacked, codec, head_buf).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::HeaderFormat;
+
+    fn data(id: u32, content: &[u8]) -> Message {
+        Message::new(
+            MessageHeader::new(id, MessageType::Data, content.len() as u64),
+            content.to_vec(),
+        )
+    }
+
+    #[test]
+    fn ignores_other_messages() {
+        let sequencer = Sequencer::new();
+
+        sequencer.sent(&Message::new(
+            MessageHeader::new(1, MessageType::Connect, 0),
+            vec![],
+        ));
+
+        assert_eq!(0, sequencer.unacked().len());
+        assert_eq!(
+            None,
+            sequencer.received(&MessageHeader::new(1, MessageType::Accept, 0))
+        );
+        assert_eq!(0, sequencer.acks().len());
+    }
+
+    #[test]
+    fn acked_messages_are_dropped() {
+        let sequencer = Sequencer::new();
+
+        sequencer.sent(&data(1, &[1]));
+        sequencer.sent(&data(1, &[2]));
+        sequencer.sent(&data(1, &[3]));
+        sequencer.sent(&data(2, &[4]));
+
+        sequencer.acked(1, &2_u64.to_be_bytes());
+
+        let mut unacked = sequencer.unacked();
+        unacked.sort_by_key(|m| m.get_data().to_vec());
+        assert_eq!(vec![data(1, &[3]), data(2, &[4])], unacked);
+    }
+
+    #[test]
+    fn unacked_keeps_sequence() {
+        let sequencer = Sequencer::new();
+
+        sequencer.sent(&data(1, &[1]));
+        sequencer.sent(&data(1, &[2]));
+        sequencer.acked(1, &1_u64.to_be_bytes());
+
+        let unacked = sequencer.unacked();
+        assert_eq!(vec![data(1, &[2])], unacked);
+
+        // Sending the Message again records it with the same Number
+        sequencer.sent(&unacked[0]);
+        sequencer.acked(1, &2_u64.to_be_bytes());
+        assert_eq!(0, sequencer.unacked().len());
+    }
+
+    #[test]
+    fn acks_every_interval() {
+        let sequencer = Sequencer::new();
+        let header = MessageHeader::new(3, MessageType::Data, 0);
+
+        for _ in 0..ACK_INTERVAL - 1 {
+            assert_eq!(None, sequencer.received(&header));
+        }
+        assert_eq!(
+            Some(Sequencer::ack(3, ACK_INTERVAL)),
+            sequencer.received(&header)
+        );
+
+        sequencer.received(&header);
+        assert_eq!(vec![Sequencer::ack(3, ACK_INTERVAL + 1)], sequencer.acks());
+    }
+
+    #[test]
+    fn close_is_acked_right_away() {
+        let sequencer = Sequencer::new();
+
+        sequencer.received(&MessageHeader::new(3, MessageType::Data, 0));
+        assert_eq!(
+            Some(Sequencer::ack(3, 2)),
+            sequencer.received(&MessageHeader::new(3, MessageType::Close, 0))
+        );
+        assert_eq!(0, sequencer.acks().len());
+    }
+
+    #[test]
+    fn closed_connections_are_removed() {
+        let sequencer = Sequencer::new();
+
+        sequencer.sent(&data(1, &[1]));
+        sequencer.sent(&Message::new(
+            MessageHeader::new(1, MessageType::Close, 0),
+            vec![],
+        ));
+        sequencer.acked(1, &2_u64.to_be_bytes());
+
+        assert_eq!(0, sequencer.outgoing.lock().unwrap().len());
+    }
+
+    #[test]
+    fn overflow_drops_messages() {
+        let sequencer = Sequencer::with_max_buffered(4);
+
+        sequencer.sent(&data(1, &[1, 2]));
+        sequencer.sent(&data(2, &[3, 4]));
+        sequencer.acked(1, &1_u64.to_be_bytes());
+        sequencer.sent(&data(1, &[5, 6]));
+        assert_eq!(false, sequencer.overflowed());
+
+        sequencer.sent(&data(1, &[7]));
+        assert_eq!(true, sequencer.overflowed());
+        assert_eq!(0, sequencer.unacked().len());
+
+        // Nothing is kept anymore, once the Session can not be resumed
+        sequencer.sent(&data(3, &[8]));
+        assert_eq!(0, sequencer.unacked().len());
+    }
+
+    #[tokio::test]
+    async fn resume_session() {
+        let mut writer = crate::general::mocks::MockWriter::new();
+        let mut head_buf = Vec::new();
+
+        let sequencer = Sequencer::new();
+        sequencer.received(&MessageHeader::new(3, MessageType::Data, 0));
+        sequencer.sent(&data(5, &[1, 2]));
+
+        let resumed = sequencer.expect_resume();
+        sequencer.resumed();
+
+        let result = sequencer
+            .resume(
+                &mut writer,
+                resumed,
+                &MessageCodec::new(HeaderFormat::V2),
+                &mut head_buf,
+            )
+            .await;
+        assert_eq!(true, result.is_ok());
+
+        let mut expected = Vec::new();
+        for msg in [
+            Sequencer::ack(3, 1),
+            Message::new(MessageHeader::new(0, MessageType::Resume, 0), vec![]),
+            data(5, &[1, 2]),
+        ] {
+            let mut header = Vec::new();
+            HeaderFormat::V2.serialize(msg.get_header(), &mut header);
+            expected.push(header);
+            expected.push(msg.get_data().to_vec());
+        }
+        assert_eq!(expected, writer.chunks());
+
+        // The Message is still recorded as not acknowledged
+        assert_eq!(vec![data(5, &[1, 2])], sequencer.unacked());
+    }
+
+    #[tokio::test]
+    async fn resume_notifies() {
+        let sequencer = Sequencer::new();
+
+        // A Resume that is not expected is simply ignored
+        sequencer.resumed();
+
+        let rx = sequencer.expect_resume();
+        sequencer.resumed();
+        assert_eq!(Ok(()), rx.await);
+    }
+}
@@ -1,8 +1,11 @@
 use async_trait::async_trait;
+use bytes::Bytes;
+
+use std::io::IoSlice;
 
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-use crate::message::Message;
+use crate::message::{CodecError, Message, MessageCodec, ReadBuffer};
 
 /// Used to read from an actual TCP-Connection
 #[async_trait]
@@ -14,12 +17,38 @@ pub trait ConnectionReader {
     /// Reads from the Connection until the Buffer is filled
     async fn read_full(&mut self, buf: &mut [u8]) -> std::io::Result<usize>;
 
-    /// Reads the next `size` amount of bytes from the connection
-    /// and throws them away
-    async fn drain(&mut self, size: usize) {
-        let mut buf = vec![0; size];
-        if let Err(e) = self.read_full(&mut buf).await {
-            error!("Draining: {}", e);
+    /// Reads an arbitrary amount of bytes, but at most `size`, from the
+    /// Connection into the Buffer
+    async fn read_into(&mut self, buf: &mut ReadBuffer, size: usize) -> std::io::Result<Bytes> {
+        let n = self.read(buf.prepare(size)).await?;
+        Ok(buf.take(n))
+    }
+
+    /// Reads the next Message from the Connection, using the Codec to
+    /// decode it
+    ///
+    /// Only as many Bytes as the Message needs are read, so that none of the
+    /// following Messages are consumed and the Codec or Buffer can be
+    /// replaced between Messages, like after the Handshake
+    ///
+    /// # Params:
+    /// * `codec`: The Codec for the Messages on the Connection
+    /// * `buf`: The Buffer the Message is read into, whose Data is then used
+    ///   for the Message without copying it
+    async fn read_msg(
+        &mut self,
+        codec: &mut MessageCodec,
+        buf: &mut ReadBuffer,
+    ) -> Result<Message, CodecError> {
+        loop {
+            if let Some(msg) = buf.decode(codec)? {
+                return Ok(msg);
+            }
+
+            let missing = codec.missing();
+            if self.read_full(buf.prepare_more(missing)).await? == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
         }
     }
 }
@@ -32,13 +61,42 @@ pub trait ConnectionWriter {
     async fn write_full(&mut self, buf: &[u8]) -> std::io::Result<()>;
 
     /// Attempts to write the message to the underlying connection
-    async fn write_msg(&mut self, msg: &Message, tmp_buf: &mut [u8; 13]) -> std::io::Result<()> {
-        let data = msg.serialize(tmp_buf);
-        if let Err(e) = self.write_full(tmp_buf).await {
-            return Err(e);
-        }
-        if let Err(e) = self.write_full(data).await {
-            return Err(e);
+    ///
+    /// # Params:
+    /// * `msg`: The Message to write
+    /// * `codec`: The Codec used for encoding the Header
+    /// * `head_buf`: The Buffer used for encoding the Header, which can be
+    ///   reused between calls
+    async fn write_msg(
+        &mut self,
+        msg: &Message,
+        codec: &MessageCodec,
+        head_buf: &mut Vec<u8>,
+    ) -> Result<(), CodecError> {
+        self.write_msgs(std::slice::from_ref(msg), codec, head_buf)
+            .await
+    }
+
+    /// Attempts to write all the messages to the underlying connection at
+    /// once, instead of writing them one after another
+    ///
+    /// # Params:
+    /// * `msgs`: The Messages to write in order
+    /// * `codec`: The Codec used for encoding the Headers
+    /// * `head_buf`: The Buffer used for encoding the Headers, which can
+    ///   be reused between calls
+    async fn write_msgs(
+        &mut self,
+        msgs: &[Message],
+        codec: &MessageCodec,
+        head_buf: &mut Vec<u8>,
+    ) -> Result<(), CodecError> {
+        for msg in msgs {
+            head_buf.clear();
+            codec.encode_header(msg.get_header(), head_buf)?;
+            let data = codec.data(msg)?;
+            self.write_full(head_buf).await?;
+            self.write_full(data).await?;
         }
 
         Ok(())
@@ -57,6 +115,26 @@ where
     async fn read_full(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         self.read_exact(buf).await
     }
+
+    // These read directly into the Buffer, without having to initialize it
+    // first
+    async fn read_into(&mut self, buf: &mut ReadBuffer, size: usize) -> std::io::Result<Bytes> {
+        buf.read(self, size).await
+    }
+
+    async fn read_msg(
+        &mut self,
+        codec: &mut MessageCodec,
+        buf: &mut ReadBuffer,
+    ) -> Result<Message, CodecError> {
+        loop {
+            if let Some(msg) = buf.decode(codec)? {
+                return Ok(msg);
+            }
+
+            buf.read_more(self, codec.missing()).await?;
+        }
+    }
 }
 
 #[async_trait]
@@ -67,4 +145,162 @@ where
     async fn write_full(&mut self, buf: &[u8]) -> std::io::Result<()> {
         self.write_all(buf).await
     }
+
+    // This writes all the Headers and Data with as few Syscalls as possible,
+    // without having to copy the Data into a single Buffer first
+    async fn write_msgs(
+        &mut self,
+        msgs: &[Message],
+        codec: &MessageCodec,
+        head_buf: &mut Vec<u8>,
+    ) -> Result<(), CodecError> {
+        head_buf.clear();
+        for msg in msgs {
+            codec.encode_header(msg.get_header(), head_buf)?;
+        }
+
+        let mut slices = Vec::with_capacity(msgs.len() * 2);
+        let mut headers = &head_buf[..];
+        for msg in msgs {
+            let (header, rest) = headers.split_at(codec.format().size(msg.get_header()));
+            headers = rest;
+            slices.push(IoSlice::new(header));
+            let data = codec.data(msg)?;
+            if !data.is_empty() {
+                slices.push(IoSlice::new(data));
+            }
+        }
+
+        let mut remaining = &mut slices[..];
+        while !remaining.is_empty() {
+            let n = self.write_vectored(remaining).await?;
+            if n == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into());
+            }
+            IoSlice::advance_slices(&mut remaining, n);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{HeaderFormat, MessageHeader, MessageType};
+    use tokio_util::codec::Encoder;
+
+    #[tokio::test]
+    async fn write_msgs_vectored() {
+        let msgs = vec![
+            Message::new(MessageHeader::new(1, MessageType::Data, 3), vec![1, 2, 3]),
+            Message::new(MessageHeader::new(0, MessageType::Ping, 0), vec![]),
+            Message::new(MessageHeader::new(2, MessageType::Data, 2), vec![4, 5, 6]),
+        ];
+
+        for format in [HeaderFormat::V1, HeaderFormat::V2] {
+            let mut codec = MessageCodec::new(format);
+            let mut output: Vec<u8> = Vec::new();
+            let mut head_buf = Vec::new();
+            assert_eq!(
+                true,
+                output
+                    .write_msgs(&msgs, &codec, &mut head_buf)
+                    .await
+                    .is_ok()
+            );
+
+            // The same Bytes as when encoding them one after another
+            let mut expected = bytes::BytesMut::new();
+            for msg in msgs.iter() {
+                codec.encode(msg, &mut expected).unwrap();
+            }
+            assert_eq!(&expected[..], &output[..]);
+        }
+    }
+
+    #[tokio::test]
+    async fn write_msgs_too_large() {
+        let codec = MessageCodec::new(HeaderFormat::V2).with_max_size(2);
+        let msg = Message::new(MessageHeader::new(1, MessageType::Data, 3), vec![1, 2, 3]);
+
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(
+            true,
+            output
+                .write_msg(&msg, &codec, &mut Vec::new())
+                .await
+                .is_err()
+        );
+        assert_eq!(true, output.is_empty());
+    }
+
+    #[tokio::test]
+    async fn write_msgs_length_mismatch() {
+        let codec = MessageCodec::new(HeaderFormat::V2);
+        let msgs = [
+            Message::new(MessageHeader::new(1, MessageType::Data, 3), vec![1, 2, 3]),
+            Message::new(MessageHeader::new(2, MessageType::Data, 4), vec![1, 2, 3]),
+        ];
+
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(
+            true,
+            matches!(
+                output.write_msgs(&msgs, &codec, &mut Vec::new()).await,
+                Err(CodecError::LengthMismatch { length: 4, .. })
+            )
+        );
+        assert_eq!(true, output.is_empty());
+    }
+
+    #[tokio::test]
+    async fn read_msgs() {
+        let msgs = [
+            Message::new(MessageHeader::new(300, MessageType::Data, 3), vec![1, 2, 3]),
+            Message::new(MessageHeader::new(0, MessageType::Ping, 0), vec![]),
+        ];
+
+        for format in [HeaderFormat::V1, HeaderFormat::V2] {
+            let mut codec = MessageCodec::new(format);
+            let mut raw = bytes::BytesMut::new();
+            for msg in msgs.iter() {
+                codec.encode(msg, &mut raw).unwrap();
+            }
+            raw.extend_from_slice(&[7; 4]);
+
+            let mut input = &raw[..];
+            let mut buf = ReadBuffer::new();
+            for msg in msgs.iter() {
+                assert_eq!(*msg, input.read_msg(&mut codec, &mut buf).await.unwrap());
+            }
+            // The Bytes following the Messages are not consumed
+            assert_eq!(&[7; 4], input);
+        }
+    }
+
+    #[tokio::test]
+    async fn read_msg_invalid() {
+        let mut codec = MessageCodec::new(HeaderFormat::V2);
+        let mut buf = ReadBuffer::new();
+
+        let mut input = &[0x1f_u8, 0, 0][..];
+        assert_eq!(
+            true,
+            matches!(
+                input.read_msg(&mut codec, &mut buf).await,
+                Err(CodecError::InvalidHeader)
+            )
+        );
+
+        let mut codec = MessageCodec::new(HeaderFormat::V2);
+        let mut input = &[3_u8, 0x80][..];
+        assert_eq!(
+            true,
+            matches!(
+                input.read_msg(&mut codec, &mut ReadBuffer::new()).await,
+                Err(CodecError::Io(_))
+            )
+        );
+    }
 }
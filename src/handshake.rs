@@ -2,5 +2,11 @@ pub mod client;
 mod config;
 mod error;
 pub mod server;
+mod session;
 pub use config::{Config, ConfigError};
 pub use error::HandshakeError;
+pub use session::Session;
+
+/// The Number of Bytes allocated at once for reading the Messages of the
+/// Handshake, which are all rather small
+const BUFFER_SIZE: usize = 1024;
@@ -1,42 +1,55 @@
 use crate::{
     general::{ConnectionReader, ConnectionWriter},
     handshake::HandshakeError,
-    message::{Message, MessageHeader, MessageType},
+    message::{
+        Compression, HeaderFormat, Message, MessageCodec, MessageHeader, MessageType, ReadBuffer,
+    },
 };
 
 use rsa::{BigUint, PaddingScheme, PublicKey, RSAPublicKey};
 
-use super::Config;
-
-pub async fn perform<C>(connection: &mut C, key: &[u8], conf: Config) -> Result<(), HandshakeError>
+use super::{Config, Session, BUFFER_SIZE};
+
+/// Performs the Handshake with the Server
+///
+/// # Returns
+/// The Protocol-Version of the Server, Servers that dont send their Version
+/// are treated as Version 0, the Session it granted, if any, and the
+/// Compression it picked for the Connection, if any
+pub async fn perform<C>(
+    connection: &mut C,
+    key: &[u8],
+    conf: Config,
+) -> Result<(u16, Option<Session>, Option<Compression>), HandshakeError>
 where
     C: ConnectionWriter + ConnectionReader + Send,
 {
+    // The Handshake always uses the original Headers, as the Protocol-Version
+    // of the Server is not known yet
+    let mut codec = MessageCodec::new(HeaderFormat::V1);
+    let mut buf = ReadBuffer::with_capacity(BUFFER_SIZE);
+    let mut head_buf = Vec::new();
+
     // Step 2 - Receive
-    let mut head_buf = [0; 13];
-    let header = match connection.read_full(&mut head_buf).await {
-        Ok(_) => match MessageHeader::deserialize(&head_buf) {
-            Some(m) => m,
-            None => return Err(HandshakeError::DeserializeMessage),
-        },
-        Err(e) => return Err(HandshakeError::ReceivingMessage(e)),
+    let key_msg = match connection.read_msg(&mut codec, &mut buf).await {
+        Ok(m) => m,
+        Err(e) => return Err(HandshakeError::ReceivingKey(e)),
     };
-    if *header.get_kind() != MessageType::Key {
+    if *key_msg.get_header().get_kind() != MessageType::Key {
         return Err(HandshakeError::WrongResponseType);
     }
 
-    let key_length = header.get_length() as usize;
-    let mut key_buf = vec![0; key_length];
-    if let Err(e) = connection.read_full(&mut key_buf).await {
-        return Err(HandshakeError::ReceivingKey(e));
-    }
-
-    let e_bytes = key_buf.split_off(256);
-    let n_bytes = key_buf;
+    // The Key consists of the 256 Bytes of the Modulus followed by the
+    // Exponent
+    let data = key_msg.get_data();
+    let (n_bytes, e_bytes) = match (data.get(..256), data.get(256..)) {
+        (Some(n), Some(e)) => (n, e),
+        _ => return Err(HandshakeError::WrongResponseType),
+    };
 
     let pub_key = match RSAPublicKey::new(
-        BigUint::from_bytes_le(&n_bytes),
-        BigUint::from_bytes_le(&e_bytes),
+        BigUint::from_bytes_le(n_bytes),
+        BigUint::from_bytes_le(e_bytes),
     ) {
         Ok(k) => k,
         Err(e) => return Err(HandshakeError::ParseKey(e)),
@@ -51,21 +64,16 @@ where
     let msg_header = MessageHeader::new(0, MessageType::Verify, encrypted_key.len() as u64);
     let msg = Message::new(msg_header, encrypted_key);
 
-    let mut h_data = [0; 13];
-    if let Err(e) = connection.write_msg(&msg, &mut h_data).await {
+    if let Err(e) = connection.write_msg(&msg, &codec, &mut head_buf).await {
         return Err(HandshakeError::SendingKey(e));
     }
 
-    let mut buf = [0; 13];
-    let header = match connection.read_full(&mut buf).await {
-        Ok(_) => match MessageHeader::deserialize(&buf) {
-            Some(c) => c,
-            None => return Err(HandshakeError::DeserializeMessage),
-        },
+    let ack_msg = match connection.read_msg(&mut codec, &mut buf).await {
+        Ok(m) => m,
         Err(e) => return Err(HandshakeError::ReceivingMessage(e)),
     };
 
-    if *header.get_kind() != MessageType::Acknowledge {
+    if *ack_msg.get_header().get_kind() != MessageType::Acknowledge {
         return Err(HandshakeError::WrongResponseType);
     }
 
@@ -74,24 +82,35 @@ where
         MessageHeader::new(0, MessageType::Config, config_msg_content.len() as u64);
     let config_msg = Message::new(config_msg_header, config_msg_content.to_vec());
 
-    if let Err(e) = connection.write_msg(&config_msg, &mut h_data).await {
+    if let Err(e) = connection
+        .write_msg(&config_msg, &codec, &mut head_buf)
+        .await
+    {
         return Err(HandshakeError::SendingMessage(e));
     }
 
-    let mut buf = [0; 13];
-    let header = match connection.read_full(&mut buf).await {
-        Ok(_) => match MessageHeader::deserialize(&buf) {
-            Some(c) => c,
-            None => return Err(HandshakeError::DeserializeMessage),
-        },
+    let ack_msg = match connection.read_msg(&mut codec, &mut buf).await {
+        Ok(m) => m,
         Err(e) => return Err(HandshakeError::ReceivingMessage(e)),
     };
 
-    if *header.get_kind() != MessageType::Acknowledge {
+    if *ack_msg.get_header().get_kind() != MessageType::Acknowledge {
         return Err(HandshakeError::WrongResponseType);
     }
 
-    Ok(())
+    let version_buf = ack_msg.get_data();
+    let server_version = match version_buf.get(0..2) {
+        Some(raw) => u16::from_be_bytes([raw[0], raw[1]]),
+        None => 0,
+    };
+    let session = version_buf.get(2..).and_then(Session::from_bytes);
+    // Only an Algorithm the Client offered can be used
+    let compression = version_buf
+        .get(2 + Session::SIZE)
+        .filter(|bit| conf.compression() & **bit != 0)
+        .and_then(|bit| Compression::from_bit(*bit));
+
+    Ok((server_version, session, compression))
 }
 
 #[cfg(test)]
@@ -145,10 +164,10 @@ mod tests {
         let config = Config::new(13);
 
         assert_eq!(
-            true,
+            Ok((0, None, None)),
             perform(&mut connection, key_password, config.clone())
                 .await
-                .is_ok()
+                .map_err(|_| ())
         );
 
         let chunks = connection.writer_mut().chunks();
@@ -157,16 +176,124 @@ mod tests {
         //let encrypted_chunk_header = chunks.get(0).unwrap();
         let encrypted_chunk_body = chunks.get(1).unwrap();
         let recv_key = priv_key
-            .decrypt(PaddingScheme::PKCS1v15Encrypt, &encrypted_chunk_body)
+            .decrypt(PaddingScheme::PKCS1v15Encrypt, encrypted_chunk_body)
             .unwrap();
         assert_eq!(key_password, recv_key);
 
         //let port_chunk_header = chunks.get(2).unwrap();
         let port_chunk_body = chunks.get(3).unwrap();
         let recv_port = u16::from_be_bytes([
-            *port_chunk_body.get(0).unwrap(),
+            *port_chunk_body.first().unwrap(),
             *port_chunk_body.get(1).unwrap(),
         ]);
         assert_eq!(config.port(), recv_port);
     }
+
+    #[tokio::test]
+    async fn short_key() {
+        let mut connection = MockConnection::new();
+        connection.reader_mut().add_message(Message::new(
+            MessageHeader::new(0, MessageType::Key, 10),
+            vec![1; 10],
+        ));
+
+        let result = perform(&mut connection, "test".as_bytes(), Config::new(13)).await;
+        assert_eq!(
+            true,
+            matches!(result, Err(HandshakeError::WrongResponseType))
+        );
+    }
+
+    #[tokio::test]
+    async fn valid_handshake_server_version() {
+        let mut connection = MockConnection::new();
+
+        let (key_msg, _) = setup_key();
+
+        connection.reader_mut().add_message(key_msg);
+        connection.reader_mut().add_message(Message::new(
+            MessageHeader::new(0, MessageType::Acknowledge, 0),
+            Vec::new(),
+        ));
+        connection.reader_mut().add_message(Message::new(
+            MessageHeader::new(0, MessageType::Acknowledge, 2),
+            2_u16.to_be_bytes().to_vec(),
+        ));
+
+        assert_eq!(
+            Ok((2, None, None)),
+            perform(&mut connection, "test".as_bytes(), Config::new(13))
+                .await
+                .map_err(|_| ())
+        );
+    }
+
+    #[tokio::test]
+    async fn valid_handshake_session() {
+        let mut connection = MockConnection::new();
+
+        let (key_msg, _) = setup_key();
+
+        let session = Session::new(123, true, std::time::Duration::from_secs(5));
+        let mut body = 5_u16.to_be_bytes().to_vec();
+        body.extend_from_slice(&session.to_bytes());
+
+        connection.reader_mut().add_message(key_msg);
+        connection.reader_mut().add_message(Message::new(
+            MessageHeader::new(0, MessageType::Acknowledge, 0),
+            Vec::new(),
+        ));
+        connection.reader_mut().add_message(Message::new(
+            MessageHeader::new(0, MessageType::Acknowledge, body.len() as u64),
+            body,
+        ));
+
+        assert_eq!(
+            Ok((5, Some(session), None)),
+            perform(
+                &mut connection,
+                "test".as_bytes(),
+                Config::new(13).with_session(123)
+            )
+            .await
+            .map_err(|_| ())
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[tokio::test]
+    async fn valid_handshake_compression() {
+        let (key_msg, _) = setup_key();
+
+        let mut body = crate::PROTOCOL_VERSION.to_be_bytes().to_vec();
+        body.extend_from_slice(&[0; Session::SIZE]);
+        body.push(Compression::Zstd.bit());
+
+        for (offered, expected) in [
+            (vec![Compression::Zstd], Some(Compression::Zstd)),
+            (vec![], None),
+        ] {
+            let mut connection = MockConnection::new();
+            connection.reader_mut().add_message(key_msg.clone());
+            connection.reader_mut().add_message(Message::new(
+                MessageHeader::new(0, MessageType::Acknowledge, 0),
+                Vec::new(),
+            ));
+            connection.reader_mut().add_message(Message::new(
+                MessageHeader::new(0, MessageType::Acknowledge, body.len() as u64),
+                body.clone(),
+            ));
+
+            assert_eq!(
+                Ok((crate::PROTOCOL_VERSION, None, expected)),
+                perform(
+                    &mut connection,
+                    "test".as_bytes(),
+                    Config::new(13).with_compression(&offered)
+                )
+                .await
+                .map_err(|_| ())
+            );
+        }
+    }
 }
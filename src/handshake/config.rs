@@ -1,6 +1,6 @@
 use std::convert::TryInto;
 
-use crate::PROTOCOL_VERSION;
+use crate::{message::Compression, PROTOCOL_VERSION};
 
 /// The Configuration for Connecting to a Server, this contains all the needed Data for
 /// establishing a Connection, like the desired Port
@@ -10,6 +10,21 @@ pub struct Config {
     /// The Version number of the Protocol, although I never exepct this to exceed 255(8-bit) it's
     /// better to be save with this than regret it later on
     prot_version: u16,
+    /// The Weight of the Client in relation to the other Clients for the same Port, which is
+    /// used by the Server when balancing the User-Connections
+    weight: u16,
+    /// The maximum Number of Bytes per Second the Client wants to transfer
+    /// over its Connection, 0 if it does not want to be limited
+    rate_limit: u64,
+    /// The Token of the Session the Client wants to resume, 0 if it wants to
+    /// start a new Session
+    session: u128,
+    /// The Group of Connections this Connection belongs to, 0 if the Client
+    /// only uses a single Connection
+    group: u128,
+    /// The Algorithms the Client supports for compressing Messages, with one
+    /// Bit for every Algorithm
+    compression: u8,
 }
 
 #[derive(Debug, PartialEq)]
@@ -23,9 +38,48 @@ impl Config {
         Self {
             port,
             prot_version: PROTOCOL_VERSION,
+            weight: 1,
+            rate_limit: 0,
+            session: 0,
+            group: 0,
+            compression: 0,
         }
     }
 
+    /// Sets the Weight of the Client
+    pub fn with_weight(mut self, weight: u16) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Sets the maximum Number of Bytes per Second the Client wants to
+    /// transfer, the Server may still enforce a stricter Limit
+    ///
+    /// 0 means that the Client does not want to be limited
+    pub fn with_rate_limit(mut self, bytes_per_second: u64) -> Self {
+        self.rate_limit = bytes_per_second;
+        self
+    }
+
+    /// Sets the Token of the Session the Client wants to resume
+    pub fn with_session(mut self, token: u128) -> Self {
+        self.session = token;
+        self
+    }
+
+    /// Sets the Group of Connections, that are all handled as a single Client
+    /// by the Server
+    pub fn with_group(mut self, group: u128) -> Self {
+        self.group = group;
+        self
+    }
+
+    /// Sets the Algorithms the Client supports for compressing Messages
+    pub fn with_compression(mut self, algorithms: &[Compression]) -> Self {
+        self.compression = Compression::mask(algorithms);
+        self
+    }
+
     /// The Port of the Configuration
     pub fn port(&self) -> u16 {
         self.port
@@ -36,13 +90,53 @@ impl Config {
         self.prot_version
     }
 
+    /// The Weight of the Client
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    /// The Rate-Limit requested by the Client in Bytes per Second, if any
+    pub fn rate_limit(&self) -> Option<u64> {
+        match self.rate_limit {
+            0 => None,
+            x => Some(x),
+        }
+    }
+
+    /// The Token of the Session the Client wants to resume, if any
+    pub fn session(&self) -> Option<u128> {
+        match self.session {
+            0 => None,
+            x => Some(x),
+        }
+    }
+
+    /// The Group of Connections this Connection belongs to, if any
+    pub fn group(&self) -> Option<u128> {
+        match self.group {
+            0 => None,
+            x => Some(x),
+        }
+    }
+
+    /// The Algorithms the Client supports for compressing Messages, with one
+    /// Bit for every Algorithm
+    pub fn compression(&self) -> u8 {
+        self.compression
+    }
+
     /// Converts the Config into its Byte representation to be transmitted over the network when
     /// connecting
-    pub fn to_bytes(&self) -> [u8; 4] {
-        let mut result = [0; 4];
+    pub fn to_bytes(&self) -> [u8; 47] {
+        let mut result = [0; 47];
 
         result[0..2].copy_from_slice(&self.port.to_be_bytes());
         result[2..4].copy_from_slice(&self.prot_version.to_be_bytes());
+        result[4..6].copy_from_slice(&self.weight.to_be_bytes());
+        result[6..14].copy_from_slice(&self.rate_limit.to_be_bytes());
+        result[14..30].copy_from_slice(&self.session.to_be_bytes());
+        result[30..46].copy_from_slice(&self.group.to_be_bytes());
+        result[46] = self.compression;
 
         result
     }
@@ -65,7 +159,49 @@ impl Config {
             }
         };
 
-        Ok(Self { port, prot_version })
+        let weight = match raw.len() {
+            x if x < 6 => 1,
+            _ => {
+                let weight_bytes = &raw[4..6];
+                u16::from_be_bytes(weight_bytes.try_into().unwrap())
+            }
+        };
+
+        let rate_limit = match raw.len() {
+            x if x < 14 => 0,
+            _ => {
+                let rate_bytes = &raw[6..14];
+                u64::from_be_bytes(rate_bytes.try_into().unwrap())
+            }
+        };
+
+        let session = match raw.len() {
+            x if x < 30 => 0,
+            _ => {
+                let session_bytes = &raw[14..30];
+                u128::from_be_bytes(session_bytes.try_into().unwrap())
+            }
+        };
+
+        let group = match raw.len() {
+            x if x < 46 => 0,
+            _ => {
+                let group_bytes = &raw[30..46];
+                u128::from_be_bytes(group_bytes.try_into().unwrap())
+            }
+        };
+
+        let compression = raw.get(46).copied().unwrap_or(0);
+
+        Ok(Self {
+            port,
+            prot_version,
+            weight,
+            rate_limit,
+            session,
+            group,
+            compression,
+        })
     }
 }
 
@@ -78,16 +214,21 @@ mod tests {
         let conf = Config {
             port: 13,
             prot_version: 1,
+            weight: 3,
+            rate_limit: 1024,
+            session: 7,
+            group: 9,
+            compression: 3,
         };
 
-        let port_bytes = 13_u16.to_be_bytes();
-        let prot_version_bytes = 1_u16.to_be_bytes();
-        let expected = [
-            port_bytes[0],
-            port_bytes[1],
-            prot_version_bytes[0],
-            prot_version_bytes[1],
-        ];
+        let mut expected = [0; 47];
+        expected[0..2].copy_from_slice(&13_u16.to_be_bytes());
+        expected[2..4].copy_from_slice(&1_u16.to_be_bytes());
+        expected[4..6].copy_from_slice(&3_u16.to_be_bytes());
+        expected[6..14].copy_from_slice(&1024_u64.to_be_bytes());
+        expected[14..30].copy_from_slice(&7_u128.to_be_bytes());
+        expected[30..46].copy_from_slice(&9_u128.to_be_bytes());
+        expected[46] = 3;
 
         let result = conf.to_bytes();
 
@@ -101,6 +242,11 @@ mod tests {
         let expected = Ok(Config {
             port: 13,
             prot_version: 0,
+            weight: 1,
+            rate_limit: 0,
+            session: 0,
+            group: 0,
+            compression: 0,
         });
 
         let result = Config::from_bytes(&input);
@@ -116,12 +262,92 @@ mod tests {
         let expected = Ok(Config {
             port: 13,
             prot_version: 1,
+            weight: 1,
+            rate_limit: 0,
+            session: 0,
+            group: 0,
+            compression: 0,
+        });
+
+        let result = Config::from_bytes(&input);
+
+        assert_eq!(expected, result);
+    }
+    #[test]
+    fn from_bytes_port_version_weight() {
+        let mut input = [0; 6];
+        input[0..2].copy_from_slice(&13_u16.to_be_bytes());
+        input[2..4].copy_from_slice(&2_u16.to_be_bytes());
+        input[4..6].copy_from_slice(&5_u16.to_be_bytes());
+
+        let expected = Ok(Config {
+            port: 13,
+            prot_version: 2,
+            weight: 5,
+            rate_limit: 0,
+            session: 0,
+            group: 0,
+            compression: 0,
         });
 
         let result = Config::from_bytes(&input);
 
         assert_eq!(expected, result);
     }
+    #[test]
+    fn from_bytes_rate_limit() {
+        let mut input = [0; 14];
+        input[0..2].copy_from_slice(&13_u16.to_be_bytes());
+        input[2..4].copy_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+        input[4..6].copy_from_slice(&1_u16.to_be_bytes());
+        input[6..14].copy_from_slice(&2048_u64.to_be_bytes());
+
+        let result = Config::from_bytes(&input).unwrap();
+
+        assert_eq!(Some(2048), result.rate_limit());
+        assert_eq!(Config::new(13).with_rate_limit(2048), result);
+    }
+    #[test]
+    fn from_bytes_session() {
+        let mut input = [0; 30];
+        input[0..2].copy_from_slice(&13_u16.to_be_bytes());
+        input[2..4].copy_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+        input[4..6].copy_from_slice(&1_u16.to_be_bytes());
+        input[14..30].copy_from_slice(&123_u128.to_be_bytes());
+
+        let result = Config::from_bytes(&input).unwrap();
+
+        assert_eq!(Some(123), result.session());
+        assert_eq!(None, result.rate_limit());
+        assert_eq!(Config::new(13).with_session(123), result);
+    }
+    #[test]
+    fn from_bytes_group() {
+        let mut input = [0; 46];
+        input[0..2].copy_from_slice(&13_u16.to_be_bytes());
+        input[2..4].copy_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+        input[4..6].copy_from_slice(&1_u16.to_be_bytes());
+        input[30..46].copy_from_slice(&42_u128.to_be_bytes());
+
+        let result = Config::from_bytes(&input).unwrap();
+
+        assert_eq!(Some(42), result.group());
+        assert_eq!(None, result.session());
+        assert_eq!(Config::new(13).with_group(42), result);
+    }
+    #[test]
+    fn from_bytes_compression() {
+        let mut input = [0; 47];
+        input[0..2].copy_from_slice(&13_u16.to_be_bytes());
+        input[2..4].copy_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+        input[4..6].copy_from_slice(&1_u16.to_be_bytes());
+        input[46] = 2;
+
+        let result = Config::from_bytes(&input).unwrap();
+
+        assert_eq!(2, result.compression());
+        assert_eq!(None, result.group());
+    }
 
     #[test]
     fn missing_port() {
@@ -1,19 +1,18 @@
 use super::ConfigError;
+use crate::message::CodecError;
 
 /// The Errors that could be encountered during the Validation
 /// Phase of establishing a Connection
 #[derive(Debug)]
 pub enum HandshakeError {
     /// The Public-Key could not be send to the Client
-    SendingKey(std::io::Error),
+    SendingKey(CodecError),
     /// The Public-Key could not be received from the Server
-    ReceivingKey(std::io::Error),
+    ReceivingKey(CodecError),
     /// The Message could not be send
-    SendingMessage(std::io::Error),
+    SendingMessage(CodecError),
     /// The next Message could not be received
-    ReceivingMessage(std::io::Error),
-    /// The received Message was malformed
-    DeserializeMessage,
+    ReceivingMessage(CodecError),
     /// Received the wrong message
     WrongResponseType,
     /// The encryption Key could not be generated
@@ -28,7 +27,7 @@ pub enum HandshakeError {
     /// The Client-Key and Server-Key don't match
     MismatchedKeys,
     /// The Acknowledge-Message could not be send
-    SendingAcknowledge(std::io::Error),
+    SendingAcknowledge(CodecError),
     /// The Config-Message was malformed in some way
     MalformedConfig(ConfigError),
     /// The received Port is not considered Valid
@@ -38,3 +37,30 @@ pub enum HandshakeError {
         other: u16,
     },
 }
+
+impl std::fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HandshakeError::SendingKey(e) => write!(f, "Sending the Public-Key: {}", e),
+            HandshakeError::ReceivingKey(e) => write!(f, "Receiving the Public-Key: {}", e),
+            HandshakeError::SendingMessage(e) => write!(f, "Sending a Message: {}", e),
+            HandshakeError::ReceivingMessage(e) => write!(f, "Receiving a Message: {}", e),
+            HandshakeError::WrongResponseType => write!(f, "Received the wrong Message"),
+            HandshakeError::GeneratingKey(e) => write!(f, "Generating the Key: {}", e),
+            HandshakeError::ParseKey(e) => write!(f, "Parsing the Public-Key: {}", e),
+            HandshakeError::Encrypting(e) => write!(f, "Encrypting the Key: {}", e),
+            HandshakeError::Decrypting(e) => write!(f, "Decrypting the Key: {}", e),
+            HandshakeError::MismatchedKeys => write!(f, "The Keys dont match"),
+            HandshakeError::SendingAcknowledge(e) => {
+                write!(f, "Sending the Acknowledge: {}", e)
+            }
+            HandshakeError::MalformedConfig(e) => write!(f, "The Config is malformed: {:?}", e),
+            HandshakeError::InvalidPort => write!(f, "The Port is invalid"),
+            HandshakeError::MismatchedProtocol { current, other } => write!(
+                f,
+                "Mismatched Protocol-Versions: {} (current) and {} (other)",
+                current, other
+            ),
+        }
+    }
+}
@@ -1,14 +1,16 @@
 use crate::{
     general::{ConnectionReader, ConnectionWriter},
     handshake::HandshakeError,
-    message::{Message, MessageHeader, MessageType},
+    message::{
+        Compression, HeaderFormat, Message, MessageCodec, MessageHeader, MessageType, ReadBuffer,
+    },
     PROTOCOL_VERSION,
 };
 
 use rand::rngs::OsRng;
 use rsa::{PaddingScheme, PublicKeyParts, RSAPrivateKey, RSAPublicKey};
 
-use super::Config;
+use super::{Config, Session, BUFFER_SIZE};
 
 // The validation flow is like this
 //
@@ -20,17 +22,33 @@ use super::Config;
 // 5b. If invalid: Server closes the connection
 // 6. Client sends the Port-Packet
 // 7. Server validates the given Port
-// 7a. Valid: Sends ACK-Message back
+// 7a. Valid: Sends ACK-Message back, containing the Protocol-Version of the Server,
+//     the Session of the Client and the negotiated Compression, if it supports them
 // 7b. Invalid: Closes the Connection
-pub async fn perform<C, V>(
+//
+// # Params:
+// * `session`: Determines the Session of the Client, which must not have any
+//   side effects, as the Handshake can still fail after it was called
+// * `compression`: The Algorithms supported by the Server, in the Order they
+//   are preferred in
+pub async fn perform<C, V, S>(
     con: &mut C,
     key: &[u8],
     is_port_valid: V,
-) -> Result<Config, HandshakeError>
+    session: S,
+    compression: &[Compression],
+) -> Result<(Config, Option<Session>, Option<Compression>), HandshakeError>
 where
     C: ConnectionReader + ConnectionWriter + Send,
     V: FnOnce(u16) -> bool,
+    S: FnOnce(&Config) -> Option<Session>,
 {
+    // The Handshake always uses the original Headers, as the Protocol-Version
+    // of the Client is not known yet
+    let mut codec = MessageCodec::new(HeaderFormat::V1);
+    let mut buf = ReadBuffer::with_capacity(BUFFER_SIZE);
+    let mut head_buf = Vec::new();
+
     // Step 2
     let mut rng = OsRng;
     let priv_key = match RSAPrivateKey::new(&mut rng, 2048) {
@@ -48,31 +66,20 @@ where
     let msg_header = MessageHeader::new(0, MessageType::Key, data.len() as u64);
     let msg = Message::new(msg_header, data);
 
-    let mut h_data = [0; 13];
-    if let Err(e) = con.write_msg(&msg, &mut h_data).await {
+    if let Err(e) = con.write_msg(&msg, &codec, &mut head_buf).await {
         return Err(HandshakeError::SendingKey(e));
     }
 
     // Step 4
-    let mut head_buf = [0; 13];
-    let header = match con.read_full(&mut head_buf).await {
-        Ok(_) => match MessageHeader::deserialize(&head_buf) {
-            Some(m) => m,
-            None => return Err(HandshakeError::DeserializeMessage),
-        },
+    let verify_msg = match con.read_msg(&mut codec, &mut buf).await {
+        Ok(m) => m,
         Err(e) => return Err(HandshakeError::ReceivingMessage(e)),
     };
-    if *header.get_kind() != MessageType::Verify {
+    if *verify_msg.get_header().get_kind() != MessageType::Verify {
         return Err(HandshakeError::WrongResponseType);
     }
 
-    let key_length = header.get_length() as usize;
-    let mut recv_encrypted_key = vec![0; key_length];
-    if let Err(e) = con.read_full(&mut recv_encrypted_key).await {
-        return Err(HandshakeError::ReceivingMessage(e));
-    }
-
-    let recv_key = match priv_key.decrypt(PaddingScheme::PKCS1v15Encrypt, &recv_encrypted_key) {
+    let recv_key = match priv_key.decrypt(PaddingScheme::PKCS1v15Encrypt, verify_msg.get_data()) {
         Ok(raw_key) => raw_key,
         Err(e) => return Err(HandshakeError::Decrypting(e)),
     };
@@ -86,40 +93,53 @@ where
     // Step 5b
     let ack_header = MessageHeader::new(0, MessageType::Acknowledge, 0);
     let ack_msg = Message::new(ack_header, vec![]);
-    let mut ack_data = [0; 13];
-    if let Err(e) = con.write_msg(&ack_msg, &mut ack_data).await {
+    if let Err(e) = con.write_msg(&ack_msg, &codec, &mut head_buf).await {
         return Err(HandshakeError::SendingAcknowledge(e));
     }
 
     // Step 6
-    let header = match con.read_full(&mut head_buf).await {
-        Ok(_) => match MessageHeader::deserialize(&head_buf) {
-            Some(h) => h,
-            None => return Err(HandshakeError::DeserializeMessage),
-        },
+    let config_msg = match con.read_msg(&mut codec, &mut buf).await {
+        Ok(m) => m,
         Err(e) => return Err(HandshakeError::ReceivingMessage(e)),
     };
-    if *header.get_kind() != MessageType::Config {
+    if *config_msg.get_header().get_kind() != MessageType::Config {
         return Err(HandshakeError::WrongResponseType);
     }
 
-    let mut recv_buffer = vec![0; header.get_length() as usize];
-    if let Err(e) = con.read_full(&mut recv_buffer).await {
-        return Err(HandshakeError::ReceivingMessage(e));
-    }
-
-    let config = match Config::from_bytes(&recv_buffer) {
+    let config = match Config::from_bytes(config_msg.get_data()) {
         Ok(c) => c,
         Err(e) => return Err(HandshakeError::MalformedConfig(e)),
     };
 
     //  Step 7
+    let mut granted = None;
+    let mut negotiated = None;
     if is_port_valid(config.port()) {
         // Step 7a
-        let ack_header = MessageHeader::new(0, MessageType::Acknowledge, 0);
-        let ack_msg = Message::new(ack_header, vec![]);
-        let mut ack_data = [0; 13];
-        if let Err(e) = con.write_msg(&ack_msg, &mut ack_data).await {
+        // Clients that support Version 2 also expect the Protocol-Version of the
+        // Server in the Body of the Acknowledge
+        let mut ack_body = match config.protocol_version() {
+            x if x >= 2 => PROTOCOL_VERSION.to_be_bytes().to_vec(),
+            _ => vec![],
+        };
+        // Clients that support Version 5 also expect their Session, where a
+        // Token of 0 indicates that no Session was granted
+        if config.protocol_version() >= 5 {
+            granted = session(&config);
+            match granted.as_ref() {
+                Some(s) => ack_body.extend_from_slice(&s.to_bytes()),
+                None => ack_body.extend_from_slice(&[0; Session::SIZE]),
+            };
+        }
+        // Clients that support Version 7 also expect the Compression, where 0
+        // indicates that Messages are not compressed
+        if config.protocol_version() >= 7 {
+            negotiated = Compression::negotiate(compression, config.compression());
+            ack_body.push(negotiated.map(|c| c.bit()).unwrap_or(0));
+        }
+        let ack_header = MessageHeader::new(0, MessageType::Acknowledge, ack_body.len() as u64);
+        let ack_msg = Message::new(ack_header, ack_body);
+        if let Err(e) = con.write_msg(&ack_msg, &codec, &mut head_buf).await {
             return Err(HandshakeError::SendingAcknowledge(e));
         }
     } else {
@@ -135,5 +155,5 @@ where
         });
     }
 
-    Ok(config)
+    Ok((config, granted, negotiated))
 }
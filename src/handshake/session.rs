@@ -0,0 +1,101 @@
+use std::convert::TryInto;
+use std::time::Duration;
+
+/// The Session granted by the Server at the End of the Handshake, which allows
+/// the Client to resume its User-Connections after reconnecting
+#[derive(Debug, PartialEq, Clone)]
+pub struct Session {
+    /// The Token the Client presents when reconnecting
+    token: u128,
+    /// Whether or not an existing Session was resumed
+    resumed: bool,
+    /// How long the Server keeps the Session around after losing the Connection
+    grace: Duration,
+}
+
+impl Session {
+    /// The Number of Bytes needed for the Byte representation of a Session
+    pub const SIZE: usize = 25;
+
+    /// Creates a new Session with the given Values
+    pub fn new(token: u128, resumed: bool, grace: Duration) -> Self {
+        Self {
+            token,
+            resumed,
+            grace,
+        }
+    }
+
+    /// The Token of the Session
+    pub fn token(&self) -> u128 {
+        self.token
+    }
+    /// Whether or not an existing Session was resumed
+    pub fn resumed(&self) -> bool {
+        self.resumed
+    }
+    /// How long the Server keeps the Session after losing the Connection
+    pub fn grace(&self) -> Duration {
+        self.grace
+    }
+
+    /// Converts the Session into its Byte representation, which is appended
+    /// to the last Acknowledge of the Handshake
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut result = [0; Self::SIZE];
+
+        result[0..16].copy_from_slice(&self.token.to_be_bytes());
+        result[16] = self.resumed as u8;
+        result[17..25].copy_from_slice(&(self.grace.as_millis() as u64).to_be_bytes());
+
+        result
+    }
+
+    /// Converts the Raw-Bytes back into a Session
+    ///
+    /// Returns:
+    /// * None if there are not enough Bytes or the Token is 0
+    /// * Some with the parsed Session
+    pub fn from_bytes(raw: &[u8]) -> Option<Self> {
+        if raw.len() < Self::SIZE {
+            return None;
+        }
+
+        let token = u128::from_be_bytes(raw[0..16].try_into().unwrap());
+        if token == 0 {
+            return None;
+        }
+        let resumed = raw[16] != 0;
+        let grace = u64::from_be_bytes(raw[17..25].try_into().unwrap());
+
+        Some(Self {
+            token,
+            resumed,
+            grace: Duration::from_millis(grace),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_and_from_bytes() {
+        let session = Session::new(1234, true, Duration::from_secs(30));
+
+        let raw = session.to_bytes();
+
+        assert_eq!(Some(session), Session::from_bytes(&raw));
+    }
+
+    #[test]
+    fn from_bytes_too_short() {
+        assert_eq!(None, Session::from_bytes(&[0; 10]));
+    }
+
+    #[test]
+    fn from_bytes_no_token() {
+        assert_eq!(None, Session::from_bytes(&[0; Session::SIZE]));
+    }
+}
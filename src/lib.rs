@@ -1,4 +1,5 @@
 #![warn(missing_docs)]
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
 //! This crate provides a simply way to start a tunneler
 //! server and client that can also easily be integradted into
 //! other projects allowing you to expose your services running
@@ -8,10 +9,23 @@
 ///
 /// Version:
 /// * 0: This Version combines all the Versions before Version tracking for the Protcol was
-/// introduced
+///   introduced
 /// * 1: The first Version of the tracked Protocol Version, which is compatible with the last
-/// Protcol of Version 0
-const PROTOCOL_VERSION: u16 = 1;
+///   Protcol of Version 0
+/// * 2: Adds the Ping and Pong Messages and the Server now sends its own Protocol-Version
+///   in the last Acknowledge of the Handshake, if the Client also supports Version 2
+/// * 3: The Client now responds to every Connect with either an Accept or Reject Message and
+///   the Server waits for that Response before forwarding any Data
+/// * 4: Close Messages can now contain a Reason in their Body
+/// * 5: Adds Sessions, which allow a Client to resume its User-Connections after
+///   reconnecting, using the new Ack and Resume Messages
+/// * 6: Clients can open multiple Connections to the Server, which are grouped
+///   into a single Client using the Group in their Config
+/// * 7: The Data of Messages can be compressed, using an Algorithm negotiated in the
+///   Handshake, and compressed Messages are marked in the highest Bit of their Type
+/// * 8: After the Handshake, the Headers of Messages use a compact Format, where the ID
+///   and Length are encoded as Varints and the highest Bits of the Type are used as Flags
+const PROTOCOL_VERSION: u16 = 8;
 
 #[macro_use]
 mod logging;
@@ -29,6 +43,8 @@ mod streams;
 /// All the Metrics related functionality
 pub mod metrics;
 
+pub mod accesslog;
+
 pub(crate) mod general;
-pub use general::Details;
+pub use general::{ChunkSize, Details, RateLimit};
 pub(crate) mod handshake;
@@ -22,3 +22,21 @@ macro_rules! error {
         tracing::error!($($arg)+);
     };
 }
+
+/// Wraps the given Future in the given `tracing` Span, if the `trace` Feature
+/// is enabled and otherwise simply returns the Future itself
+///
+/// The Span-Expression is only evaluated if the `trace` Feature is enabled and
+/// is evaluated before the Future, so it can still borrow Values that are
+/// moved into the Future
+macro_rules! instrument {
+    ($fut:expr, $span:expr) => {{
+        #[cfg(feature = "trace")]
+        let span = $span;
+        #[cfg(feature = "trace")]
+        let fut = tracing_futures::Instrument::instrument($fut, span);
+        #[cfg(not(feature = "trace"))]
+        let fut = $fut;
+        fut
+    }};
+}
@@ -0,0 +1,219 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::codec::Decoder;
+
+use crate::message::{CodecError, Message, MessageCodec};
+
+/// The Number of Bytes allocated at once by a ReadBuffer, if it was not
+/// given a different Capacity
+const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+/// A reusable Buffer to read the Data of Messages into
+///
+/// The Data read into the Buffer is handed out as `Bytes` without copying it,
+/// which can then be used as the Data of a Message. All the Data handed out
+/// shares the same Allocation, which is reused once all of it was dropped,
+/// so that reading does not need a new Allocation for every Message.
+///
+/// # Example
+/// ```rust
+/// # use tunneler_core::message::ReadBuffer;
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let mut buffer = ReadBuffer::new();
+/// let mut reader: &[u8] = &[1, 2, 3, 4];
+///
+/// let data = buffer.read_exact(&mut reader, 2).await.unwrap();
+/// assert_eq!(&[1, 2], &data[..]);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ReadBuffer {
+    inner: BytesMut,
+    capacity: usize,
+}
+
+impl ReadBuffer {
+    /// Creates a new Buffer with the Default-Capacity
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Creates a new Buffer, that allocates the given Number of Bytes at once
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: BytesMut::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Makes sure that the next `size` Bytes fit into the Buffer
+    fn reserve(&mut self, size: usize) {
+        self.inner.clear();
+        self.grow(size);
+    }
+
+    /// Makes sure that `size` more Bytes fit into the Buffer, after the ones
+    /// that were already read
+    fn grow(&mut self, size: usize) {
+        if self.inner.capacity() - self.inner.len() < size {
+            // This reclaims the existing Allocation, if none of the Data
+            // handed out from it is still in use
+            self.inner.reserve(self.capacity.max(size));
+        }
+    }
+
+    /// Reads an arbitrary amount of Bytes, but at most `size`, from the Reader
+    ///
+    /// # Returns
+    /// The Bytes that were read, which are empty once the Reader reached its
+    /// End
+    pub async fn read<R>(&mut self, reader: &mut R, size: usize) -> std::io::Result<Bytes>
+    where
+        R: AsyncRead + Unpin,
+    {
+        self.reserve(size);
+        let n = reader.read_buf(&mut (&mut self.inner).limit(size)).await?;
+        Ok(self.take(n))
+    }
+
+    /// Reads exactly `size` Bytes from the Reader
+    pub async fn read_exact<R>(&mut self, reader: &mut R, size: usize) -> std::io::Result<Bytes>
+    where
+        R: AsyncRead + Unpin,
+    {
+        self.reserve(size);
+        while self.inner.len() < size {
+            let remaining = size - self.inner.len();
+            if reader
+                .read_buf(&mut (&mut self.inner).limit(remaining))
+                .await?
+                == 0
+            {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+        Ok(self.take(size))
+    }
+
+    /// Prepares the Space for reading the next `size` Bytes into, for Readers
+    /// that can only read into a Slice
+    ///
+    /// # Returns
+    /// The Space, which should then be filled and taken using
+    /// [`take`](Self::take)
+    pub(crate) fn prepare(&mut self, size: usize) -> &mut [u8] {
+        self.reserve(size);
+        self.inner.resize(size, 0);
+        &mut self.inner[..]
+    }
+
+    /// Takes the first `length` Bytes, which were read into the Buffer
+    pub(crate) fn take(&mut self, length: usize) -> Bytes {
+        self.inner.truncate(length);
+        self.inner.split().freeze()
+    }
+
+    /// Reads exactly `size` more Bytes from the Reader, after the ones that
+    /// were already read, so that they can be decoded together
+    pub(crate) async fn read_more<R>(&mut self, reader: &mut R, size: usize) -> std::io::Result<()>
+    where
+        R: AsyncRead + Unpin,
+    {
+        self.grow(size);
+        let target = self.inner.len() + size;
+        while self.inner.len() < target {
+            let remaining = target - self.inner.len();
+            if reader
+                .read_buf(&mut (&mut self.inner).limit(remaining))
+                .await?
+                == 0
+            {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Prepares the Space for reading `size` more Bytes into, after the ones
+    /// that were already read, for Readers that can only read into a Slice
+    pub(crate) fn prepare_more(&mut self, size: usize) -> &mut [u8] {
+        self.grow(size);
+        let start = self.inner.len();
+        self.inner.resize(start + size, 0);
+        &mut self.inner[start..]
+    }
+
+    /// Decodes the next Message from the Bytes read so far, whose Data is
+    /// handed out without copying it
+    pub(crate) fn decode(
+        &mut self,
+        codec: &mut MessageCodec,
+    ) -> Result<Option<Message>, CodecError> {
+        codec.decode(&mut self.inner)
+    }
+}
+
+impl Default for ReadBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_prepared() {
+        let mut buffer = ReadBuffer::with_capacity(16);
+
+        buffer.prepare(8).copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        let first = buffer.take(3);
+        buffer.prepare(4).copy_from_slice(&[9, 10, 11, 12]);
+        let second = buffer.take(4);
+
+        assert_eq!(&[1, 2, 3], &first[..]);
+        assert_eq!(&[9, 10, 11, 12], &second[..]);
+    }
+
+    #[tokio::test]
+    async fn read() {
+        let mut buffer = ReadBuffer::with_capacity(16);
+        let mut reader: &[u8] = &[1, 2, 3, 4, 5];
+
+        assert_eq!(&[1, 2, 3], &buffer.read(&mut reader, 3).await.unwrap()[..]);
+        assert_eq!(&[4, 5], &buffer.read(&mut reader, 3).await.unwrap()[..]);
+        assert_eq!(true, buffer.read(&mut reader, 3).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn read_exact() {
+        let mut buffer = ReadBuffer::with_capacity(4);
+        let mut reader: &[u8] = &[1; 10];
+
+        assert_eq!(
+            vec![1; 8],
+            buffer.read_exact(&mut reader, 8).await.unwrap().to_vec()
+        );
+        assert_eq!(
+            std::io::ErrorKind::UnexpectedEof,
+            buffer.read_exact(&mut reader, 8).await.unwrap_err().kind()
+        );
+    }
+
+    #[tokio::test]
+    async fn reuses_allocation() {
+        let mut buffer = ReadBuffer::with_capacity(16);
+        let mut reader: &[u8] = &[0; 32];
+
+        let first = buffer.read_exact(&mut reader, 8).await.unwrap();
+        let ptr = first.as_ptr();
+        let second = buffer.read_exact(&mut reader, 8).await.unwrap();
+        drop(first);
+        drop(second);
+
+        let third = buffer.read_exact(&mut reader, 8).await.unwrap();
+        assert_eq!(ptr, third.as_ptr());
+    }
+}
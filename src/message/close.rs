@@ -0,0 +1,189 @@
+use crate::message::{Message, MessageHeader, MessageType};
+
+/// The Code describing why a Connection was closed
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CloseCode {
+    /// The Connection was simply finished
+    Normal,
+    /// The Client could not connect to the Backend that should handle the
+    /// Connection
+    ConnectFailed,
+    /// The Connection timed out
+    Timeout,
+    /// The Connection was closed because of some Policy, like a Limit
+    Policy,
+    /// The Connection was closed because of some internal Error
+    Error,
+}
+
+impl CloseCode {
+    /// Deserializes the Code from a single Byte
+    ///
+    /// Returns:
+    /// * None if the Byte was not a valid Code
+    /// * Some with the Code of the byte
+    pub fn deserialize(data: u8) -> Option<Self> {
+        match data {
+            0 => Some(Self::Normal),
+            1 => Some(Self::ConnectFailed),
+            2 => Some(Self::Timeout),
+            3 => Some(Self::Policy),
+            4 => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    /// Serializes the Code into a single Byte
+    pub fn serialize(&self) -> u8 {
+        match *self {
+            Self::Normal => 0,
+            Self::ConnectFailed => 1,
+            Self::Timeout => 2,
+            Self::Policy => 3,
+            Self::Error => 4,
+        }
+    }
+
+    /// Whether or not the Connection was closed because something went
+    /// wrong, instead of simply being finished
+    pub fn is_error(&self) -> bool {
+        *self != Self::Normal
+    }
+}
+
+/// The Reason for closing a Connection, which is send as the Body of a
+/// Close-Message
+#[derive(Debug, PartialEq, Clone)]
+pub struct CloseReason {
+    code: CloseCode,
+    text: Option<String>,
+}
+
+impl CloseReason {
+    /// Creates a new Reason with only a Code
+    pub fn new(code: CloseCode) -> Self {
+        Self { code, text: None }
+    }
+
+    /// Creates a new Reason with a Code and a more detailed Text
+    pub fn with_text<T>(code: CloseCode, text: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            code,
+            text: Some(text.into()),
+        }
+    }
+
+    /// The Code of the Reason
+    pub fn code(&self) -> CloseCode {
+        self.code
+    }
+    /// The more detailed Text of the Reason, if there is one
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+
+    /// Serializes the Reason into the Body of a Close-Message, which is the
+    /// Code followed by the UTF-8 encoded Text
+    pub fn serialize(&self) -> Vec<u8> {
+        let text = self.text.as_deref().unwrap_or("").as_bytes();
+
+        let mut result = Vec::with_capacity(1 + text.len());
+        result.push(self.code.serialize());
+        result.extend_from_slice(text);
+        result
+    }
+
+    /// Deserializes the Reason from the Body of a Close-Message
+    ///
+    /// Returns None if the Body is empty, which is the case for Peers that
+    /// dont support Close-Reasons, or if the Code is unknown
+    pub fn deserialize(raw: &[u8]) -> Option<Self> {
+        let (raw_code, raw_text) = raw.split_first()?;
+        let code = CloseCode::deserialize(*raw_code)?;
+
+        let text = match raw_text.len() {
+            0 => None,
+            _ => Some(String::from_utf8_lossy(raw_text).into_owned()),
+        };
+
+        Some(Self { code, text })
+    }
+
+    /// Creates the Close-Message for the given Connection containing this Reason
+    pub fn into_message(self, id: u32) -> Message {
+        let body = self.serialize();
+        Message::new(
+            MessageHeader::new(id, MessageType::Close, body.len() as u64),
+            body,
+        )
+    }
+}
+
+impl std::fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.text {
+            Some(text) => write!(f, "{:?}: {}", self.code, text),
+            None => write!(f, "{:?}", self.code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_serialize_deserialize() {
+        for code in [
+            CloseCode::Normal,
+            CloseCode::ConnectFailed,
+            CloseCode::Timeout,
+            CloseCode::Policy,
+            CloseCode::Error,
+        ] {
+            assert_eq!(Some(code), CloseCode::deserialize(code.serialize()));
+        }
+    }
+    #[test]
+    fn code_deserialize_invalid() {
+        assert_eq!(None, CloseCode::deserialize(123));
+    }
+
+    #[test]
+    fn reason_serialize() {
+        let reason = CloseReason::with_text(CloseCode::Timeout, "idle");
+        assert_eq!(vec![2, b'i', b'd', b'l', b'e'], reason.serialize());
+    }
+    #[test]
+    fn reason_serialize_deserialize() {
+        let reason = CloseReason::with_text(CloseCode::ConnectFailed, "refused");
+        assert_eq!(
+            Some(reason.clone()),
+            CloseReason::deserialize(&reason.serialize())
+        );
+
+        let reason = CloseReason::new(CloseCode::Normal);
+        assert_eq!(
+            Some(reason.clone()),
+            CloseReason::deserialize(&reason.serialize())
+        );
+    }
+    #[test]
+    fn reason_deserialize_empty() {
+        assert_eq!(None, CloseReason::deserialize(&[]));
+    }
+
+    #[test]
+    fn reason_into_message() {
+        let msg = CloseReason::new(CloseCode::Error).into_message(13);
+
+        assert_eq!(
+            Message::new(MessageHeader::new(13, MessageType::Close, 1), vec![4]),
+            msg
+        );
+        assert_eq!(Some(CloseReason::new(CloseCode::Error)), msg.close_reason());
+    }
+}
@@ -0,0 +1,373 @@
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::message::{HeaderError, HeaderFormat, Message, MessageHeader};
+
+/// The Error returned when a Message could not be encoded or decoded
+#[derive(Debug)]
+pub enum CodecError {
+    /// Reading from or writing to the underlying Connection failed
+    Io(std::io::Error),
+    /// The received Bytes dont contain a valid Header
+    InvalidHeader,
+    /// The Data of the Message is larger than the maximum Size of the Codec
+    TooLarge {
+        /// The Length of the Data of the Message
+        length: u64,
+        /// The maximum Size of the Codec
+        max_size: usize,
+    },
+    /// The Header of the Message covers more Data than the Message has
+    LengthMismatch {
+        /// The Length of the Data according to the Header
+        length: u64,
+        /// The actual Length of the Data of the Message
+        actual: usize,
+    },
+}
+
+impl std::fmt::Display for CodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodecError::Io(e) => write!(f, "IO-Error: {}", e),
+            CodecError::InvalidHeader => write!(f, "The Header is invalid"),
+            CodecError::TooLarge { length, max_size } => write!(
+                f,
+                "The Message has {} Bytes of Data, but at most {} are allowed",
+                length, max_size
+            ),
+            CodecError::LengthMismatch { length, actual } => write!(
+                f,
+                "The Header covers {} Bytes of Data, but the Message only has {}",
+                length, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<std::io::Error> for CodecError {
+    fn from(other: std::io::Error) -> Self {
+        Self::Io(other)
+    }
+}
+
+/// The Codec for the Messages send between the Server and Client
+///
+/// This can be used with `tokio_util::codec::Framed` to send and receive
+/// Messages over any Connection. The Headers use the given
+/// [`HeaderFormat`], which is always [`HeaderFormat::V1`] during the
+/// Handshake and afterwards depends on the Protocol-Version of the other side.
+/// Messages with more Data than the maximum Size are rejected, both when
+/// encoding and decoding them.
+///
+/// # Example
+/// ```rust
+/// # use bytes::BytesMut;
+/// # use tokio_util::codec::{Decoder, Encoder};
+/// # use tunneler_core::message::{HeaderFormat, Message, MessageCodec, MessageHeader, MessageType};
+/// let mut codec = MessageCodec::new(HeaderFormat::V2);
+/// let msg = Message::new(MessageHeader::new(13, MessageType::Data, 3), vec![1, 2, 3]);
+///
+/// let mut buffer = BytesMut::new();
+/// codec.encode(&msg, &mut buffer).unwrap();
+/// assert_eq!(Some(msg), codec.decode(&mut buffer).unwrap());
+/// ```
+#[derive(Debug, Clone)]
+pub struct MessageCodec {
+    format: HeaderFormat,
+    max_size: usize,
+    /// The Header of the Message currently being decoded, while its Data is
+    /// still incomplete
+    header: Option<MessageHeader>,
+    /// The Number of Bytes that are at least still needed to decode the next
+    /// Message
+    missing: usize,
+}
+
+impl MessageCodec {
+    /// The maximum Size of the Data of a single Message, if no other Size
+    /// was configured
+    pub const DEFAULT_MAX_SIZE: usize = 16 * 1024 * 1024;
+
+    /// Creates a new Codec, whose Headers use the given Format
+    pub fn new(format: HeaderFormat) -> Self {
+        Self {
+            format,
+            max_size: Self::DEFAULT_MAX_SIZE,
+            header: None,
+            missing: format.min_size(),
+        }
+    }
+
+    /// Sets the maximum Size of the Data of a single Message
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// The Format of the Headers
+    pub fn format(&self) -> HeaderFormat {
+        self.format
+    }
+
+    /// The maximum Size of the Data of a single Message
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// The Number of Bytes that are at least still needed to decode the next
+    /// Message, after the last call to `decode` returned `None`
+    ///
+    /// Reading only this many Bytes makes sure that none of the Bytes
+    /// following the Message are consumed
+    pub(crate) fn missing(&self) -> usize {
+        self.missing
+    }
+
+    /// The Size of the Message, once it is encoded
+    pub(crate) fn size(&self, header: &MessageHeader) -> usize {
+        self.format.size(header) + header.get_length() as usize
+    }
+
+    /// Makes sure the Length of the Data does not exceed the maximum Size
+    fn check(&self, length: u64) -> Result<(), CodecError> {
+        match length > self.max_size as u64 {
+            true => Err(CodecError::TooLarge {
+                length,
+                max_size: self.max_size,
+            }),
+            false => Ok(()),
+        }
+    }
+
+    /// The Data of the Message that is covered by its Header, which is the
+    /// only Data that is send
+    pub(crate) fn data<'a>(&self, msg: &'a Message) -> Result<&'a [u8], CodecError> {
+        let length = msg.get_header().get_length();
+        let data = msg.get_data();
+        match data.get(..length as usize) {
+            Some(d) => Ok(d),
+            None => Err(CodecError::LengthMismatch {
+                length,
+                actual: data.len(),
+            }),
+        }
+    }
+
+    /// Makes sure the Message can be encoded by this Codec, without
+    /// actually encoding it
+    pub(crate) fn validate(&self, msg: &Message) -> Result<(), CodecError> {
+        self.check(msg.get_header().get_length())?;
+        self.data(msg)?;
+        Ok(())
+    }
+
+    /// Encodes only the Header of the Message and appends it to the Target,
+    /// so that the Data can be written without copying it first
+    pub(crate) fn encode_header(
+        &self,
+        header: &MessageHeader,
+        dst: &mut Vec<u8>,
+    ) -> Result<(), CodecError> {
+        self.check(header.get_length())?;
+        self.format.serialize(header, dst);
+        Ok(())
+    }
+}
+
+impl Decoder for MessageCodec {
+    type Item = Message;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Message>, CodecError> {
+        let header = match self.header.take() {
+            Some(h) => h,
+            None => match self.format.deserialize(src) {
+                Ok((header, size)) => {
+                    self.check(header.get_length())?;
+                    src.advance(size);
+                    header
+                }
+                Err(HeaderError::Incomplete(n)) => {
+                    self.missing = n;
+                    return Ok(None);
+                }
+                Err(HeaderError::Invalid) => return Err(CodecError::InvalidHeader),
+            },
+        };
+
+        let length = header.get_length() as usize;
+        if src.len() < length {
+            self.missing = length - src.len();
+            self.header = Some(header);
+            return Ok(None);
+        }
+
+        // The Data is split off without copying it, so it still shares the
+        // Allocation of the Buffer
+        let data = src.split_to(length).freeze();
+        self.missing = self.format.min_size();
+        Ok(Some(Message::new(header, data)))
+    }
+}
+
+impl Encoder<&Message> for MessageCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, msg: &Message, dst: &mut BytesMut) -> Result<(), CodecError> {
+        let header = msg.get_header();
+        self.check(header.get_length())?;
+        let data = self.data(msg)?;
+
+        dst.reserve(self.size(header));
+        self.format.serialize(header, dst);
+        dst.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+impl Encoder<Message> for MessageCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, msg: Message, dst: &mut BytesMut) -> Result<(), CodecError> {
+        self.encode(&msg, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::MessageType;
+
+    fn data(id: u32, length: usize) -> Message {
+        Message::new(
+            MessageHeader::new(id, MessageType::Data, length as u64),
+            vec![7; length],
+        )
+    }
+
+    #[test]
+    fn roundtrip() {
+        for format in [HeaderFormat::V1, HeaderFormat::V2] {
+            let mut codec = MessageCodec::new(format);
+            let msgs = vec![
+                data(13, 10),
+                data(300, 0),
+                Message::new(MessageHeader::new(0, MessageType::Ping, 8), vec![1; 8]),
+            ];
+
+            let mut buffer = BytesMut::new();
+            for msg in msgs.iter() {
+                codec.encode(msg, &mut buffer).unwrap();
+            }
+
+            for msg in msgs {
+                assert_eq!(Some(msg), codec.decode(&mut buffer).unwrap());
+            }
+            assert_eq!(None, codec.decode(&mut buffer).unwrap());
+            assert_eq!(true, buffer.is_empty());
+        }
+    }
+
+    #[test]
+    fn encode_only_covered_data() {
+        let mut codec = MessageCodec::new(HeaderFormat::V1);
+        let msg = Message::new(MessageHeader::new(13, MessageType::Data, 2), vec![1, 2, 3]);
+
+        let mut buffer = BytesMut::new();
+        codec.encode(msg, &mut buffer).unwrap();
+
+        let mut expected = [0; 13];
+        MessageHeader::new(13, MessageType::Data, 2).serialize(&mut expected);
+        assert_eq!(&expected[..], &buffer[..13]);
+        assert_eq!(&[1, 2], &buffer[13..]);
+    }
+
+    #[test]
+    fn encode_length_mismatch() {
+        let mut codec = MessageCodec::new(HeaderFormat::V1);
+        let msg = Message::new(MessageHeader::new(13, MessageType::Data, 4), vec![1, 2, 3]);
+
+        let mut buffer = BytesMut::new();
+        assert_eq!(
+            true,
+            matches!(
+                codec.encode(msg, &mut buffer),
+                Err(CodecError::LengthMismatch {
+                    length: 4,
+                    actual: 3
+                })
+            )
+        );
+        assert_eq!(true, buffer.is_empty());
+    }
+
+    #[test]
+    fn decode_partial() {
+        let mut codec = MessageCodec::new(HeaderFormat::V2);
+        let msg = data(1 << 20, 200);
+
+        let mut raw = BytesMut::new();
+        codec.encode(&msg, &mut raw).unwrap();
+
+        // Feeding the Bytes one after another never needs more Bytes than
+        // the Message actually has
+        let mut buffer = BytesMut::new();
+        for (index, byte) in raw.iter().enumerate() {
+            assert_eq!(None, codec.decode(&mut buffer).unwrap());
+            assert_eq!(true, index + codec.missing() <= raw.len());
+            buffer.extend_from_slice(&[*byte]);
+        }
+        assert_eq!(Some(msg), codec.decode(&mut buffer).unwrap());
+        assert_eq!(HeaderFormat::V2.min_size(), codec.missing());
+    }
+
+    #[test]
+    fn decode_invalid_header() {
+        let mut codec = MessageCodec::new(HeaderFormat::V2);
+
+        let mut buffer = BytesMut::from(&[0x1f, 0, 0][..]);
+        assert_eq!(
+            true,
+            matches!(codec.decode(&mut buffer), Err(CodecError::InvalidHeader))
+        );
+    }
+
+    #[test]
+    fn max_size() {
+        let mut codec = MessageCodec::new(HeaderFormat::V2).with_max_size(16);
+
+        let mut buffer = BytesMut::new();
+        assert_eq!(
+            true,
+            matches!(
+                codec.encode(data(13, 17), &mut buffer),
+                Err(CodecError::TooLarge {
+                    length: 17,
+                    max_size: 16
+                })
+            )
+        );
+        assert_eq!(true, buffer.is_empty());
+
+        // Oversized Messages are rejected as soon as their Header is
+        // received, without waiting for their Data
+        let mut raw = Vec::new();
+        HeaderFormat::V2.serialize(data(13, 17).get_header(), &mut raw);
+        let mut buffer = BytesMut::from(&raw[..]);
+        assert_eq!(
+            true,
+            matches!(
+                codec.decode(&mut buffer),
+                Err(CodecError::TooLarge { length: 17, .. })
+            )
+        );
+
+        assert_eq!(
+            true,
+            codec.encode(data(13, 16), &mut BytesMut::new()).is_ok()
+        );
+    }
+}
@@ -0,0 +1,414 @@
+use std::convert::TryInto;
+
+use bytes::Bytes;
+
+use crate::message::{Message, MessageHeader, MessageType};
+
+/// Messages with less Data than this are never compressed, as it is unlikely
+/// to save any Space
+const MIN_SIZE: usize = 64;
+
+/// The Number of Bytes in front of the compressed Data, which store the
+/// Length of the original Data
+const LENGTH_SIZE: usize = 4;
+
+/// An Algorithm that can be used to compress the Data of Messages
+///
+/// The Algorithms are only available if the Crate was compiled with their
+/// Feature enabled. Both sides announce the Algorithms they support in the
+/// Handshake and the Server picks the one that is used for the Connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Zstandard, requires the `zstd` Feature
+    #[cfg(feature = "zstd")]
+    Zstd,
+    /// Deflate, requires the `flate2` Feature
+    #[cfg(feature = "flate2")]
+    Deflate,
+}
+
+/// The Error returned when the Data of a compressed Message could not be
+/// decompressed
+#[derive(Debug)]
+pub enum DecompressError {
+    /// The Message was compressed, but no Algorithm was negotiated
+    NotNegotiated,
+    /// The Data is too short to contain the Length of the original Data
+    MissingLength,
+    /// The original Data would be larger than the maximum Size of the Codec,
+    /// which protects against Messages that would decompress to an enormous
+    /// Size
+    TooLarge(usize),
+    /// The compressed Data is invalid
+    Invalid(std::io::Error),
+    /// The decompressed Data does not have the expected Length
+    MismatchedLength {
+        /// The Length stored in front of the compressed Data
+        expected: usize,
+        /// The actual Length of the decompressed Data
+        actual: usize,
+    },
+}
+
+impl std::fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecompressError::NotNegotiated => write!(f, "No Compression was negotiated"),
+            DecompressError::MissingLength => write!(f, "The original Length is missing"),
+            DecompressError::TooLarge(length) => {
+                write!(f, "The original Data would have {} Bytes", length)
+            }
+            DecompressError::Invalid(e) => write!(f, "The compressed Data is invalid: {}", e),
+            DecompressError::MismatchedLength { expected, actual } => write!(
+                f,
+                "Expected {} Bytes of decompressed Data, but got {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl Compression {
+    /// The Bit used for the Algorithm when announcing it in the Handshake
+    pub(crate) fn bit(self) -> u8 {
+        match self {
+            #[cfg(feature = "zstd")]
+            Self::Zstd => 0b01,
+            #[cfg(feature = "flate2")]
+            Self::Deflate => 0b10,
+        }
+    }
+
+    /// Combines the Bits of all the given Algorithms
+    pub(crate) fn mask(algorithms: &[Self]) -> u8 {
+        algorithms.iter().fold(0, |mask, a| mask | a.bit())
+    }
+
+    /// Picks the first of the supported Algorithms, that is also contained in
+    /// the Mask announced by the other side
+    pub(crate) fn negotiate(supported: &[Self], announced: u8) -> Option<Self> {
+        supported.iter().copied().find(|a| announced & a.bit() != 0)
+    }
+
+    /// The Algorithm with the given Bit, if it is available
+    pub(crate) fn from_bit(bit: u8) -> Option<Self> {
+        match bit {
+            #[cfg(feature = "zstd")]
+            0b01 => Some(Self::Zstd),
+            #[cfg(feature = "flate2")]
+            0b10 => Some(Self::Deflate),
+            _ => None,
+        }
+    }
+
+    fn compress_raw(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        // The original Length has to fit into the Prefix, so larger Data is
+        // send without compressing it
+        let length: u32 = data.len().try_into().map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Data is too large to be compressed",
+            )
+        })?;
+
+        let mut result = Vec::with_capacity(LENGTH_SIZE + data.len() / 2);
+        result.extend_from_slice(&length.to_le_bytes());
+
+        match self {
+            #[cfg(feature = "zstd")]
+            Self::Zstd => {
+                result.extend_from_slice(&zstd::bulk::compress(data, 0)?);
+                Ok(result)
+            }
+            #[cfg(feature = "flate2")]
+            Self::Deflate => {
+                use std::io::Write;
+
+                let mut encoder =
+                    flate2::write::DeflateEncoder::new(result, flate2::Compression::fast());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    // Without any Algorithm enabled, this can never be called
+    #[cfg_attr(
+        not(any(feature = "zstd", feature = "flate2")),
+        allow(unused_variables)
+    )]
+    fn decompress_raw(self, data: &[u8], length: usize) -> std::io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "zstd")]
+            Self::Zstd => zstd::bulk::decompress(data, length),
+            #[cfg(feature = "flate2")]
+            Self::Deflate => {
+                use std::io::Read;
+
+                // Reading one more Byte than expected, allows detecting Data
+                // that is longer than announced without reading all of it
+                let mut result = Vec::with_capacity(length);
+                flate2::read::DeflateDecoder::new(data)
+                    .take(length as u64 + 1)
+                    .read_to_end(&mut result)?;
+                Ok(result)
+            }
+        }
+    }
+
+    /// Compresses the Data of the Message, if it is User-Data and compressing
+    /// it actually saves Space
+    ///
+    /// # Params:
+    /// * `msg`: The Message to compress
+    /// * `max_size`: The maximum Size of the Codec, larger Data is never
+    ///   compressed as the other side could not decompress it
+    ///
+    /// Returns:
+    /// The compressed Message or the original one
+    pub(crate) fn compress(self, msg: Message, max_size: usize) -> Message {
+        let header = msg.get_header();
+        let length = header.get_length() as usize;
+        if *header.get_kind() != MessageType::Data
+            || header.is_compressed()
+            || !(MIN_SIZE..=max_size).contains(&length)
+        {
+            return msg;
+        }
+
+        // Messages without enough Data are left as they are, for the Codec to
+        // reject them
+        let data = match msg.get_data().get(..length) {
+            Some(d) => d,
+            None => return msg,
+        };
+        let compressed = match self.compress_raw(data) {
+            Ok(c) if c.len() < length => c,
+            Ok(_) => return msg,
+            Err(e) => {
+                error!("Compressing Message: {}", e);
+                return msg;
+            }
+        };
+
+        let header =
+            MessageHeader::new(header.get_id(), MessageType::Data, compressed.len() as u64)
+                .with_compressed(true);
+        Message::new(header, compressed)
+    }
+
+    /// Decompresses the Data of a compressed Message
+    ///
+    /// # Params:
+    /// * `header`: The Header of the compressed Message
+    /// * `data`: The compressed Data
+    /// * `max_size`: The maximum Size of the Codec, which also limits the
+    ///   Size of the decompressed Data
+    ///
+    /// Returns:
+    /// The Message with the original Data
+    pub(crate) fn decompress(
+        self,
+        header: &MessageHeader,
+        data: &[u8],
+        max_size: usize,
+    ) -> Result<Message, DecompressError> {
+        let length = match data.get(0..LENGTH_SIZE) {
+            Some(raw) => u32::from_le_bytes(raw.try_into().unwrap()) as usize,
+            None => return Err(DecompressError::MissingLength),
+        };
+        if length > max_size {
+            return Err(DecompressError::TooLarge(length));
+        }
+
+        let result = self
+            .decompress_raw(&data[LENGTH_SIZE..], length)
+            .map_err(DecompressError::Invalid)?;
+        if result.len() != length {
+            return Err(DecompressError::MismatchedLength {
+                expected: length,
+                actual: result.len(),
+            });
+        }
+
+        let header = MessageHeader::new(header.get_id(), header.get_kind().clone(), length as u64);
+        Ok(Message::new(header, result))
+    }
+}
+
+/// Decompresses the Message, if it is compressed, using the negotiated
+/// Algorithm
+///
+/// # Params:
+/// * `compression`: The Algorithm negotiated for the Connection, if any
+/// * `header`: The Header of the received Message
+/// * `data`: The Data of the received Message
+/// * `max_size`: The maximum Size of the Codec of the Connection
+pub(crate) fn decompress(
+    compression: Option<Compression>,
+    header: MessageHeader,
+    data: Bytes,
+    max_size: usize,
+) -> Result<Message, DecompressError> {
+    if !header.is_compressed() {
+        return Ok(Message::new(header, data));
+    }
+
+    match compression {
+        Some(c) => c.decompress(&header, &data, max_size),
+        None => Err(DecompressError::NotNegotiated),
+    }
+}
+
+/// The Length of the Data of the Message before it was compressed, which is
+/// the Length stored in front of the Data for compressed Messages
+pub(crate) fn original_length(msg: &Message) -> u64 {
+    let header = msg.get_header();
+    if !header.is_compressed() {
+        return header.get_length();
+    }
+
+    msg.get_data()
+        .get(0..LENGTH_SIZE)
+        .map(|raw| u32::from_le_bytes(raw.try_into().unwrap()) as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::MessageCodec;
+
+    const MAX_SIZE: usize = MessageCodec::DEFAULT_MAX_SIZE;
+
+    #[test]
+    fn not_negotiated() {
+        let header = MessageHeader::new(1, MessageType::Data, 4).with_compressed(true);
+        assert_eq!(
+            true,
+            decompress(None, header, Bytes::from(vec![0; 4]), MAX_SIZE).is_err()
+        );
+    }
+
+    #[test]
+    fn uncompressed_message() {
+        let header = MessageHeader::new(1, MessageType::Data, 4);
+        let msg = decompress(None, header.clone(), Bytes::from(vec![3; 4]), MAX_SIZE).unwrap();
+
+        assert_eq!(4, original_length(&msg));
+        assert_eq!(Message::new(header, vec![3; 4]), msg);
+    }
+
+    #[test]
+    fn negotiate() {
+        assert_eq!(None, Compression::negotiate(&[], 0xff));
+        assert_eq!(0, Compression::mask(&[]));
+        assert_eq!(None, Compression::from_bit(0));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn negotiate_zstd() {
+        assert_eq!(
+            Some(Compression::Zstd),
+            Compression::negotiate(&[Compression::Zstd], 0b11)
+        );
+        assert_eq!(None, Compression::negotiate(&[Compression::Zstd], 0b10));
+        assert_eq!(Some(Compression::Zstd), Compression::from_bit(0b01));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_roundtrip() {
+        roundtrip(Compression::Zstd);
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn deflate_roundtrip() {
+        roundtrip(Compression::Deflate);
+    }
+
+    #[cfg(any(feature = "zstd", feature = "flate2"))]
+    fn roundtrip(compression: Compression) {
+        let data = b"{\"level\":\"info\",\"message\":\"request handled\"}".repeat(20);
+        let msg = Message::new(
+            MessageHeader::new(7, MessageType::Data, data.len() as u64),
+            data.clone(),
+        );
+
+        let compressed = compression.compress(msg.clone(), MAX_SIZE);
+        assert_eq!(true, compressed.get_header().is_compressed());
+        assert_eq!(true, compressed.get_data().len() < data.len());
+        assert_eq!(data.len() as u64, original_length(&compressed));
+
+        let (header, data) = (
+            compressed.get_header().clone(),
+            Bytes::copy_from_slice(compressed.get_data()),
+        );
+        assert_eq!(
+            msg,
+            decompress(Some(compression), header, data, MAX_SIZE).unwrap()
+        );
+    }
+
+    #[cfg(any(feature = "zstd", feature = "flate2"))]
+    #[test]
+    fn skips_small_and_incompressible() {
+        #[cfg(feature = "zstd")]
+        let compression = Compression::Zstd;
+        #[cfg(not(feature = "zstd"))]
+        let compression = Compression::Deflate;
+
+        let small = Message::new(MessageHeader::new(1, MessageType::Data, 8), vec![0; 8]);
+        assert_eq!(small, compression.compress(small.clone(), MAX_SIZE));
+
+        let control = Message::new(MessageHeader::new(1, MessageType::Ping, 128), vec![0; 128]);
+        assert_eq!(control, compression.compress(control.clone(), MAX_SIZE));
+
+        let random: Vec<u8> = (0..256).map(|_| rand::random()).collect();
+        let random = Message::new(MessageHeader::new(1, MessageType::Data, 256), random);
+        assert_eq!(random, compression.compress(random.clone(), MAX_SIZE));
+    }
+
+    #[cfg(any(feature = "zstd", feature = "flate2"))]
+    #[test]
+    fn rejects_wrong_length() {
+        #[cfg(feature = "zstd")]
+        let compression = Compression::Zstd;
+        #[cfg(not(feature = "zstd"))]
+        let compression = Compression::Deflate;
+
+        let data = vec![1; 128];
+        let compressed = compression.compress(
+            Message::new(MessageHeader::new(1, MessageType::Data, 128), data),
+            MAX_SIZE,
+        );
+        let mut raw = compressed.get_data().to_vec();
+        raw[0..4].copy_from_slice(&64_u32.to_le_bytes());
+
+        assert_eq!(
+            true,
+            decompress(
+                Some(compression),
+                compressed.get_header().clone(),
+                Bytes::from(raw),
+                MAX_SIZE
+            )
+            .is_err()
+        );
+        raw = compressed.get_data().to_vec();
+        raw[0..4].copy_from_slice(&(MAX_SIZE as u32 + 1).to_le_bytes());
+        assert_eq!(
+            true,
+            decompress(
+                Some(compression),
+                compressed.get_header().clone(),
+                Bytes::from(raw),
+                MAX_SIZE
+            )
+            .is_err()
+        );
+    }
+}
@@ -1,16 +1,52 @@
-use crate::message::{MessageHeader, MessageType};
+use bytes::Bytes;
+
+use crate::message::{CloseReason, MessageHeader, MessageType};
 
 /// A single Message that is send between the Server and Client
-#[derive(Debug)]
+///
+/// The Data is reference counted, so cloning a Message or passing its Data
+/// on does not copy it
+#[derive(Debug, Clone)]
 pub struct Message {
     header: MessageHeader,
-    data: Vec<u8>, // X bytes
+    data: Bytes, // X bytes
 }
 
 impl Message {
     /// Creates a new Message with the given "raw" Data
-    pub fn new(header: MessageHeader, data: Vec<u8>) -> Self {
-        Self { header, data }
+    ///
+    /// Both a `Vec<u8>` and `Bytes` can be used as the Data without copying it
+    pub fn new<D>(header: MessageHeader, data: D) -> Self
+    where
+        D: Into<Bytes>,
+    {
+        Self {
+            header,
+            data: data.into(),
+        }
+    }
+
+    /// Splits the Data into as many Data-Messages for the Connection as
+    /// needed, so that none of them has more than the maximum Size of Data,
+    /// without copying it
+    ///
+    /// Empty Data still results in a single empty Message
+    pub(crate) fn split_data(
+        id: u32,
+        mut data: Bytes,
+        max_size: usize,
+    ) -> impl Iterator<Item = Message> {
+        let mut done = false;
+        std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            let chunk = data.split_to(data.len().min(max_size));
+            done = data.is_empty();
+
+            let header = MessageHeader::new(id, MessageType::Data, chunk.len() as u64);
+            Some(Message::new(header, chunk))
+        })
     }
 
     /// Serializes the Message into a Vector of Bytes that can
@@ -31,6 +67,10 @@ impl Message {
     pub fn get_data(&self) -> &[u8] {
         &self.data
     }
+    /// Takes the underlying Data out of the Message, without copying it
+    pub fn into_data(self) -> Bytes {
+        self.data
+    }
 
     /// Checks if the messsage is marked as an EOF(End-Of-File)
     ///
@@ -39,6 +79,22 @@ impl Message {
     pub fn is_eof(&self) -> bool {
         self.header.kind == MessageType::EOF
     }
+
+    /// Checks if the message is marked as a Close, which is the last Message
+    /// received for a Connection that was closed by the other side
+    pub fn is_close(&self) -> bool {
+        self.header.kind == MessageType::Close
+    }
+
+    /// The Reason contained in a Close-Message
+    ///
+    /// Returns None if this is not a Close-Message or if it contains no Reason
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        if !self.is_close() {
+            return None;
+        }
+        CloseReason::deserialize(&self.data)
+    }
 }
 
 impl PartialEq for Message {
@@ -51,19 +107,41 @@ impl PartialEq for Message {
 mod tests {
     use super::*;
 
+    #[test]
+    fn split_data() {
+        let data = Bytes::from((0..10).collect::<Vec<u8>>());
+        let msgs: Vec<_> = Message::split_data(13, data, 4).collect();
+        assert_eq!(
+            vec![
+                Message::new(
+                    MessageHeader::new(13, MessageType::Data, 4),
+                    vec![0, 1, 2, 3]
+                ),
+                Message::new(
+                    MessageHeader::new(13, MessageType::Data, 4),
+                    vec![4, 5, 6, 7]
+                ),
+                Message::new(MessageHeader::new(13, MessageType::Data, 2), vec![8, 9]),
+            ],
+            msgs
+        );
+
+        let msgs: Vec<_> = Message::split_data(13, Bytes::new(), 4).collect();
+        assert_eq!(
+            vec![Message::new(
+                MessageHeader::new(13, MessageType::Data, 0),
+                vec![]
+            )],
+            msgs
+        );
+    }
+
     #[test]
     fn message_serialize_connect() {
         let mut inner_data = vec![0; 2];
         inner_data[0] = 1;
         inner_data[1] = 1;
-        let msg = Message::new(
-            MessageHeader {
-                id: 13,
-                kind: MessageType::Connect,
-                length: 2,
-            },
-            inner_data,
-        );
+        let msg = Message::new(MessageHeader::new(13, MessageType::Connect, 2), inner_data);
 
         let mut h_output = [0; 13];
         let d_output = msg.serialize(&mut h_output);
@@ -83,14 +161,7 @@ mod tests {
     fn message_serialize_data() {
         let mut inner_data = vec![0; 12];
         inner_data[2] = 33;
-        let msg = Message::new(
-            MessageHeader {
-                id: 13,
-                kind: MessageType::Data,
-                length: 12,
-            },
-            inner_data,
-        );
+        let msg = Message::new(MessageHeader::new(13, MessageType::Data, 12), inner_data);
         let mut h_output = [0; 13];
         let d_output = msg.serialize(&mut h_output);
 
@@ -109,14 +180,7 @@ mod tests {
     fn message_serialize_length_less_than_vec_size() {
         let mut inner_data = vec![0; 20];
         inner_data[2] = 33;
-        let msg = Message::new(
-            MessageHeader {
-                id: 13,
-                kind: MessageType::Data,
-                length: 12,
-            },
-            inner_data,
-        );
+        let msg = Message::new(MessageHeader::new(13, MessageType::Data, 12), inner_data);
         let mut h_output = [0; 13];
         let d_output = msg.serialize(&mut h_output);
 
@@ -131,6 +195,16 @@ mod tests {
         assert_eq!(&data_expect, d_output);
     }
 
+    #[test]
+    fn message_from_bytes() {
+        let data = Bytes::from(vec![1, 2, 3]);
+        let msg = Message::new(MessageHeader::new(13, MessageType::Data, 3), data.clone());
+
+        assert_eq!(&[1, 2, 3], msg.get_data());
+        // The Data is shared instead of copied
+        assert_eq!(data.as_ptr(), msg.clone().into_data().as_ptr());
+    }
+
     #[test]
     fn message_is_eof() {
         let header = MessageHeader::new(0, MessageType::EOF, 0);
@@ -0,0 +1,322 @@
+use std::convert::TryFrom;
+
+use bytes::BufMut;
+
+use crate::message::{header::COMPRESSED_FLAG, MessageHeader, MessageType};
+
+/// The Bits of the Type-Byte that are reserved for future Flags in the
+/// compact Format and therefore have to be unset
+const RESERVED_FLAGS: u8 = 0x60;
+
+/// The maximum Number of Bytes of the Varints for the ID and Length
+const MAX_ID_SIZE: usize = 5;
+const MAX_LENGTH_SIZE: usize = 10;
+
+/// The Format in which the Headers of Messages are send over a Connection
+///
+/// The Handshake always uses [`HeaderFormat::V1`], afterwards both sides
+/// switch to [`HeaderFormat::V2`] if the other side supports Protocol-Version
+/// 8 or newer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderFormat {
+    /// The original Format, where every Header takes 13 Bytes: The ID as a
+    /// `u32`, the Type and the Length as a `u64`, both in little-endian
+    V1,
+    /// The compact Format, which starts with the Type, whose highest Bits are
+    /// used as Flags, followed by the ID and the Length as Varints (LEB128).
+    /// Most Headers therefore only take 3 to 6 Bytes
+    V2,
+}
+
+/// The Error returned when a Header could not be deserialized
+#[derive(Debug, PartialEq, Eq)]
+pub enum HeaderError {
+    /// More Bytes are needed to deserialize the Header, at least the given
+    /// Number of them
+    Incomplete(usize),
+    /// The Bytes dont contain a valid Header
+    Invalid,
+}
+
+impl HeaderFormat {
+    /// The largest Size a serialized Header can have in any Format
+    pub const MAX_SIZE: usize = 1 + MAX_ID_SIZE + MAX_LENGTH_SIZE;
+
+    /// The Format used once the Handshake with the other side, which reported
+    /// the given Protocol-Version, is done
+    pub fn for_version(protocol_version: u16) -> Self {
+        match protocol_version >= 8 {
+            true => Self::V2,
+            false => Self::V1,
+        }
+    }
+
+    /// The smallest Size a serialized Header can have in this Format
+    pub fn min_size(self) -> usize {
+        match self {
+            Self::V1 => MessageHeader::SIZE,
+            Self::V2 => 3,
+        }
+    }
+
+    /// The Size of the given Header, once it is serialized in this Format
+    pub fn size(self, header: &MessageHeader) -> usize {
+        match self {
+            Self::V1 => MessageHeader::SIZE,
+            Self::V2 => 1 + varint_size(u64::from(header.id)) + varint_size(header.length),
+        }
+    }
+
+    /// Serializes the Header in this Format and appends it to the Target
+    pub fn serialize<B>(self, header: &MessageHeader, target: &mut B)
+    where
+        B: BufMut,
+    {
+        match self {
+            Self::V1 => {
+                let mut raw = [0; MessageHeader::SIZE];
+                header.serialize(&mut raw);
+                target.put_slice(&raw);
+            }
+            Self::V2 => {
+                target.put_u8(match header.is_compressed() {
+                    true => header.kind.serialize() | COMPRESSED_FLAG,
+                    false => header.kind.serialize(),
+                });
+                put_varint(u64::from(header.id), target);
+                put_varint(header.length, target);
+            }
+        }
+    }
+
+    /// Deserializes a Header in this Format from the start of the given Bytes
+    ///
+    /// # Returns:
+    /// The Header and the Number of Bytes it took up
+    pub fn deserialize(self, raw: &[u8]) -> Result<(MessageHeader, usize), HeaderError> {
+        match self {
+            Self::V1 => {
+                let raw = match raw.get(0..MessageHeader::SIZE) {
+                    Some(r) => r,
+                    None => return Err(HeaderError::Incomplete(MessageHeader::SIZE - raw.len())),
+                };
+                let mut tmp = [0; MessageHeader::SIZE];
+                tmp.copy_from_slice(raw);
+                match MessageHeader::deserialize(&tmp) {
+                    Some(h) => Ok((h, MessageHeader::SIZE)),
+                    None => Err(HeaderError::Invalid),
+                }
+            }
+            Self::V2 => {
+                let kind_part = match raw.first() {
+                    Some(k) => *k,
+                    None => return Err(HeaderError::Incomplete(self.min_size())),
+                };
+                if kind_part & RESERVED_FLAGS != 0 {
+                    return Err(HeaderError::Invalid);
+                }
+                let kind =
+                    MessageType::deserialize(kind_part & !(COMPRESSED_FLAG | RESERVED_FLAGS))
+                        .ok_or(HeaderError::Invalid)?;
+
+                // The Length still needs at least one Byte, while the ID is
+                // incomplete
+                let (id, id_size) = get_varint(&raw[1..], MAX_ID_SIZE).map_err(|e| match e {
+                    HeaderError::Incomplete(n) => HeaderError::Incomplete(n + 1),
+                    e => e,
+                })?;
+                let id = u32::try_from(id).map_err(|_| HeaderError::Invalid)?;
+                let (length, length_size) = get_varint(&raw[1 + id_size..], MAX_LENGTH_SIZE)?;
+
+                let header = MessageHeader::new(id, kind, length)
+                    .with_compressed(kind_part & COMPRESSED_FLAG != 0);
+                Ok((header, 1 + id_size + length_size))
+            }
+        }
+    }
+}
+
+/// The Number of Bytes the Value takes up as a Varint
+fn varint_size(value: u64) -> usize {
+    let bits = 64 - value.leading_zeros() as usize;
+    std::cmp::max(1, bits.div_ceil(7))
+}
+
+/// Appends the Value as a Varint, 7 Bits per Byte starting with the lowest
+/// ones, where the highest Bit marks that another Byte follows
+fn put_varint<B>(mut value: u64, target: &mut B)
+where
+    B: BufMut,
+{
+    while value >= 0x80 {
+        target.put_u8((value as u8) | 0x80);
+        value >>= 7;
+    }
+    target.put_u8(value as u8);
+}
+
+/// Reads a Varint, that takes at most `max_size` Bytes, from the start of the
+/// given Bytes
+///
+/// # Returns:
+/// The Value and the Number of Bytes it took up
+fn get_varint(raw: &[u8], max_size: usize) -> Result<(u64, usize), HeaderError> {
+    let mut value: u64 = 0;
+    for (index, byte) in raw.iter().take(max_size).enumerate() {
+        let part = u64::from(byte & 0x7f);
+        // The last Byte of a u64 can only contain a single Bit
+        if index == MAX_LENGTH_SIZE - 1 && part > 1 {
+            return Err(HeaderError::Invalid);
+        }
+        value |= part << (7 * index);
+
+        if byte & 0x80 == 0 {
+            return Ok((value, index + 1));
+        }
+    }
+
+    match raw.len() >= max_size {
+        true => Err(HeaderError::Invalid),
+        false => Err(HeaderError::Incomplete(1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(format: HeaderFormat, header: MessageHeader) {
+        let mut raw = Vec::new();
+        format.serialize(&header, &mut raw);
+
+        assert_eq!(format.size(&header), raw.len());
+        assert_eq!(Ok((header, raw.len())), format.deserialize(&raw));
+    }
+
+    #[test]
+    fn roundtrip_v1() {
+        roundtrip(
+            HeaderFormat::V1,
+            MessageHeader::new(13, MessageType::Data, 20),
+        );
+        roundtrip(
+            HeaderFormat::V1,
+            MessageHeader::new(u32::MAX, MessageType::Data, u64::MAX).with_compressed(true),
+        );
+    }
+
+    #[test]
+    fn roundtrip_v2() {
+        roundtrip(
+            HeaderFormat::V2,
+            MessageHeader::new(0, MessageType::Ping, 0),
+        );
+        roundtrip(
+            HeaderFormat::V2,
+            MessageHeader::new(13, MessageType::Data, 4096),
+        );
+        roundtrip(
+            HeaderFormat::V2,
+            MessageHeader::new(u32::MAX, MessageType::Resume, u64::MAX),
+        );
+        roundtrip(
+            HeaderFormat::V2,
+            MessageHeader::new(300, MessageType::Data, 128).with_compressed(true),
+        );
+    }
+
+    #[test]
+    fn v2_layout() {
+        let mut raw = Vec::new();
+        HeaderFormat::V2.serialize(
+            &MessageHeader::new(300, MessageType::Data, 5).with_compressed(true),
+            &mut raw,
+        );
+
+        assert_eq!(vec![0x83, 0xac, 0x02, 5], raw);
+    }
+
+    #[test]
+    fn v1_compatible() {
+        // The V1-Format is the same as the one of the Header itself
+        let header = MessageHeader::new(13, MessageType::Connect, 20);
+        let mut expected = [0; 13];
+        header.serialize(&mut expected);
+
+        let mut raw = Vec::new();
+        HeaderFormat::V1.serialize(&header, &mut raw);
+        assert_eq!(expected.to_vec(), raw);
+
+        raw.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(Ok((header, 13)), HeaderFormat::V1.deserialize(&raw));
+        assert_eq!(
+            Err(HeaderError::Incomplete(3)),
+            HeaderFormat::V1.deserialize(&expected[..10])
+        );
+    }
+
+    #[test]
+    fn v2_incomplete() {
+        let mut raw = Vec::new();
+        HeaderFormat::V2.serialize(
+            &MessageHeader::new(1 << 20, MessageType::Data, 1 << 20),
+            &mut raw,
+        );
+
+        // The Number of missing Bytes must never exceed the actual Header
+        for end in 0..raw.len() {
+            match HeaderFormat::V2.deserialize(&raw[..end]) {
+                Err(HeaderError::Incomplete(n)) => assert_eq!(true, end + n <= raw.len()),
+                other => panic!("Expected an incomplete Header, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn v2_invalid() {
+        // Unknown Type
+        assert_eq!(
+            Err(HeaderError::Invalid),
+            HeaderFormat::V2.deserialize(&[0x1f, 0, 0])
+        );
+        // Reserved Flag
+        assert_eq!(
+            Err(HeaderError::Invalid),
+            HeaderFormat::V2.deserialize(&[0x43, 0, 0])
+        );
+        // ID larger than u32
+        assert_eq!(
+            Err(HeaderError::Invalid),
+            HeaderFormat::V2.deserialize(&[3, 0xff, 0xff, 0xff, 0xff, 0x1f, 0])
+        );
+        // ID longer than 5 Bytes
+        assert_eq!(
+            Err(HeaderError::Invalid),
+            HeaderFormat::V2.deserialize(&[3, 0x80, 0x80, 0x80, 0x80, 0x80, 0])
+        );
+        // Length larger than u64
+        let mut raw = vec![3, 0];
+        raw.extend_from_slice(&[0xff; 9]);
+        raw.push(0x02);
+        assert_eq!(
+            Err(HeaderError::Invalid),
+            HeaderFormat::V2.deserialize(&raw)
+        );
+    }
+
+    #[test]
+    fn format_for_version() {
+        assert_eq!(HeaderFormat::V1, HeaderFormat::for_version(0));
+        assert_eq!(HeaderFormat::V1, HeaderFormat::for_version(7));
+        assert_eq!(HeaderFormat::V2, HeaderFormat::for_version(8));
+    }
+
+    #[test]
+    fn varint_sizes() {
+        assert_eq!(1, varint_size(0));
+        assert_eq!(1, varint_size(127));
+        assert_eq!(2, varint_size(128));
+        assert_eq!(5, varint_size(u64::from(u32::MAX)));
+        assert_eq!(10, varint_size(u64::MAX));
+    }
+}
@@ -11,12 +11,32 @@ pub struct MessageHeader {
     pub kind: MessageType, // 1 byte
     /// The Length of the Data assosicated with this Message
     pub length: u64, // 8 bytes
+    /// Whether or not the Data of the Message is compressed, which is stored
+    /// in the highest Bit of the Type
+    compressed: bool,
 }
 
+/// The Bit of the Type-Byte that marks compressed Data
+pub(crate) const COMPRESSED_FLAG: u8 = 0x80;
+
 impl MessageHeader {
+    /// The Size of a serialized Header in Bytes
+    pub const SIZE: usize = 13;
+
     /// Creates a new Header with the given Metadata
     pub fn new(id: u32, kind: MessageType, length: u64) -> MessageHeader {
-        MessageHeader { id, kind, length }
+        MessageHeader {
+            id,
+            kind,
+            length,
+            compressed: false,
+        }
+    }
+
+    /// Marks the Data of the Message as compressed
+    pub fn with_compressed(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
     }
 
     /// Deserializes a 13-Byte array into the fitting Message-Header
@@ -29,10 +49,15 @@ impl MessageHeader {
         let length_part = &raw_data[5..13];
 
         let id = u32::from_le_bytes(id_part.try_into().unwrap());
-        let kind = MessageType::deserialize(kind_part)?;
+        let kind = MessageType::deserialize(kind_part & !COMPRESSED_FLAG)?;
         let length = u64::from_le_bytes(length_part.try_into().unwrap());
 
-        Some(MessageHeader { id, kind, length })
+        Some(MessageHeader {
+            id,
+            kind,
+            length,
+            compressed: kind_part & COMPRESSED_FLAG != 0,
+        })
     }
 
     /// Serializes the Header itself into a 13-Byte array
@@ -45,7 +70,10 @@ impl MessageHeader {
         target[2] = id[2];
         target[3] = id[3];
 
-        target[4] = self.kind.serialize();
+        target[4] = match self.compressed {
+            true => self.kind.serialize() | COMPRESSED_FLAG,
+            false => self.kind.serialize(),
+        };
 
         target[5] = length[0];
         target[6] = length[1];
@@ -69,6 +97,10 @@ impl MessageHeader {
     pub fn get_length(&self) -> u64 {
         self.length
     }
+    /// Returns whether or not the data of this message is compressed
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
 }
 
 #[cfg(test)]
@@ -83,6 +115,7 @@ mod tests {
             id: 13,
             kind: MessageType::Connect,
             length: 20,
+            compressed: false,
         }
         .serialize(&mut output);
 
@@ -100,6 +133,7 @@ mod tests {
                 id: 13,
                 kind: MessageType::Connect,
                 length: 20,
+                compressed: false,
             }),
             MessageHeader::deserialize(&input)
         );
@@ -115,4 +149,14 @@ mod tests {
         assert_eq!(true, deserialized.is_some());
         assert_eq!(first, deserialized.unwrap());
     }
+
+    #[test]
+    fn serialize_deserialize_compressed() {
+        let first = MessageHeader::new(123, MessageType::Data, 123).with_compressed(true);
+        let mut serialized = [0; 13];
+        first.serialize(&mut serialized);
+
+        assert_eq!(0x83, serialized[4]);
+        assert_eq!(Some(first), MessageHeader::deserialize(&serialized));
+    }
 }
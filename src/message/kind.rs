@@ -34,6 +34,26 @@ pub enum MessageType {
     /// the client along with other Data to inform the Server about the
     /// desired Config to use
     Config,
+    /// Send by either side to check if the other side is still
+    /// there, the Body contains a Timestamp that should simply be
+    /// echoed back in the Pong
+    Ping,
+    /// The Response to a Ping, which contains the same Body as the
+    /// Ping it is responding to and is used to measure the
+    /// Round-Trip-Time of the Connection
+    Pong,
+    /// Send by the Client in response to a Connect, to signal that it will
+    /// handle the new Connection
+    Accept,
+    /// Send by the Client in response to a Connect, to signal that it will
+    /// not handle the new Connection, the Body contains the Reason for it
+    Reject,
+    /// Acknowledges the Messages received for a single Connection, the Body
+    /// contains the total Number of Messages received for it so far
+    Ack,
+    /// Send by either side after resuming a Session, once it sent all of its
+    /// Acks for the Messages it received before the Connection was lost
+    Resume,
 }
 
 impl MessageType {
@@ -54,6 +74,12 @@ impl MessageType {
             8 => Some(MessageType::Acknowledge),
             9 => Some(MessageType::EOF),
             10 => Some(MessageType::Config),
+            11 => Some(MessageType::Ping),
+            12 => Some(MessageType::Pong),
+            13 => Some(MessageType::Accept),
+            14 => Some(MessageType::Reject),
+            15 => Some(MessageType::Ack),
+            16 => Some(MessageType::Resume),
             _ => None,
         }
     }
@@ -72,6 +98,12 @@ impl MessageType {
             MessageType::Acknowledge => 8,
             MessageType::EOF => 9,
             MessageType::Config => 10,
+            MessageType::Ping => 11,
+            MessageType::Pong => 12,
+            MessageType::Accept => 13,
+            MessageType::Reject => 14,
+            MessageType::Ack => 15,
+            MessageType::Resume => 16,
         }
     }
 }
@@ -117,6 +149,30 @@ mod tests {
         assert_eq!(Some(MessageType::EOF), MessageType::deserialize(9));
     }
     #[test]
+    fn message_type_deserialize_ping() {
+        assert_eq!(Some(MessageType::Ping), MessageType::deserialize(11));
+    }
+    #[test]
+    fn message_type_deserialize_pong() {
+        assert_eq!(Some(MessageType::Pong), MessageType::deserialize(12));
+    }
+    #[test]
+    fn message_type_deserialize_accept() {
+        assert_eq!(Some(MessageType::Accept), MessageType::deserialize(13));
+    }
+    #[test]
+    fn message_type_deserialize_reject() {
+        assert_eq!(Some(MessageType::Reject), MessageType::deserialize(14));
+    }
+    #[test]
+    fn message_type_deserialize_ack() {
+        assert_eq!(Some(MessageType::Ack), MessageType::deserialize(15));
+    }
+    #[test]
+    fn message_type_deserialize_resume() {
+        assert_eq!(Some(MessageType::Resume), MessageType::deserialize(16));
+    }
+    #[test]
     fn message_type_deserialize_invalid() {
         assert_eq!(None, MessageType::deserialize(123));
     }
@@ -157,4 +213,28 @@ mod tests {
     fn message_type_serialize_eof() {
         assert_eq!(9, MessageType::EOF.serialize());
     }
+    #[test]
+    fn message_type_serialize_ping() {
+        assert_eq!(11, MessageType::Ping.serialize());
+    }
+    #[test]
+    fn message_type_serialize_pong() {
+        assert_eq!(12, MessageType::Pong.serialize());
+    }
+    #[test]
+    fn message_type_serialize_accept() {
+        assert_eq!(13, MessageType::Accept.serialize());
+    }
+    #[test]
+    fn message_type_serialize_reject() {
+        assert_eq!(14, MessageType::Reject.serialize());
+    }
+    #[test]
+    fn message_type_serialize_ack() {
+        assert_eq!(15, MessageType::Ack.serialize());
+    }
+    #[test]
+    fn message_type_serialize_resume() {
+        assert_eq!(16, MessageType::Resume.serialize());
+    }
 }
@@ -1,8 +1,24 @@
 mod header;
 pub use header::MessageHeader;
 
+mod format;
+pub use format::{HeaderError, HeaderFormat};
+
+mod codec;
+pub use codec::{CodecError, MessageCodec};
+
 mod kind;
 pub use kind::MessageType;
 
 mod entire;
 pub use entire::Message;
+
+mod close;
+pub use close::{CloseCode, CloseReason};
+
+mod buffer;
+pub use buffer::ReadBuffer;
+
+mod compression;
+pub use compression::Compression;
+pub(crate) use compression::{decompress, original_length};
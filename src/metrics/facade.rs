@@ -0,0 +1,263 @@
+//! A Metrics-Collector that forwards all the Metrics to the
+//! [`metrics`](::metrics) Crate, which then passes them on to whatever
+//! Recorder is installed by the Application
+
+use std::time::Duration;
+
+use ::metrics::{counter, gauge, histogram};
+
+use crate::message::CloseReason;
+use crate::metrics::{ConnectionLabels, ConnectionStats, LimitKind, Metrics};
+
+/// Forwards all the Metrics to the Macros of the [`metrics`](::metrics) Crate
+///
+/// This does not keep any State on its own, all the Metrics are handled by
+/// the globally installed Recorder
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Facade;
+
+impl Facade {
+    /// Creates a new Facade-Collector
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Metrics for Facade {
+    fn received_msg(&self) {
+        counter!("tunneler_messages_received_total").increment(1);
+    }
+    fn recv_bytes(&self, recv: u64) {
+        counter!("tunneler_received_bytes_total").increment(recv);
+    }
+    fn recv_wire_bytes(&self, recv: u64) {
+        counter!("tunneler_received_wire_bytes_total").increment(recv);
+    }
+
+    fn send_msg(&self) {
+        counter!("tunneler_messages_sent_total").increment(1);
+    }
+    fn send_bytes(&self, send: u64) {
+        counter!("tunneler_sent_bytes_total").increment(send);
+    }
+    fn send_wire_bytes(&self, send: u64) {
+        counter!("tunneler_sent_wire_bytes_total").increment(send);
+    }
+
+    fn rtt(&self, rtt: Duration) {
+        histogram!("tunneler_rtt_seconds").record(rtt.as_secs_f64());
+    }
+
+    fn received_close(&self, reason: &CloseReason) {
+        counter!("tunneler_closes_received_total", "code" => format!("{:?}", reason.code()))
+            .increment(1);
+    }
+
+    fn client_connected(&self, port: u16) {
+        gauge!("tunneler_clients", "port" => port.to_string()).increment(1.0);
+        counter!("tunneler_client_connects_total", "port" => port.to_string()).increment(1);
+    }
+    fn client_disconnected(&self, port: u16) {
+        gauge!("tunneler_clients", "port" => port.to_string()).decrement(1.0);
+        counter!("tunneler_client_disconnects_total", "port" => port.to_string()).increment(1);
+    }
+
+    fn handshake_succeeded(&self) {
+        counter!("tunneler_handshakes_total", "result" => "success").increment(1);
+    }
+    fn handshake_failed(&self) {
+        counter!("tunneler_handshakes_total", "result" => "failure").increment(1);
+    }
+
+    fn user_accepted(&self, port: u16) {
+        counter!("tunneler_users_total", "port" => port.to_string(), "result" => "accepted")
+            .increment(1);
+    }
+    fn user_rejected(&self, port: u16) {
+        counter!("tunneler_users_total", "port" => port.to_string(), "result" => "rejected")
+            .increment(1);
+    }
+    fn user_limited(&self, port: u16, limit: LimitKind) {
+        counter!(
+            "tunneler_users_total",
+            "port" => port.to_string(),
+            "result" => format!("limited_{}", limit.as_str())
+        )
+        .increment(1);
+    }
+    fn user_timeout(&self, port: u16) {
+        counter!("tunneler_users_total", "port" => port.to_string(), "result" => "timeout")
+            .increment(1);
+    }
+
+    fn user_recv_bytes(&self, port: u16, recv: u64) {
+        counter!("tunneler_user_bytes_total", "port" => port.to_string(), "direction" => "recv")
+            .increment(recv);
+    }
+    fn user_send_bytes(&self, port: u16, send: u64) {
+        counter!("tunneler_user_bytes_total", "port" => port.to_string(), "direction" => "send")
+            .increment(send);
+    }
+
+    fn connection_opened(&self, labels: &ConnectionLabels) {
+        gauge!("tunneler_open_connections", "port" => labels.port.to_string()).increment(1.0);
+    }
+    fn connection_closed(&self, labels: &ConnectionLabels, stats: &ConnectionStats) {
+        let port = labels.port.to_string();
+        gauge!("tunneler_open_connections", "port" => port.clone()).decrement(1.0);
+        histogram!("tunneler_connection_duration_seconds", "port" => port.clone())
+            .record(stats.duration.as_secs_f64());
+        histogram!("tunneler_connection_bytes", "port" => port.clone(), "direction" => "recv")
+            .record(stats.recv_bytes as f64);
+        histogram!("tunneler_connection_bytes", "port" => port, "direction" => "send")
+            .record(stats.send_bytes as f64);
+    }
+
+    fn reconnect_attempt(&self, port: u16, _attempt: u32) {
+        counter!("tunneler_reconnect_attempts_total", "port" => port.to_string()).increment(1);
+    }
+
+    fn queue_depth(&self, port: u16, depth: usize) {
+        gauge!("tunneler_wait_queue_depth", "port" => port.to_string()).set(depth as f64);
+    }
+
+    fn handshake_latency(&self, port: u16, latency: Duration) {
+        histogram!("tunneler_handshake_duration_seconds", "port" => port.to_string())
+            .record(latency.as_secs_f64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    /// A single recorded Metric as (Name, Labels, Value)
+    type Recorded = (String, Vec<(String, String)>, DebugValue);
+
+    /// Runs the given Function with a local Recorder and returns all the
+    /// recorded Metrics
+    fn record<F>(func: F) -> Vec<Recorded>
+    where
+        F: FnOnce(),
+    {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        ::metrics::with_local_recorder(&recorder, func);
+
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels = key
+                    .labels()
+                    .map(|l| (l.key().to_owned(), l.value().to_owned()))
+                    .collect();
+                (key.name().to_owned(), labels, value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counters() {
+        let recorded = record(|| {
+            let metrics = Facade::new();
+            metrics.received_msg();
+            metrics.recv_bytes(10);
+            metrics.recv_bytes(5);
+        });
+
+        assert_eq!(2, recorded.len());
+        assert_eq!(
+            true,
+            recorded.contains(&(
+                "tunneler_messages_received_total".to_owned(),
+                vec![],
+                DebugValue::Counter(1)
+            ))
+        );
+        assert_eq!(
+            true,
+            recorded.contains(&(
+                "tunneler_received_bytes_total".to_owned(),
+                vec![],
+                DebugValue::Counter(15)
+            ))
+        );
+    }
+
+    #[test]
+    fn labeled_counters() {
+        let recorded = record(|| {
+            let metrics = Facade::new();
+            metrics.user_accepted(8080);
+            metrics.user_accepted(8080);
+            metrics.user_rejected(8081);
+        });
+
+        assert_eq!(
+            true,
+            recorded.contains(&(
+                "tunneler_users_total".to_owned(),
+                vec![
+                    ("port".to_owned(), "8080".to_owned()),
+                    ("result".to_owned(), "accepted".to_owned())
+                ],
+                DebugValue::Counter(2)
+            ))
+        );
+        assert_eq!(
+            true,
+            recorded.contains(&(
+                "tunneler_users_total".to_owned(),
+                vec![
+                    ("port".to_owned(), "8081".to_owned()),
+                    ("result".to_owned(), "rejected".to_owned())
+                ],
+                DebugValue::Counter(1)
+            ))
+        );
+    }
+
+    #[test]
+    fn connection_lifecycle() {
+        let labels = ConnectionLabels {
+            client_id: 1,
+            port: 8080,
+            connection_id: 2,
+        };
+        let recorded = record(|| {
+            let metrics = Facade::new();
+            metrics.connection_opened(&labels);
+            metrics.connection_closed(
+                &labels,
+                &ConnectionStats {
+                    duration: Duration::from_secs(2),
+                    recv_bytes: 100,
+                    send_bytes: 200,
+                },
+            );
+        });
+
+        let port = vec![("port".to_owned(), "8080".to_owned())];
+        assert_eq!(
+            true,
+            recorded.contains(&(
+                "tunneler_open_connections".to_owned(),
+                port.clone(),
+                DebugValue::Gauge(0.0.into())
+            ))
+        );
+        assert_eq!(
+            true,
+            recorded.contains(&(
+                "tunneler_connection_duration_seconds".to_owned(),
+                port,
+                DebugValue::Histogram(vec![2.0.into()])
+            ))
+        );
+    }
+}
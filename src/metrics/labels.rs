@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+/// Identifies a single User-Connection when reporting Metrics
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionLabels {
+    /// The ID of the Client that handles the Connection
+    pub client_id: u32,
+    /// The external Port on which the User connected
+    pub port: u16,
+    /// The ID of the User-Connection itself
+    pub connection_id: u32,
+}
+
+/// The Statistics of a single User-Connection, either while it is still open
+/// or once it was closed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionStats {
+    /// How long the Connection has been open
+    pub duration: Duration,
+    /// The Number of Bytes received from the User
+    pub recv_bytes: u64,
+    /// The Number of Bytes send to the User
+    pub send_bytes: u64,
+}
+
+/// The Limit that caused a User-Connection to be rejected
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitKind {
+    /// The maximum Number of Connections on the external Port was reached
+    Port,
+    /// All the Clients for the external Port reached their maximum Number of
+    /// Connections
+    Client,
+    /// The maximum Number of Connections from the IP of the User was reached
+    Ip,
+}
+
+impl LimitKind {
+    /// The Name of the Limit, which can be used as a Label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Port => "port",
+            Self::Client => "client",
+            Self::Ip => "ip",
+        }
+    }
+}
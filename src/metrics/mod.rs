@@ -3,3 +3,21 @@ pub use empty::Empty;
 
 mod traits;
 pub use traits::*;
+
+mod labels;
+pub use labels::{ConnectionLabels, ConnectionStats, LimitKind};
+
+#[cfg(feature = "prometheus")]
+mod prometheus;
+#[cfg(feature = "prometheus")]
+pub use self::prometheus::{Prometheus, PrometheusEndpoint};
+
+#[cfg(feature = "metrics")]
+mod facade;
+#[cfg(feature = "metrics")]
+pub use facade::Facade;
+
+#[cfg(feature = "opentelemetry")]
+mod opentelemetry;
+#[cfg(feature = "opentelemetry")]
+pub use self::opentelemetry::OpenTelemetry;
@@ -0,0 +1,342 @@
+//! A Metrics-Collector that records all the Metrics using
+//! [OpenTelemetry](::opentelemetry) Instruments
+
+use std::time::Duration;
+
+use ::opentelemetry::{
+    metrics::{Counter, Gauge, Histogram, Meter, UpDownCounter},
+    KeyValue,
+};
+
+use crate::message::CloseReason;
+use crate::metrics::{ConnectionLabels, ConnectionStats, LimitKind, Metrics};
+
+/// Records all the Metrics using OpenTelemetry Instruments, which are created
+/// from the given Meter
+#[derive(Debug, Clone)]
+pub struct OpenTelemetry {
+    received_msgs: Counter<u64>,
+    received_bytes: Counter<u64>,
+    received_wire_bytes: Counter<u64>,
+    sent_msgs: Counter<u64>,
+    sent_bytes: Counter<u64>,
+    sent_wire_bytes: Counter<u64>,
+    rtt: Histogram<f64>,
+    closes: Counter<u64>,
+    clients: UpDownCounter<i64>,
+    client_connects: Counter<u64>,
+    client_disconnects: Counter<u64>,
+    handshakes: Counter<u64>,
+    handshake_latency: Histogram<f64>,
+    users: Counter<u64>,
+    user_bytes: Counter<u64>,
+    open_connections: UpDownCounter<i64>,
+    connection_duration: Histogram<f64>,
+    connection_bytes: Histogram<u64>,
+    reconnect_attempts: Counter<u64>,
+    queue_depth: Gauge<u64>,
+}
+
+fn port(port: u16) -> KeyValue {
+    KeyValue::new("port", i64::from(port))
+}
+
+impl OpenTelemetry {
+    /// Creates all the Instruments using the given Meter
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            received_msgs: meter
+                .u64_counter("tunneler.messages.received")
+                .with_description("The Number of Data-Messages received")
+                .build(),
+            received_bytes: meter
+                .u64_counter("tunneler.received")
+                .with_description("The Number of Bytes received in Data-Messages")
+                .with_unit("By")
+                .build(),
+            received_wire_bytes: meter
+                .u64_counter("tunneler.received.wire")
+                .with_description(
+                    "The Number of Bytes received over the Connection, including Headers and after Compression",
+                )
+                .with_unit("By")
+                .build(),
+            sent_msgs: meter
+                .u64_counter("tunneler.messages.sent")
+                .with_description("The Number of Messages send")
+                .build(),
+            sent_bytes: meter
+                .u64_counter("tunneler.sent")
+                .with_description("The Number of Bytes send in Messages")
+                .with_unit("By")
+                .build(),
+            sent_wire_bytes: meter
+                .u64_counter("tunneler.sent.wire")
+                .with_description(
+                    "The Number of Bytes send over the Connection, including Headers and after Compression",
+                )
+                .with_unit("By")
+                .build(),
+            rtt: meter
+                .f64_histogram("tunneler.rtt")
+                .with_description("The Round-Trip-Time measured using Pings")
+                .with_unit("s")
+                .build(),
+            closes: meter
+                .u64_counter("tunneler.closes.received")
+                .with_description("The Number of Connections closed by the other Side")
+                .build(),
+            clients: meter
+                .i64_up_down_counter("tunneler.clients")
+                .with_description("The Number of currently connected Clients")
+                .build(),
+            client_connects: meter
+                .u64_counter("tunneler.client.connects")
+                .with_description("The Number of Client-Connects")
+                .build(),
+            client_disconnects: meter
+                .u64_counter("tunneler.client.disconnects")
+                .with_description("The Number of Client-Disconnects")
+                .build(),
+            handshakes: meter
+                .u64_counter("tunneler.handshakes")
+                .with_description("The Number of performed Handshakes")
+                .build(),
+            handshake_latency: meter
+                .f64_histogram("tunneler.handshake.duration")
+                .with_description("The Time it took to perform successful Handshakes")
+                .with_unit("s")
+                .build(),
+            users: meter
+                .u64_counter("tunneler.users")
+                .with_description("The Number of User-Connections")
+                .build(),
+            user_bytes: meter
+                .u64_counter("tunneler.user.bytes")
+                .with_description("The Number of Bytes exchanged with the Users")
+                .with_unit("By")
+                .build(),
+            open_connections: meter
+                .i64_up_down_counter("tunneler.connections.open")
+                .with_description("The Number of currently open User-Connections")
+                .build(),
+            connection_duration: meter
+                .f64_histogram("tunneler.connection.duration")
+                .with_description("How long the User-Connections were open")
+                .with_unit("s")
+                .build(),
+            connection_bytes: meter
+                .u64_histogram("tunneler.connection.bytes")
+                .with_description("The Number of Bytes exchanged over single User-Connections")
+                .with_unit("By")
+                .build(),
+            reconnect_attempts: meter
+                .u64_counter("tunneler.reconnect.attempts")
+                .with_description("The Number of Attempts to reconnect to the Server")
+                .build(),
+            queue_depth: meter
+                .u64_gauge("tunneler.wait_queue.depth")
+                .with_description("The Number of Users waiting for a Client")
+                .build(),
+        }
+    }
+}
+
+impl Metrics for OpenTelemetry {
+    fn received_msg(&self) {
+        self.received_msgs.add(1, &[]);
+    }
+    fn recv_bytes(&self, recv: u64) {
+        self.received_bytes.add(recv, &[]);
+    }
+    fn recv_wire_bytes(&self, recv: u64) {
+        self.received_wire_bytes.add(recv, &[]);
+    }
+
+    fn send_msg(&self) {
+        self.sent_msgs.add(1, &[]);
+    }
+    fn send_bytes(&self, send: u64) {
+        self.sent_bytes.add(send, &[]);
+    }
+    fn send_wire_bytes(&self, send: u64) {
+        self.sent_wire_bytes.add(send, &[]);
+    }
+
+    fn rtt(&self, rtt: Duration) {
+        self.rtt.record(rtt.as_secs_f64(), &[]);
+    }
+
+    fn received_close(&self, reason: &CloseReason) {
+        self.closes
+            .add(1, &[KeyValue::new("code", format!("{:?}", reason.code()))]);
+    }
+
+    fn client_connected(&self, p: u16) {
+        self.clients.add(1, &[port(p)]);
+        self.client_connects.add(1, &[port(p)]);
+    }
+    fn client_disconnected(&self, p: u16) {
+        self.clients.add(-1, &[port(p)]);
+        self.client_disconnects.add(1, &[port(p)]);
+    }
+
+    fn handshake_succeeded(&self) {
+        self.handshakes
+            .add(1, &[KeyValue::new("result", "success")]);
+    }
+    fn handshake_failed(&self) {
+        self.handshakes
+            .add(1, &[KeyValue::new("result", "failure")]);
+    }
+
+    fn user_accepted(&self, p: u16) {
+        self.users
+            .add(1, &[port(p), KeyValue::new("result", "accepted")]);
+    }
+    fn user_rejected(&self, p: u16) {
+        self.users
+            .add(1, &[port(p), KeyValue::new("result", "rejected")]);
+    }
+    fn user_limited(&self, p: u16, limit: LimitKind) {
+        self.users.add(
+            1,
+            &[
+                port(p),
+                KeyValue::new("result", format!("limited_{}", limit.as_str())),
+            ],
+        );
+    }
+    fn user_timeout(&self, p: u16) {
+        self.users
+            .add(1, &[port(p), KeyValue::new("result", "timeout")]);
+    }
+
+    fn user_recv_bytes(&self, p: u16, recv: u64) {
+        self.user_bytes
+            .add(recv, &[port(p), KeyValue::new("direction", "recv")]);
+    }
+    fn user_send_bytes(&self, p: u16, send: u64) {
+        self.user_bytes
+            .add(send, &[port(p), KeyValue::new("direction", "send")]);
+    }
+
+    fn connection_opened(&self, labels: &ConnectionLabels) {
+        self.open_connections.add(1, &[port(labels.port)]);
+    }
+    fn connection_closed(&self, labels: &ConnectionLabels, stats: &ConnectionStats) {
+        self.open_connections.add(-1, &[port(labels.port)]);
+        self.connection_duration
+            .record(stats.duration.as_secs_f64(), &[port(labels.port)]);
+        self.connection_bytes.record(
+            stats.recv_bytes,
+            &[port(labels.port), KeyValue::new("direction", "recv")],
+        );
+        self.connection_bytes.record(
+            stats.send_bytes,
+            &[port(labels.port), KeyValue::new("direction", "send")],
+        );
+    }
+
+    fn reconnect_attempt(&self, p: u16, _attempt: u32) {
+        self.reconnect_attempts.add(1, &[port(p)]);
+    }
+
+    fn queue_depth(&self, p: u16, depth: usize) {
+        self.queue_depth.record(depth as u64, &[port(p)]);
+    }
+
+    fn handshake_latency(&self, p: u16, latency: Duration) {
+        self.handshake_latency
+            .record(latency.as_secs_f64(), &[port(p)]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ::opentelemetry::metrics::MeterProvider;
+    use opentelemetry_sdk::metrics::{
+        data::{AggregatedMetrics, MetricData},
+        InMemoryMetricExporter, PeriodicReader, SdkMeterProvider,
+    };
+
+    /// Runs the given Function with a Collector, backed by an in-memory
+    /// Exporter, and returns the Sums of all u64-Counters as
+    /// (Name, Attributes, Value)
+    fn record<F>(func: F) -> Vec<(String, Vec<KeyValue>, u64)>
+    where
+        F: FnOnce(&OpenTelemetry),
+    {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+
+        func(&OpenTelemetry::new(&provider.meter("tunneler")));
+        provider.force_flush().unwrap();
+
+        let mut result = Vec::new();
+        for resource in exporter.get_finished_metrics().unwrap() {
+            for scope in resource.scope_metrics() {
+                for metric in scope.metrics() {
+                    if let AggregatedMetrics::U64(MetricData::Sum(sum)) = metric.data() {
+                        for point in sum.data_points() {
+                            let mut attributes: Vec<KeyValue> =
+                                point.attributes().cloned().collect();
+                            attributes.sort_by(|a, b| a.key.cmp(&b.key));
+                            result.push((metric.name().to_owned(), attributes, point.value()));
+                        }
+                    }
+                }
+            }
+        }
+        provider.shutdown().unwrap();
+        result
+    }
+
+    #[test]
+    fn counters() {
+        let recorded = record(|metrics| {
+            metrics.send_msg();
+            metrics.send_bytes(10);
+            metrics.send_bytes(5);
+        });
+
+        assert_eq!(
+            true,
+            recorded.contains(&("tunneler.messages.sent".to_owned(), vec![], 1))
+        );
+        assert_eq!(
+            true,
+            recorded.contains(&("tunneler.sent".to_owned(), vec![], 15))
+        );
+    }
+
+    #[test]
+    fn labeled_counters() {
+        let recorded = record(|metrics| {
+            metrics.user_recv_bytes(8080, 10);
+            metrics.user_recv_bytes(8080, 20);
+            metrics.user_send_bytes(8081, 5);
+        });
+
+        assert_eq!(
+            true,
+            recorded.contains(&(
+                "tunneler.user.bytes".to_owned(),
+                vec![KeyValue::new("direction", "recv"), port(8080)],
+                30
+            ))
+        );
+        assert_eq!(
+            true,
+            recorded.contains(&(
+                "tunneler.user.bytes".to_owned(),
+                vec![KeyValue::new("direction", "send"), port(8081)],
+                5
+            ))
+        );
+    }
+}
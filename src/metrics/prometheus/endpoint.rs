@@ -0,0 +1,187 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use super::Prometheus;
+
+/// The maximum Size of a Request that is accepted by the Endpoint
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// The Time a Connection has for sending its Request and for receiving the
+/// Response, before it is closed
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A small HTTP-Endpoint that serves the Metrics of a [`Prometheus`]-Collector
+/// on `/metrics`, so that they can be scraped by Prometheus
+#[derive(Debug, Clone)]
+pub struct PrometheusEndpoint {
+    addr: SocketAddr,
+    metrics: Prometheus,
+}
+
+impl PrometheusEndpoint {
+    /// Creates a new Endpoint
+    ///
+    /// # Params:
+    /// * `addr`: The Address on which the Endpoint should listen
+    /// * `metrics`: The Collector whose Metrics should be served
+    pub fn new(addr: SocketAddr, metrics: Prometheus) -> Self {
+        Self { addr, metrics }
+    }
+
+    /// The Address on which the Endpoint listens
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Binds to the configured Address and then serves the Metrics
+    ///
+    /// # Behaviour
+    /// This only returns if binding to the Address failed
+    pub async fn serve(self) -> std::io::Result<()> {
+        let listener = TcpListener::bind(self.addr).await?;
+        info!("Serving Metrics on: {}", self.addr);
+
+        Self::run(listener, self.metrics).await;
+        Ok(())
+    }
+
+    async fn run(listener: TcpListener, metrics: Prometheus) {
+        loop {
+            let con = match listener.accept().await {
+                Ok((con, _)) => con,
+                Err(e) => {
+                    error!("Accepting Metrics-Connection: {}", e);
+                    continue;
+                }
+            };
+
+            let metrics = metrics.clone();
+            tokio::task::spawn(async move {
+                if let Err(e) = Self::respond(con, &metrics).await {
+                    error!("Responding to Metrics-Request: {}", e);
+                }
+            });
+        }
+    }
+
+    /// Reads the Request-Head from the Connection
+    ///
+    /// Returns None if the Connection was closed before a complete Head was
+    /// received or the Head was too large
+    async fn read_head(con: &mut TcpStream) -> std::io::Result<Option<String>> {
+        let mut head = Vec::new();
+        let mut buf = [0; 1024];
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+            let read = con.read(&mut buf).await?;
+            if read == 0 || head.len() + read > MAX_REQUEST_SIZE {
+                return Ok(None);
+            }
+            head.extend_from_slice(&buf[..read]);
+        }
+
+        Ok(Some(String::from_utf8_lossy(&head).into_owned()))
+    }
+
+    async fn respond(mut con: TcpStream, metrics: &Prometheus) -> std::io::Result<()> {
+        // Connections that are too slow are simply closed, so that they can
+        // not hold on to their Task forever
+        let head = match tokio::time::timeout(REQUEST_TIMEOUT, Self::read_head(&mut con)).await {
+            Ok(h) => h?,
+            Err(_) => return Ok(()),
+        };
+        let head = match head {
+            Some(h) => h,
+            None => return Ok(()),
+        };
+
+        let mut request_line = head.lines().next().unwrap_or("").split(' ');
+        let method = request_line.next().unwrap_or("");
+        let path = request_line.next().unwrap_or("");
+
+        let (status, content_type, body) = match (method, path) {
+            ("GET", "/metrics") => ("200 OK", ::prometheus::TEXT_FORMAT, metrics.render()),
+            ("GET", _) => ("404 Not Found", "text/plain", "Not Found\n".to_owned()),
+            _ => (
+                "405 Method Not Allowed",
+                "text/plain",
+                "Method Not Allowed\n".to_owned(),
+            ),
+        };
+
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        );
+        let write = async {
+            con.write_all(response.as_bytes()).await?;
+            con.shutdown().await
+        };
+        match tokio::time::timeout(REQUEST_TIMEOUT, write).await {
+            Ok(r) => r,
+            Err(_) => Err(std::io::Error::from(std::io::ErrorKind::TimedOut)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+
+    async fn request(addr: SocketAddr, request: &str) -> String {
+        let mut con = TcpStream::connect(addr).await.unwrap();
+        con.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        con.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn serves_metrics() {
+        let metrics = Prometheus::new();
+        metrics.handshake_succeeded();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::task::spawn(PrometheusEndpoint::run(listener, metrics));
+
+        let response = request(addr, "GET /metrics HTTP/1.1\r\nHost: test\r\n\r\n").await;
+        assert_eq!(true, response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(
+            true,
+            response.contains("tunneler_handshakes_total{result=\"success\"} 1\n")
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_connection_closed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::task::spawn(PrometheusEndpoint::run(listener, Prometheus::new()));
+
+        let mut con = TcpStream::connect(addr).await.unwrap();
+        let start = tokio::time::Instant::now();
+
+        let mut response = Vec::new();
+        con.read_to_end(&mut response).await.unwrap();
+        assert_eq!(true, response.is_empty());
+        assert_eq!(true, start.elapsed() >= REQUEST_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn unknown_path() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::task::spawn(PrometheusEndpoint::run(listener, Prometheus::new()));
+
+        let response = request(addr, "GET /other HTTP/1.1\r\n\r\n").await;
+        assert_eq!(true, response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
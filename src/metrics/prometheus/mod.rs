@@ -0,0 +1,499 @@
+//! A ready to use Metrics-Collector that keeps track of all the Metrics using
+//! the [`prometheus`](::prometheus) Crate, which can then be rendered in the
+//! Prometheus Text-Format or served over HTTP using the
+//! [`PrometheusEndpoint`]
+
+use std::time::Duration;
+
+use ::prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter,
+    IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+use crate::message::CloseReason;
+use crate::metrics::{ConnectionLabels, ConnectionStats, LimitKind, Metrics};
+
+mod endpoint;
+pub use endpoint::PrometheusEndpoint;
+
+/// The Namespace used for all the Metrics
+const NAMESPACE: &str = "tunneler";
+
+/// A Metrics-Collector that records everything in a Prometheus-Registry
+///
+/// Cloning this is cheap and all the Clones share the same underlying
+/// Metrics
+#[derive(Clone)]
+pub struct Prometheus {
+    registry: Registry,
+    received_msgs: IntCounter,
+    received_bytes: IntCounter,
+    received_wire_bytes: IntCounter,
+    sent_msgs: IntCounter,
+    sent_bytes: IntCounter,
+    sent_wire_bytes: IntCounter,
+    rtt: Histogram,
+    closes: IntCounterVec,
+    clients: IntGaugeVec,
+    client_connects: IntCounterVec,
+    client_disconnects: IntCounterVec,
+    handshakes: IntCounterVec,
+    handshake_latency: HistogramVec,
+    users: IntCounterVec,
+    user_bytes: IntCounterVec,
+    open_connections: IntGaugeVec,
+    connection_duration: HistogramVec,
+    connection_bytes: HistogramVec,
+    reconnect_attempts: IntCounterVec,
+    queue_depth: IntGaugeVec,
+}
+
+impl std::fmt::Debug for Prometheus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Prometheus").finish()
+    }
+}
+
+impl Default for Prometheus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn opts(name: &str, help: &str) -> Opts {
+    Opts::new(name, help).namespace(NAMESPACE)
+}
+fn histogram_opts(name: &str, help: &str, buckets: Vec<f64>) -> HistogramOpts {
+    HistogramOpts::new(name, help)
+        .namespace(NAMESPACE)
+        .buckets(buckets)
+}
+
+impl Prometheus {
+    /// Creates a new Collector, with its own Registry
+    pub fn new() -> Self {
+        Self::with_registry(Registry::new())
+    }
+
+    /// Creates a new Collector, that registers all its Metrics in the given
+    /// Registry
+    ///
+    /// # Panics
+    /// If the Registry already contains Metrics with the same Names, for
+    /// example because another Collector was already created for it
+    pub fn with_registry(registry: Registry) -> Self {
+        let duration_buckets = exponential_buckets(0.1, 4.0, 10).unwrap();
+        let byte_buckets = exponential_buckets(256.0, 4.0, 12).unwrap();
+
+        let metrics = Self {
+            received_msgs: IntCounter::with_opts(opts(
+                "messages_received_total",
+                "The Number of Data-Messages received",
+            ))
+            .unwrap(),
+            received_bytes: IntCounter::with_opts(opts(
+                "received_bytes_total",
+                "The Number of Bytes received in Data-Messages",
+            ))
+            .unwrap(),
+            received_wire_bytes: IntCounter::with_opts(opts(
+                "received_wire_bytes_total",
+                "The Number of Bytes received over the Connection, including Headers and after Compression",
+            ))
+            .unwrap(),
+            sent_msgs: IntCounter::with_opts(opts(
+                "messages_sent_total",
+                "The Number of Messages send",
+            ))
+            .unwrap(),
+            sent_bytes: IntCounter::with_opts(opts(
+                "sent_bytes_total",
+                "The Number of Bytes send in Messages",
+            ))
+            .unwrap(),
+            sent_wire_bytes: IntCounter::with_opts(opts(
+                "sent_wire_bytes_total",
+                "The Number of Bytes send over the Connection, including Headers and after Compression",
+            ))
+            .unwrap(),
+            rtt: Histogram::with_opts(histogram_opts(
+                "rtt_seconds",
+                "The Round-Trip-Time measured using Pings",
+                ::prometheus::DEFAULT_BUCKETS.to_vec(),
+            ))
+            .unwrap(),
+            closes: IntCounterVec::new(
+                opts(
+                    "closes_received_total",
+                    "The Number of Connections closed by the other Side",
+                ),
+                &["code"],
+            )
+            .unwrap(),
+            clients: IntGaugeVec::new(
+                opts("clients", "The Number of currently connected Clients"),
+                &["port"],
+            )
+            .unwrap(),
+            client_connects: IntCounterVec::new(
+                opts("client_connects_total", "The Number of Client-Connects"),
+                &["port"],
+            )
+            .unwrap(),
+            client_disconnects: IntCounterVec::new(
+                opts(
+                    "client_disconnects_total",
+                    "The Number of Client-Disconnects",
+                ),
+                &["port"],
+            )
+            .unwrap(),
+            handshakes: IntCounterVec::new(
+                opts("handshakes_total", "The Number of performed Handshakes"),
+                &["result"],
+            )
+            .unwrap(),
+            handshake_latency: HistogramVec::new(
+                histogram_opts(
+                    "handshake_duration_seconds",
+                    "The Time it took to perform successful Handshakes",
+                    ::prometheus::DEFAULT_BUCKETS.to_vec(),
+                ),
+                &["port"],
+            )
+            .unwrap(),
+            users: IntCounterVec::new(
+                opts("users_total", "The Number of User-Connections"),
+                &["port", "result"],
+            )
+            .unwrap(),
+            user_bytes: IntCounterVec::new(
+                opts(
+                    "user_bytes_total",
+                    "The Number of Bytes exchanged with the Users",
+                ),
+                &["port", "direction"],
+            )
+            .unwrap(),
+            open_connections: IntGaugeVec::new(
+                opts(
+                    "open_connections",
+                    "The Number of currently open User-Connections",
+                ),
+                &["port"],
+            )
+            .unwrap(),
+            connection_duration: HistogramVec::new(
+                histogram_opts(
+                    "connection_duration_seconds",
+                    "How long the User-Connections were open",
+                    duration_buckets,
+                ),
+                &["port"],
+            )
+            .unwrap(),
+            connection_bytes: HistogramVec::new(
+                histogram_opts(
+                    "connection_bytes",
+                    "The Number of Bytes exchanged over single User-Connections",
+                    byte_buckets,
+                ),
+                &["port", "direction"],
+            )
+            .unwrap(),
+            reconnect_attempts: IntCounterVec::new(
+                opts(
+                    "reconnect_attempts_total",
+                    "The Number of Attempts to reconnect to the Server",
+                ),
+                &["port"],
+            )
+            .unwrap(),
+            queue_depth: IntGaugeVec::new(
+                opts(
+                    "wait_queue_depth",
+                    "The Number of Users waiting for a Client",
+                ),
+                &["port"],
+            )
+            .unwrap(),
+            registry,
+        };
+
+        metrics.register();
+        metrics
+    }
+
+    fn register(&self) {
+        let collectors: Vec<Box<dyn ::prometheus::core::Collector>> = vec![
+            Box::new(self.received_msgs.clone()),
+            Box::new(self.received_bytes.clone()),
+            Box::new(self.received_wire_bytes.clone()),
+            Box::new(self.sent_msgs.clone()),
+            Box::new(self.sent_bytes.clone()),
+            Box::new(self.sent_wire_bytes.clone()),
+            Box::new(self.rtt.clone()),
+            Box::new(self.closes.clone()),
+            Box::new(self.clients.clone()),
+            Box::new(self.client_connects.clone()),
+            Box::new(self.client_disconnects.clone()),
+            Box::new(self.handshakes.clone()),
+            Box::new(self.handshake_latency.clone()),
+            Box::new(self.users.clone()),
+            Box::new(self.user_bytes.clone()),
+            Box::new(self.open_connections.clone()),
+            Box::new(self.connection_duration.clone()),
+            Box::new(self.connection_bytes.clone()),
+            Box::new(self.reconnect_attempts.clone()),
+            Box::new(self.queue_depth.clone()),
+        ];
+
+        for collector in collectors {
+            self.registry
+                .register(collector)
+                .expect("Registering the Metrics");
+        }
+    }
+
+    /// The Registry containing all the Metrics
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Renders all the Metrics of the Registry in the Prometheus Text-Format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            error!("Encoding Metrics: {}", e);
+        }
+        String::from_utf8_lossy(&buffer).into_owned()
+    }
+}
+
+impl Metrics for Prometheus {
+    fn received_msg(&self) {
+        self.received_msgs.inc();
+    }
+    fn recv_bytes(&self, recv: u64) {
+        self.received_bytes.inc_by(recv);
+    }
+    fn recv_wire_bytes(&self, recv: u64) {
+        self.received_wire_bytes.inc_by(recv);
+    }
+
+    fn send_msg(&self) {
+        self.sent_msgs.inc();
+    }
+    fn send_bytes(&self, send: u64) {
+        self.sent_bytes.inc_by(send);
+    }
+    fn send_wire_bytes(&self, send: u64) {
+        self.sent_wire_bytes.inc_by(send);
+    }
+
+    fn rtt(&self, rtt: Duration) {
+        self.rtt.observe(rtt.as_secs_f64());
+    }
+
+    fn received_close(&self, reason: &CloseReason) {
+        let code = format!("{:?}", reason.code());
+        self.closes.with_label_values(&[&code]).inc();
+    }
+
+    fn client_connected(&self, port: u16) {
+        let port = port.to_string();
+        self.clients.with_label_values(&[&port]).inc();
+        self.client_connects.with_label_values(&[&port]).inc();
+    }
+    fn client_disconnected(&self, port: u16) {
+        let port = port.to_string();
+        self.clients.with_label_values(&[&port]).dec();
+        self.client_disconnects.with_label_values(&[&port]).inc();
+    }
+
+    fn handshake_succeeded(&self) {
+        self.handshakes.with_label_values(&["success"]).inc();
+    }
+    fn handshake_failed(&self) {
+        self.handshakes.with_label_values(&["failure"]).inc();
+    }
+
+    fn user_accepted(&self, port: u16) {
+        self.users
+            .with_label_values(&[&port.to_string(), "accepted"])
+            .inc();
+    }
+    fn user_rejected(&self, port: u16) {
+        self.users
+            .with_label_values(&[&port.to_string(), "rejected"])
+            .inc();
+    }
+    fn user_limited(&self, port: u16, limit: LimitKind) {
+        self.users
+            .with_label_values(&[&port.to_string(), &format!("limited_{}", limit.as_str())])
+            .inc();
+    }
+    fn user_timeout(&self, port: u16) {
+        self.users
+            .with_label_values(&[&port.to_string(), "timeout"])
+            .inc();
+    }
+
+    fn user_recv_bytes(&self, port: u16, recv: u64) {
+        self.user_bytes
+            .with_label_values(&[&port.to_string(), "recv"])
+            .inc_by(recv);
+    }
+    fn user_send_bytes(&self, port: u16, send: u64) {
+        self.user_bytes
+            .with_label_values(&[&port.to_string(), "send"])
+            .inc_by(send);
+    }
+
+    fn connection_opened(&self, labels: &ConnectionLabels) {
+        self.open_connections
+            .with_label_values(&[&labels.port.to_string()])
+            .inc();
+    }
+    fn connection_closed(&self, labels: &ConnectionLabels, stats: &ConnectionStats) {
+        let port = labels.port.to_string();
+        self.open_connections.with_label_values(&[&port]).dec();
+        self.connection_duration
+            .with_label_values(&[&port])
+            .observe(stats.duration.as_secs_f64());
+        self.connection_bytes
+            .with_label_values(&[&port, "recv"])
+            .observe(stats.recv_bytes as f64);
+        self.connection_bytes
+            .with_label_values(&[&port, "send"])
+            .observe(stats.send_bytes as f64);
+    }
+
+    fn reconnect_attempt(&self, port: u16, _attempt: u32) {
+        self.reconnect_attempts
+            .with_label_values(&[&port.to_string()])
+            .inc();
+    }
+
+    fn queue_depth(&self, port: u16, depth: usize) {
+        self.queue_depth
+            .with_label_values(&[&port.to_string()])
+            .set(depth as i64);
+    }
+
+    fn handshake_latency(&self, port: u16, latency: Duration) {
+        self.handshake_latency
+            .with_label_values(&[&port.to_string()])
+            .observe(latency.as_secs_f64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::CloseCode;
+
+    #[test]
+    fn render_counters() {
+        let metrics = Prometheus::new();
+
+        metrics.received_msg();
+        metrics.recv_bytes(10);
+        metrics.recv_wire_bytes(17);
+        metrics.user_accepted(8080);
+        metrics.user_accepted(8080);
+        metrics.user_rejected(8081);
+        metrics.user_limited(8081, LimitKind::Ip);
+        metrics.user_timeout(8081);
+        metrics.received_close(&CloseReason::new(CloseCode::Timeout));
+
+        let rendered = metrics.render();
+        assert_eq!(
+            true,
+            rendered.contains("tunneler_messages_received_total 1\n")
+        );
+        assert_eq!(
+            true,
+            rendered.contains("tunneler_received_bytes_total 10\n")
+        );
+        assert_eq!(
+            true,
+            rendered.contains("tunneler_received_wire_bytes_total 17\n")
+        );
+        assert_eq!(
+            true,
+            rendered.contains("tunneler_users_total{port=\"8080\",result=\"accepted\"} 2\n")
+        );
+        assert_eq!(
+            true,
+            rendered.contains("tunneler_users_total{port=\"8081\",result=\"rejected\"} 1\n")
+        );
+        assert_eq!(
+            true,
+            rendered.contains("tunneler_users_total{port=\"8081\",result=\"limited_ip\"} 1\n")
+        );
+        assert_eq!(
+            true,
+            rendered.contains("tunneler_users_total{port=\"8081\",result=\"timeout\"} 1\n")
+        );
+        assert_eq!(
+            true,
+            rendered.contains("tunneler_closes_received_total{code=\"Timeout\"} 1\n")
+        );
+    }
+
+    #[test]
+    fn render_connection_lifecycle() {
+        let metrics = Prometheus::new();
+        let labels = ConnectionLabels {
+            client_id: 1,
+            port: 8080,
+            connection_id: 2,
+        };
+
+        metrics.connection_opened(&labels);
+        assert_eq!(
+            true,
+            metrics
+                .render()
+                .contains("tunneler_open_connections{port=\"8080\"} 1\n")
+        );
+
+        metrics.connection_closed(
+            &labels,
+            &ConnectionStats {
+                duration: Duration::from_secs(2),
+                recv_bytes: 100,
+                send_bytes: 200,
+            },
+        );
+        let rendered = metrics.render();
+        assert_eq!(
+            true,
+            rendered.contains("tunneler_open_connections{port=\"8080\"} 0\n")
+        );
+        assert_eq!(
+            true,
+            rendered.contains("tunneler_connection_duration_seconds_sum{port=\"8080\"} 2\n")
+        );
+        assert_eq!(
+            true,
+            rendered
+                .contains("tunneler_connection_bytes_sum{direction=\"send\",port=\"8080\"} 200\n")
+        );
+    }
+
+    #[test]
+    fn clones_share_metrics() {
+        let metrics = Prometheus::new();
+        let cloned = metrics.clone();
+
+        cloned.client_connected(8080);
+        assert_eq!(
+            true,
+            metrics
+                .render()
+                .contains("tunneler_clients{port=\"8080\"} 1\n")
+        );
+    }
+}
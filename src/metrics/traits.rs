@@ -1,3 +1,8 @@
+use std::time::Duration;
+
+use crate::message::CloseReason;
+use crate::metrics::{ConnectionLabels, ConnectionStats, LimitKind};
+
 /// The Interface used to collect metrics from the Tunneler-Software.
 /// This allows for the usage of a variety of different Metrics-Systems
 /// as long as they can provide an implementation of this Interface.
@@ -5,12 +10,84 @@ pub trait Metrics {
     /// This is called every time a Message is received
     fn received_msg(&self) {}
     /// This is called every time a message was received with the size of the
-    /// Data contained in the Message (not the size of the entire Message).
+    /// Data contained in the Message (not the size of the entire Message),
+    /// after it was decompressed.
     fn recv_bytes(&self, _recv: u64) {}
+    /// This is called every time a message was received with the size of the
+    /// entire Message as it was received, including its Header and with its
+    /// Data still compressed.
+    fn recv_wire_bytes(&self, _recv: u64) {}
 
     /// This is called every time a Message is send
     fn send_msg(&self) {}
     /// This is called every time a message is send with the size of the Data
-    /// contained in the Message (not the size of the entire Message).
+    /// contained in the Message (not the size of the entire Message), before
+    /// it was compressed.
     fn send_bytes(&self, _send: u64) {}
+    /// This is called every time a message is send with the size of the
+    /// entire Message as it is send, including its Header and with its Data
+    /// already compressed.
+    fn send_wire_bytes(&self, _send: u64) {}
+
+    /// This is called every time a Pong is received with the measured
+    /// Round-Trip-Time of the Ping it answered
+    fn rtt(&self, _rtt: Duration) {}
+
+    /// This is called every time the other side closes a Connection and
+    /// provides a Reason for it
+    fn received_close(&self, _reason: &CloseReason) {}
+
+    /// This is called by the Server every time a Client connected for the
+    /// given Port
+    fn client_connected(&self, _port: u16) {}
+    /// This is called by the Server every time a Client for the given Port
+    /// disconnected
+    fn client_disconnected(&self, _port: u16) {}
+
+    /// This is called by the Server every time the Handshake with a new Client
+    /// was successful
+    fn handshake_succeeded(&self) {}
+    /// This is called by the Server every time the Handshake with a new Client
+    /// failed
+    fn handshake_failed(&self) {}
+
+    /// This is called by the Server every time a User-Connection on the given
+    /// Port was accepted by a Client
+    fn user_accepted(&self, _port: u16) {}
+    /// This is called by the Server every time a User-Connection on the given
+    /// Port could not be handed to any Client and was closed
+    fn user_rejected(&self, _port: u16) {}
+    /// This is called by the Server every time a User-Connection on the given
+    /// Port was rejected right away, because it would exceed the given Limit
+    fn user_limited(&self, _port: u16, _limit: LimitKind) {}
+    /// This is called by both the Server and the Client every time a
+    /// User-Connection on the given Port is closed, because there was no
+    /// Activity on it for the configured Idle-Timeout
+    fn user_timeout(&self, _port: u16) {}
+
+    /// This is called by the Server every time Data from the Users on the
+    /// given Port is forwarded to a Client, with the size of that Data
+    fn user_recv_bytes(&self, _port: u16, _recv: u64) {}
+    /// This is called by the Server every time Data from a Client is forwarded
+    /// to the Users on the given Port, with the size of that Data
+    fn user_send_bytes(&self, _port: u16, _send: u64) {}
+
+    /// This is called by the Server every time a User-Connection was accepted
+    /// by a Client and Data is being forwarded
+    fn connection_opened(&self, _labels: &ConnectionLabels) {}
+    /// This is called by the Server once a User-Connection, that was
+    /// previously opened, is completely closed
+    fn connection_closed(&self, _labels: &ConnectionLabels, _stats: &ConnectionStats) {}
+
+    /// This is called by the Client every time it attempts to reconnect to
+    /// the Server for the given Port, with the Number of the current Attempt
+    fn reconnect_attempt(&self, _port: u16, _attempt: u32) {}
+
+    /// This is called by the Server every time the Number of Users waiting
+    /// for a Client on the given Port changes
+    fn queue_depth(&self, _port: u16, _depth: usize) {}
+
+    /// This is called by both the Server and the Client every time a
+    /// Handshake for the given Port was successful, with the Time it took
+    fn handshake_latency(&self, _port: u16, _latency: Duration) {}
 }
@@ -12,7 +12,14 @@
 //! Users and forwarding them to a given Client and managing their Data
 //! exchange for the entire lifetime of the connection
 
-use crate::{handshake, metrics::Metrics};
+use crate::{
+    accesslog::AccessLog,
+    general::{ConnectionReader, ConnectionWriter, Pinger},
+    handshake,
+    message::Compression,
+    metrics::Metrics,
+    ChunkSize, RateLimit,
+};
 
 use rand::Rng;
 use std::collections::BTreeMap;
@@ -21,25 +28,64 @@ use tokio::net::TcpListener;
 
 mod tcpforwarder;
 use tcpforwarder::TCPClient;
+pub mod balancer;
+pub use balancer::Balancing;
 mod clientmanager;
 use clientmanager::ClientManager;
 mod ports;
+mod sessions;
+use sessions::Sessions;
 mod user;
 
+mod limits;
+pub use limits::Limits;
+
+mod events;
+use events::Events;
+pub use events::{DisconnectReason, ServerEvent};
+
+mod handle;
+use handle::Ports;
+pub use handle::{ClientStatus, ServerHandle, UserStatus};
+
 mod builder;
 pub use builder::ServerBuilder;
 
 pub use ports::Strategy;
 use tcpforwarder::TCPForwarder;
+pub use tcpforwarder::WaitQueue;
 
 /// Holds all information needed to creating and running
 /// a single Tunneler-Server
 #[derive(Debug)]
-pub struct Server<M> {
+pub struct Server<M>
+where
+    M: Metrics,
+{
     listen_port: u32,
     port_strategy: Strategy,
     key: Vec<u8>,
     metrics: Arc<M>,
+    balancing: Balancing,
+    port_balancing: BTreeMap<u16, Balancing>,
+    wait_queue: Option<WaitQueue>,
+    ports: Ports<M>,
+    events: Events,
+    access_log: Option<AccessLog>,
+    limits: Limits,
+    client_rate_limit: Option<RateLimit>,
+    user_rate_limit: Option<RateLimit>,
+    idle_timeout: Option<std::time::Duration>,
+    chunk_size: ChunkSize,
+    port_chunk_size: BTreeMap<u16, ChunkSize>,
+    /// The Compression-Algorithms supported by the Server, in the Order in
+    /// which they are preferred
+    compression: Vec<Compression>,
+    /// The maximum Size of the Data of a single Message
+    max_message_size: usize,
+    sessions: Option<Arc<Sessions<M>>>,
+    #[cfg(feature = "prometheus")]
+    metrics_endpoint: Option<crate::metrics::PrometheusEndpoint>,
 }
 
 /// Creates a new Builder to construct a new Server Instance
@@ -49,8 +95,22 @@ pub fn builder() -> ServerBuilder<builder::BuilderEmpty> {
 
 impl<M> Server<M>
 where
-    M: Metrics,
+    M: Metrics + Send + Sync + 'static,
 {
+    /// Returns a Handle to inspect and manage the Server, once it was started
+    pub fn handle(&self) -> ServerHandle<M> {
+        ServerHandle::new(self.ports.clone(), self.events.clone())
+    }
+
+    /// Subscribes to the Events of the Server, like Clients and Users
+    /// connecting or disconnecting
+    ///
+    /// Only the Events published after subscribing are received and if the
+    /// Receiver falls too far behind, it will miss the oldest Events
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<ServerEvent> {
+        self.events.subscribe()
+    }
+
     /// Actually starts the Server and starts listening for incoming Connections from
     /// both users and clients.
     ///
@@ -61,6 +121,16 @@ where
     pub async fn listen(self) -> Result<(), ()> {
         info!("Starting...");
 
+        #[cfg(feature = "prometheus")]
+        if let Some(endpoint) = self.metrics_endpoint.clone() {
+            tokio::task::spawn(async move {
+                let addr = endpoint.addr();
+                if let Err(e) = endpoint.serve().await {
+                    error!("Serving Metrics on {}: {}", addr, e);
+                }
+            });
+        }
+
         let listen_bind_addr = format!("0.0.0.0:{}", self.listen_port);
         let client_listener = match TcpListener::bind(&listen_bind_addr).await {
             Ok(l) => l,
@@ -72,70 +142,423 @@ where
 
         info!("Listening for Clients on: {}", listen_bind_addr);
 
-        let mut ports: BTreeMap<u16, Arc<ClientManager<TCPClient>>> = BTreeMap::new();
-
         // Accept new Clients
         loop {
             // Get Client
-            let mut client_socket = match client_listener.accept().await {
-                Ok((socket, _)) => socket,
+            let (mut client_socket, client_addr) = match client_listener.accept().await {
+                Ok(s) => s,
                 Err(e) => {
                     error!("Accepting client-connection: {}", e);
                     continue;
                 }
             };
 
-            let conf = match handshake::server::perform(&mut client_socket, &self.key, |port| {
-                self.port_strategy.contains_port(port)
-            })
-            .await
-            {
+            let handshake_start = tokio::time::Instant::now();
+            let handshake_result = self.handshake(&mut client_socket).await;
+            let (conf, session, compression) = match handshake_result {
                 Ok(p) => p,
                 Err(e) => {
-                    error!("Validating Client-Connection: {:?}", e);
+                    error!("Validating Client-Connection: {}", e);
+                    self.metrics.handshake_failed();
+                    self.events
+                        .emit(ServerEvent::HandshakeFailed { peer: client_addr });
                     continue;
                 }
             };
+            self.metrics.handshake_succeeded();
+            self.metrics
+                .handshake_latency(conf.port(), handshake_start.elapsed());
 
-            let clients = match ports.get(&conf.port()) {
-                Some(c) => c.clone(),
+            let existing = self.ports.lock().unwrap().get(&conf.port()).cloned();
+            let clients = match existing {
+                Some(c) => c,
                 None => {
                     // Create new Client-List for the Port and start a Forwarder for
                     // the Port as well
-                    let tmp = Arc::new(ClientManager::new());
-                    let fwd = match TCPForwarder::new(conf.port(), tmp.clone()).await {
+                    let balancing = self
+                        .port_balancing
+                        .get(&conf.port())
+                        .unwrap_or(&self.balancing);
+                    let tmp = Arc::new(
+                        ClientManager::with_balancer(balancing.create())
+                            .with_max_connections(self.limits.max_per_client()),
+                    );
+                    let fwd = match TCPForwarder::new(
+                        conf.port(),
+                        tmp.clone(),
+                        self.wait_queue.clone(),
+                        self.metrics.clone(),
+                        self.limits.clone(),
+                    )
+                    .await
+                    {
                         Ok(f) => f,
                         Err(e) => {
                             error!("Binding Forwader: {:?}", e);
                             continue;
                         }
                     };
-                    tokio::task::spawn(fwd.start());
+                    tokio::task::spawn(instrument!(
+                        fwd.start(),
+                        tracing::info_span!("forwarder", port = conf.port())
+                    ));
 
-                    ports.insert(conf.port(), tmp.clone());
+                    self.ports.lock().unwrap().insert(conf.port(), tmp.clone());
                     tmp
                 }
             };
 
+            let (rx, tx) = client_socket.into_split();
+
+            if let Some(session) = session.as_ref().filter(|s| s.resumed()) {
+                self.resume_client(session.token(), client_addr, &conf, &clients, rx, tx);
+                continue;
+            }
+
+            // Further Connections of a Client are added to the existing one
+            let grouped = conf
+                .group()
+                .and_then(|group| self.find_client(conf.port(), |c| c.group() == Some(group)));
+            if let Some(client) = grouped {
+                self.join_client(client, client_addr, rx, tx);
+                continue;
+            }
+
             let c_id: u32 = rand::thread_rng().gen();
 
-            info!("Accepted client: {}", c_id);
+            info!("Accepted client: {} from {}", c_id, client_addr);
 
-            let (rx, tx) = client_socket.into_split();
+            // All the Tasks for the Client-Connection share the same Span
+            #[cfg(feature = "trace")]
+            let span = tracing::info_span!(
+                "client",
+                client_id = c_id,
+                port = conf.port(),
+                peer = %client_addr.ip()
+            );
 
             let (queue_tx, queue_rx) = tokio::sync::mpsc::unbounded_channel();
+            let queue_rx = Arc::new(tokio::sync::Mutex::new(queue_rx));
 
-            let client = TCPClient::new(c_id, clients.clone(), queue_tx);
-
-            tokio::task::spawn(TCPClient::sender(c_id, tx, queue_rx, clients.clone()));
-            tokio::task::spawn(TCPClient::receiver(
+            let pinger = Arc::new(Pinger::new());
+            let mut client = TCPClient::new(
                 c_id,
-                rx,
-                client.get_user_cons(),
+                client_addr,
+                &conf,
                 clients.clone(),
-            ));
+                queue_tx.clone(),
+                self.metrics.clone(),
+                self.events.clone(),
+            )
+            .with_access_log(self.access_log.clone())
+            .with_rate_limits(self.client_rate_limit, self.user_rate_limit)
+            .with_idle_timeout(self.idle_timeout)
+            .with_chunk_size(
+                *self
+                    .port_chunk_size
+                    .get(&conf.port())
+                    .unwrap_or(&self.chunk_size),
+            )
+            .with_compression(compression)
+            .with_max_message_size(self.max_message_size);
+            if let (Some(sessions), Some(session)) = (self.sessions.as_ref(), session) {
+                client = client.with_session(session.token(), sessions.clone(), queue_rx.clone());
+            }
+
+            // The Client needs to be added before starting its Tasks, so that
+            // it can be removed again if any of them fails right away
+            clients.add(client.clone());
+            self.metrics.client_connected(conf.port());
+            self.events.emit(ServerEvent::ClientConnected {
+                client_id: c_id,
+                peer: client_addr,
+                port: conf.port(),
+            });
+
+            client.add_task(tokio::task::spawn(instrument!(
+                client.clone().sender(tx, queue_rx, None),
+                span.clone()
+            )));
+            client.add_task(tokio::task::spawn(instrument!(
+                client
+                    .clone()
+                    .receiver(rx, queue_tx.clone(), pinger.clone()),
+                span.clone()
+            )));
+            // Older Clients dont know how to respond to a Ping
+            if conf.protocol_version() >= 2 {
+                client.add_task(tokio::task::spawn(instrument!(
+                    client.clone().pinger(queue_tx, pinger),
+                    span
+                )));
+            }
+        }
+    }
 
-            clients.add(client);
+    /// Performs the Handshake with a new Client-Connection
+    ///
+    /// A Client, whose Session is resumed while its old Connection still
+    /// seems to be open, is only suspended once the Handshake succeeded, so
+    /// that a failed Attempt does not disconnect it
+    async fn handshake<C>(
+        &self,
+        con: &mut C,
+    ) -> Result<
+        (
+            handshake::Config,
+            Option<handshake::Session>,
+            Option<Compression>,
+        ),
+        handshake::HandshakeError,
+    >
+    where
+        C: ConnectionReader + ConnectionWriter + Send,
+    {
+        let (conf, session, compression) = handshake::server::perform(
+            con,
+            &self.key,
+            |port| self.port_strategy.contains_port(port),
+            |conf| self.grant_session(conf),
+            &self.compression,
+        )
+        .await?;
+
+        // The old Connection of a Client might not be detected as lost yet,
+        // when it reconnects
+        if let Some(session) = session.as_ref().filter(|s| s.resumed()) {
+            if let Some(active) = self.session_client(conf.port(), session.token()) {
+                active.reconnected();
+            }
+        }
+
+        Ok((conf, session, compression))
+    }
+
+    /// Determines the Session for the Client-Connection with the given
+    /// Config, without suspending or resuming anything yet
+    fn grant_session(&self, conf: &handshake::Config) -> Option<handshake::Session> {
+        let sessions = self.sessions.as_ref()?;
+        // Sessions are only granted to Clients using a single Connection
+        if conf.group().is_some() {
+            return None;
         }
+
+        let resumable = conf.session().filter(|token| {
+            sessions.contains(*token, conf.port())
+                || self.session_client(conf.port(), *token).is_some()
+        });
+        let session = match resumable {
+            Some(token) => handshake::Session::new(token, true, sessions.grace()),
+            None => handshake::Session::new(Sessions::<M>::token(), false, sessions.grace()),
+        };
+        Some(session)
+    }
+
+    /// Finds the connected Client for the Port, that matches the Predicate
+    fn find_client<P>(&self, port: u16, predicate: P) -> Option<TCPClient<M>>
+    where
+        P: Fn(&TCPClient<M>) -> bool,
+    {
+        let clients = self.ports.lock().unwrap().get(&port).cloned()?;
+        clients.all().into_iter().find(predicate)
+    }
+
+    /// Finds the connected Client for the Port, that has the Session with the
+    /// given Token
+    fn session_client(&self, port: u16, token: u128) -> Option<TCPClient<M>> {
+        self.find_client(port, |c| c.session_token() == Some(token) && c.resumable())
+    }
+
+    /// Adds another Client-Connection to a Client, which uses multiple
+    /// Connections
+    ///
+    /// The Client stays a single Client, but its User-Connections are spread
+    /// across all of its Connections and losing any of them disconnects the
+    /// Client as a whole
+    fn join_client(
+        &self,
+        client: TCPClient<M>,
+        peer: std::net::SocketAddr,
+        rx: tokio::net::tcp::OwnedReadHalf,
+        tx: tokio::net::tcp::OwnedWriteHalf,
+    ) {
+        let c_id = client.get_id();
+        info!("Added Connection to client: {} from {}", c_id, peer);
+
+        #[cfg(feature = "trace")]
+        let span = tracing::info_span!(
+            "client",
+            client_id = c_id,
+            port = client.port(),
+            peer = %peer.ip()
+        );
+
+        let (queue_tx, queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        client.add_connection(queue_tx.clone());
+
+        let pinger = Arc::new(Pinger::new());
+        client.add_task(tokio::task::spawn(instrument!(
+            client
+                .clone()
+                .sender(tx, Arc::new(tokio::sync::Mutex::new(queue_rx)), None),
+            span.clone()
+        )));
+        client.add_task(tokio::task::spawn(instrument!(
+            client
+                .clone()
+                .receiver(rx, queue_tx.clone(), pinger.clone()),
+            span.clone()
+        )));
+        client.add_task(tokio::task::spawn(instrument!(
+            client.clone().pinger(queue_tx, pinger),
+            span
+        )));
+    }
+
+    /// Resumes the suspended Session of a Client on its new Client-Connection
+    ///
+    /// The Connection is simply closed again, if the Session expired in the
+    /// mean time, after which the Client has to start a new Session
+    fn resume_client(
+        &self,
+        token: u128,
+        peer: std::net::SocketAddr,
+        conf: &handshake::Config,
+        clients: &Arc<ClientManager<TCPClient<M>>>,
+        rx: tokio::net::tcp::OwnedReadHalf,
+        tx: tokio::net::tcp::OwnedWriteHalf,
+    ) {
+        let client = match self.sessions.as_ref().and_then(|s| s.resume(token)) {
+            Some(c) => c,
+            None => {
+                error!("Session of Client from {} expired while resuming", peer);
+                return;
+            }
+        };
+        let (queue, resumed) = match client.resume(peer) {
+            Some(r) => r,
+            None => return,
+        };
+
+        let c_id = client.get_id();
+        info!("Resumed client: {} from {}", c_id, peer);
+
+        #[cfg(feature = "trace")]
+        let span = tracing::info_span!(
+            "client",
+            client_id = c_id,
+            port = conf.port(),
+            peer = %peer.ip()
+        );
+
+        clients.add(client.clone());
+        self.metrics.client_connected(conf.port());
+        self.events.emit(ServerEvent::ClientResumed {
+            client_id: c_id,
+            peer,
+            port: conf.port(),
+        });
+
+        let pinger = Arc::new(Pinger::new());
+        client.add_task(tokio::task::spawn(instrument!(
+            client.clone().sender(tx, queue, Some(resumed)),
+            span.clone()
+        )));
+        client.add_task(tokio::task::spawn(instrument!(
+            client
+                .clone()
+                .receiver(rx, client.send_queue(), pinger.clone()),
+            span.clone()
+        )));
+        client.add_task(tokio::task::spawn(instrument!(
+            client.clone().pinger(client.send_queue(), pinger),
+            span
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use async_trait::async_trait;
+    use std::time::Duration;
+
+    use crate::{metrics::Empty, server::events::Events};
+
+    /// A Connection, that fails every Write after the given Number of them
+    struct FailingWrites {
+        con: tokio::net::TcpStream,
+        remaining: usize,
+    }
+
+    #[async_trait]
+    impl ConnectionReader for FailingWrites {
+        async fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            ConnectionReader::read(&mut self.con, buf).await
+        }
+        async fn read_full(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            ConnectionReader::read_full(&mut self.con, buf).await
+        }
+    }
+
+    #[async_trait]
+    impl ConnectionWriter for FailingWrites {
+        async fn write_full(&mut self, buf: &[u8]) -> std::io::Result<()> {
+            if self.remaining == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+            }
+            self.remaining -= 1;
+            self.con.write_full(buf).await
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_resume_keeps_active_client() {
+        let server = builder()
+            .listen_port(0)
+            .port_strategy(Strategy::Single(13))
+            .key(b"test".to_vec())
+            .empty_metrics()
+            .session_grace(Duration::from_secs(10))
+            .build();
+        let sessions = server.sessions.clone().unwrap();
+
+        // The Client, whose Session the new Connection attempts to resume
+        let token = 7;
+        let clients = Arc::new(ClientManager::new());
+        let (queue_tx, queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        let active = TCPClient::new(
+            123,
+            "127.0.0.1:12345".parse().unwrap(),
+            &handshake::Config::new(13),
+            clients.clone(),
+            queue_tx,
+            Arc::new(Empty::new()),
+            Events::default(),
+        )
+        .with_session(
+            token,
+            sessions.clone(),
+            Arc::new(tokio::sync::Mutex::new(queue_rx)),
+        );
+        clients.add(active);
+        server.ports.lock().unwrap().insert(13, clients.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut con = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let config = handshake::Config::new(13).with_session(token);
+            let _ = handshake::client::perform(&mut con, b"test", config).await;
+        });
+        let (con, _) = listener.accept().await.unwrap();
+
+        // Only the Key and the first Acknowledge are written successfully
+        let mut con = FailingWrites { con, remaining: 4 };
+        assert_eq!(true, server.handshake(&mut con).await.is_err());
+
+        assert_eq!(1, clients.all().len());
+        assert_eq!(false, sessions.contains(token, 13));
     }
 }
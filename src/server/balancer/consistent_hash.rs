@@ -0,0 +1,98 @@
+use std::net::IpAddr;
+
+use super::{Balancer, ClientInfo};
+
+/// Always selects the same Client for the same User-IP, as long as that
+/// Client is still connected, which allows for Session-Affinity.
+///
+/// This uses Rendezvous-Hashing so that only the Users of a Client that
+/// disconnected are moved to another Client, while all the other Users stay
+/// with their current one
+#[derive(Debug, Default)]
+pub struct ConsistentHash {}
+
+impl ConsistentHash {
+    /// Creates a new ConsistentHash Balancer
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Scores the Client for the User using FNV-1a, which unlike the
+    /// `DefaultHasher` of the Standard-Library produces the same Scores
+    /// regardless of the Rust-Version the Server was built with
+    fn score(client: &ClientInfo, user: &IpAddr) -> u64 {
+        const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        let user = match user {
+            IpAddr::V4(ip) => ip.to_ipv6_mapped().octets(),
+            IpAddr::V6(ip) => ip.octets(),
+        };
+        user.iter()
+            .chain(client.id.to_be_bytes().iter())
+            .fold(OFFSET, |hash, byte| {
+                (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+            })
+    }
+}
+
+impl Balancer for ConsistentHash {
+    fn select(&self, clients: &[ClientInfo], user: &IpAddr) -> Option<usize> {
+        clients
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, c)| Self::score(c, user))
+            .map(|(index, _)| index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    fn client(id: u32) -> ClientInfo {
+        ClientInfo {
+            id,
+            weight: 1,
+            active_connections: 0,
+        }
+    }
+
+    #[test]
+    fn same_user_same_client() {
+        let balancer = ConsistentHash::new();
+        let clients = vec![client(1), client(2), client(3), client(4)];
+        let user = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 13));
+
+        let first = balancer.select(&clients, &user);
+        assert_eq!(true, first.is_some());
+        for _ in 0..10 {
+            assert_eq!(first, balancer.select(&clients, &user));
+        }
+    }
+
+    #[test]
+    fn stays_on_client_when_other_leaves() {
+        let balancer = ConsistentHash::new();
+        let mut clients = vec![client(1), client(2), client(3), client(4)];
+        let user = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 13));
+
+        let selected_id = clients[balancer.select(&clients, &user).unwrap()].id;
+
+        clients.retain(|c| c.id == selected_id || c.id == 1 || c.id == 4);
+        let new_id = clients[balancer.select(&clients, &user).unwrap()].id;
+        assert_eq!(selected_id, new_id);
+    }
+
+    #[test]
+    fn stable_score() {
+        let user = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 13));
+
+        assert_eq!(
+            1990293717092052453,
+            ConsistentHash::score(&client(1), &user)
+        );
+    }
+}
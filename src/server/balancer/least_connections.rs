@@ -0,0 +1,57 @@
+use std::net::IpAddr;
+
+use super::{Balancer, ClientInfo};
+
+/// Selects the Client that currently handles the fewest User-Connections
+#[derive(Debug, Default)]
+pub struct LeastConnections {}
+
+impl LeastConnections {
+    /// Creates a new LeastConnections Balancer
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Balancer for LeastConnections {
+    fn select(&self, clients: &[ClientInfo], _user: &IpAddr) -> Option<usize> {
+        clients
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, c)| c.active_connections)
+            .map(|(index, _)| index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    fn client(id: u32, active_connections: usize) -> ClientInfo {
+        ClientInfo {
+            id,
+            weight: 1,
+            active_connections,
+        }
+    }
+
+    #[test]
+    fn selects_least() {
+        let balancer = LeastConnections::new();
+        let clients = vec![client(1, 5), client(2, 2), client(3, 7)];
+        let user = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        assert_eq!(Some(1), balancer.select(&clients, &user));
+    }
+
+    #[test]
+    fn tie_selects_first() {
+        let balancer = LeastConnections::new();
+        let clients = vec![client(1, 2), client(2, 2)];
+        let user = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        assert_eq!(Some(0), balancer.select(&clients, &user));
+    }
+}
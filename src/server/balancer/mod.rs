@@ -0,0 +1,64 @@
+//! The Balancers decide which of the Clients, registered for a single Port,
+//! should handle a new User-Connection
+
+use std::fmt::Debug;
+
+mod traits;
+pub use traits::*;
+
+mod round_robin;
+pub use round_robin::RoundRobin;
+mod least_connections;
+pub use least_connections::LeastConnections;
+mod weighted;
+pub use weighted::Weighted;
+mod two_choices;
+pub use two_choices::RandomTwoChoices;
+mod consistent_hash;
+pub use consistent_hash::ConsistentHash;
+
+/// The Balancing-Strategy that should be used for the Clients of a Port
+#[derive(Default)]
+pub enum Balancing {
+    /// Uses the [`RoundRobin`] Balancer
+    #[default]
+    RoundRobin,
+    /// Uses the [`LeastConnections`] Balancer
+    LeastConnections,
+    /// Uses the [`Weighted`] Balancer
+    Weighted,
+    /// Uses the [`RandomTwoChoices`] Balancer
+    RandomTwoChoices,
+    /// Uses the [`ConsistentHash`] Balancer
+    ConsistentHash,
+    /// This allows you to use whatever Balancer you need, the Function is
+    /// called once for every Port to create its Balancer
+    Custom(Box<dyn Send + Sync + Fn() -> Box<dyn Balancer>>),
+}
+
+impl Debug for Balancing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RoundRobin => f.debug_tuple("RoundRobin").finish(),
+            Self::LeastConnections => f.debug_tuple("LeastConnections").finish(),
+            Self::Weighted => f.debug_tuple("Weighted").finish(),
+            Self::RandomTwoChoices => f.debug_tuple("RandomTwoChoices").finish(),
+            Self::ConsistentHash => f.debug_tuple("ConsistentHash").finish(),
+            Self::Custom(_) => f.debug_tuple("Custom").finish(),
+        }
+    }
+}
+
+impl Balancing {
+    /// Creates a new Balancer for this Strategy
+    pub fn create(&self) -> Box<dyn Balancer> {
+        match self {
+            Self::RoundRobin => Box::new(RoundRobin::new()),
+            Self::LeastConnections => Box::new(LeastConnections::new()),
+            Self::Weighted => Box::new(Weighted::new()),
+            Self::RandomTwoChoices => Box::new(RandomTwoChoices::new()),
+            Self::ConsistentHash => Box::new(ConsistentHash::new()),
+            Self::Custom(func) => func(),
+        }
+    }
+}
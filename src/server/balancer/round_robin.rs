@@ -0,0 +1,67 @@
+use std::{
+    net::IpAddr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::{Balancer, ClientInfo};
+
+/// Simply cycles through all the Clients one after another
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    index: AtomicUsize,
+}
+
+impl RoundRobin {
+    /// Creates a new RoundRobin Balancer
+    pub fn new() -> Self {
+        Self {
+            index: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Balancer for RoundRobin {
+    fn select(&self, clients: &[ClientInfo], _user: &IpAddr) -> Option<usize> {
+        if clients.is_empty() {
+            return None;
+        }
+
+        let raw_index = self.index.fetch_add(1, Ordering::Relaxed);
+        Some(raw_index % clients.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    fn client(id: u32) -> ClientInfo {
+        ClientInfo {
+            id,
+            weight: 1,
+            active_connections: 0,
+        }
+    }
+
+    #[test]
+    fn cycles() {
+        let balancer = RoundRobin::new();
+        let clients = vec![client(1), client(2), client(3)];
+        let user = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        assert_eq!(Some(0), balancer.select(&clients, &user));
+        assert_eq!(Some(1), balancer.select(&clients, &user));
+        assert_eq!(Some(2), balancer.select(&clients, &user));
+        assert_eq!(Some(0), balancer.select(&clients, &user));
+    }
+
+    #[test]
+    fn empty() {
+        let balancer = RoundRobin::new();
+        let user = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        assert_eq!(None, balancer.select(&[], &user));
+    }
+}
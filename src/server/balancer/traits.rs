@@ -0,0 +1,28 @@
+use std::net::IpAddr;
+
+/// The Information about a single Client, that a Balancer can use to make
+/// its decision
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientInfo {
+    /// The ID of the Client
+    pub id: u32,
+    /// The Weight the Client reported during the Handshake, which describes
+    /// its capacity in relation to the other Clients
+    pub weight: u16,
+    /// The Number of User-Connections currently handled by the Client
+    pub active_connections: usize,
+}
+
+/// The Interface every Balancer needs to implement
+pub trait Balancer: Send + Sync {
+    /// Selects the Client that should handle a new User-Connection
+    ///
+    /// # Params:
+    /// * `clients`: All the Clients that are currently available, this is never empty
+    /// * `user`: The IP-Address of the User that opened the Connection
+    ///
+    /// # Returns
+    /// The Index of the selected Client in `clients` or None if no Client
+    /// should be used for the Connection
+    fn select(&self, clients: &[ClientInfo], user: &IpAddr) -> Option<usize>;
+}
@@ -0,0 +1,84 @@
+use std::net::IpAddr;
+
+use rand::Rng;
+
+use super::{Balancer, ClientInfo};
+
+/// Picks two random Clients and then selects the one with fewer
+/// User-Connections, which avoids having to look at every Client while
+/// still spreading the load quite evenly
+#[derive(Debug, Default)]
+pub struct RandomTwoChoices {}
+
+impl RandomTwoChoices {
+    /// Creates a new RandomTwoChoices Balancer
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Balancer for RandomTwoChoices {
+    fn select(&self, clients: &[ClientInfo], _user: &IpAddr) -> Option<usize> {
+        match clients.len() {
+            0 => None,
+            1 => Some(0),
+            count => {
+                let mut rng = rand::thread_rng();
+                let first = rng.gen_range(0, count);
+                // Makes sure that the second one is different from the first one
+                let second = (first + rng.gen_range(1, count)) % count;
+
+                if clients[second].active_connections < clients[first].active_connections {
+                    Some(second)
+                } else {
+                    Some(first)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    fn client(id: u32, active_connections: usize) -> ClientInfo {
+        ClientInfo {
+            id,
+            weight: 1,
+            active_connections,
+        }
+    }
+
+    #[test]
+    fn two_clients_selects_least() {
+        let balancer = RandomTwoChoices::new();
+        let clients = vec![client(1, 10), client(2, 3)];
+        let user = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        for _ in 0..10 {
+            assert_eq!(Some(1), balancer.select(&clients, &user));
+        }
+    }
+
+    #[test]
+    fn never_selects_busiest() {
+        let balancer = RandomTwoChoices::new();
+        let clients = vec![client(1, 1), client(2, 100), client(3, 2)];
+        let user = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        for _ in 0..20 {
+            assert_ne!(Some(1), balancer.select(&clients, &user));
+        }
+    }
+
+    #[test]
+    fn single() {
+        let balancer = RandomTwoChoices::new();
+        let user = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        assert_eq!(Some(0), balancer.select(&[client(1, 5)], &user));
+    }
+}
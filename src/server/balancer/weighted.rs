@@ -0,0 +1,89 @@
+use std::{
+    net::IpAddr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use super::{Balancer, ClientInfo};
+
+/// Cycles through the Clients like [`RoundRobin`](super::RoundRobin), but
+/// every Client receives as many Connections in a row as its Weight.
+///
+/// A Weight of 0 is treated like a Weight of 1
+#[derive(Debug, Default)]
+pub struct Weighted {
+    index: AtomicU64,
+}
+
+impl Weighted {
+    /// Creates a new Weighted Balancer
+    pub fn new() -> Self {
+        Self {
+            index: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Balancer for Weighted {
+    fn select(&self, clients: &[ClientInfo], _user: &IpAddr) -> Option<usize> {
+        let total: u64 = clients.iter().map(|c| c.weight.max(1) as u64).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut target = self.index.fetch_add(1, Ordering::Relaxed) % total;
+        for (index, client) in clients.iter().enumerate() {
+            let weight = client.weight.max(1) as u64;
+            if target < weight {
+                return Some(index);
+            }
+            target -= weight;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    fn client(id: u32, weight: u16) -> ClientInfo {
+        ClientInfo {
+            id,
+            weight,
+            active_connections: 0,
+        }
+    }
+
+    #[test]
+    fn respects_weights() {
+        let balancer = Weighted::new();
+        let clients = vec![client(1, 2), client(2, 1)];
+        let user = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        assert_eq!(Some(0), balancer.select(&clients, &user));
+        assert_eq!(Some(0), balancer.select(&clients, &user));
+        assert_eq!(Some(1), balancer.select(&clients, &user));
+        assert_eq!(Some(0), balancer.select(&clients, &user));
+    }
+
+    #[test]
+    fn zero_weight() {
+        let balancer = Weighted::new();
+        let clients = vec![client(1, 0), client(2, 0)];
+        let user = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        assert_eq!(Some(0), balancer.select(&clients, &user));
+        assert_eq!(Some(1), balancer.select(&clients, &user));
+    }
+
+    #[test]
+    fn empty() {
+        let balancer = Weighted::new();
+        let user = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        assert_eq!(None, balancer.select(&[], &user));
+    }
+}
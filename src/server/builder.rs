@@ -1,6 +1,12 @@
-use crate::metrics;
+use std::collections::BTreeMap;
 
-use super::{Server, Strategy};
+use crate::{
+    accesslog::AccessLog,
+    message::{Compression, MessageCodec},
+    metrics, ChunkSize, RateLimit,
+};
+
+use super::{events::Events, Balancing, Limits, Server, ServerEvent, Strategy, WaitQueue};
 
 pub struct BuilderEmpty;
 pub struct BuilderListenPort {
@@ -17,6 +23,23 @@ pub struct BuilderKey {
 pub struct BuilderMetrics<M> {
     prev: BuilderKey,
     metrics: M,
+    balancing: Balancing,
+    port_balancing: BTreeMap<u16, Balancing>,
+    wait_queue: Option<WaitQueue>,
+    events: Events,
+    access_log: Option<AccessLog>,
+    limits: Limits,
+    client_rate_limit: Option<RateLimit>,
+    user_rate_limit: Option<RateLimit>,
+    idle_timeout: Option<std::time::Duration>,
+    chunk_size: ChunkSize,
+    port_chunk_size: BTreeMap<u16, ChunkSize>,
+    compression: Vec<Compression>,
+    max_message_size: usize,
+    session_grace: Option<std::time::Duration>,
+    session_buffer: usize,
+    #[cfg(feature = "prometheus")]
+    metrics_endpoint: Option<metrics::PrometheusEndpoint>,
 }
 
 /// The Builder used for creating a new Instance of the Server
@@ -83,6 +106,23 @@ impl ServerBuilder<BuilderKey> {
             state: BuilderMetrics {
                 prev: self.state,
                 metrics,
+                balancing: Balancing::default(),
+                port_balancing: BTreeMap::new(),
+                wait_queue: None,
+                events: Events::default(),
+                access_log: None,
+                limits: Limits::default(),
+                client_rate_limit: None,
+                user_rate_limit: None,
+                idle_timeout: None,
+                chunk_size: ChunkSize::default(),
+                port_chunk_size: BTreeMap::new(),
+                compression: Vec::new(),
+                max_message_size: MessageCodec::DEFAULT_MAX_SIZE,
+                session_grace: None,
+                session_buffer: crate::general::DEFAULT_MAX_BUFFERED,
+                #[cfg(feature = "prometheus")]
+                metrics_endpoint: None,
             },
         }
     }
@@ -91,16 +131,201 @@ impl ServerBuilder<BuilderKey> {
     pub fn empty_metrics(self) -> ServerBuilder<BuilderMetrics<metrics::Empty>> {
         self.metrics(metrics::Empty::new())
     }
+
+    /// Sets the Metrics to the builtin Prometheus Metrics Collector
+    #[cfg(feature = "prometheus")]
+    pub fn prometheus_metrics(self) -> ServerBuilder<BuilderMetrics<metrics::Prometheus>> {
+        self.metrics(metrics::Prometheus::new())
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl ServerBuilder<BuilderMetrics<metrics::Prometheus>> {
+    /// Serves the Metrics over HTTP on the given Address, under `/metrics`
+    pub fn metrics_endpoint(mut self, addr: std::net::SocketAddr) -> Self {
+        self.state.metrics_endpoint = Some(metrics::PrometheusEndpoint::new(
+            addr,
+            self.state.metrics.clone(),
+        ));
+        self
+    }
 }
 
-impl<M> ServerBuilder<BuilderMetrics<M>> {
+impl<M> ServerBuilder<BuilderMetrics<M>>
+where
+    M: metrics::Metrics,
+{
+    /// Sets the Balancing-Strategy used for all the Ports that dont have their
+    /// own Strategy configured
+    ///
+    /// Defaults to [`Balancing::RoundRobin`]
+    pub fn balancing(mut self, balancing: Balancing) -> Self {
+        self.state.balancing = balancing;
+        self
+    }
+
+    /// Sets the Balancing-Strategy for the given User-Port
+    pub fn port_balancing(mut self, port: u16, balancing: Balancing) -> Self {
+        self.state.port_balancing.insert(port, balancing);
+        self
+    }
+
+    /// Enables the Wait-Queue on every Port
+    ///
+    /// While there is no Client available for a Port, new User-Connections are held in this
+    /// Queue until a Client connects, instead of being closed right away
+    pub fn wait_queue(mut self, queue: WaitQueue) -> Self {
+        self.state.wait_queue = Some(queue);
+        self
+    }
+
+    /// Publishes all the Events of the Server on the given Sender, instead of
+    /// a Channel created by the Server itself
+    ///
+    /// This can be used to configure the Capacity of the Channel or to receive
+    /// the Events without obtaining a Receiver from the Server
+    pub fn events(mut self, sender: tokio::sync::broadcast::Sender<ServerEvent>) -> Self {
+        self.state.events = Events::new(sender);
+        self
+    }
+
+    /// Writes an Access-Record for every User-Connection, once it is closed,
+    /// to the given Log
+    pub fn access_log(mut self, log: AccessLog) -> Self {
+        self.state.access_log = Some(log);
+        self
+    }
+
+    /// Sets the Limits for the Number of concurrent User-Connections
+    ///
+    /// User-Connections that would exceed any of the Limits are reset right away
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.state.limits = limits;
+        self
+    }
+
+    /// Limits the Bandwidth of every Client-Connection, which is shared by
+    /// all the User-Connections of the Client
+    ///
+    /// Clients can request a stricter Limit for themselves in the Handshake.
+    /// A Limit of 0 Bytes per Second disables it
+    pub fn client_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.state.client_rate_limit = Some(limit);
+        self
+    }
+
+    /// Limits the Bandwidth of every single User-Connection
+    ///
+    /// A Limit of 0 Bytes per Second disables it
+    pub fn user_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.state.user_rate_limit = Some(limit);
+        self
+    }
+
+    /// Closes User-Connections once no Data was transferred over them, in
+    /// either Direction, for the given Timeout
+    ///
+    /// Both the User and the Client are notified with a Timeout-Reason
+    pub fn idle_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.state.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the Size of the Chunks in which Data is read from the
+    /// User-Connections on every Port, that does not have its own Size
+    /// configured
+    ///
+    /// Every Chunk is forwarded to the Client as a single Message, so larger
+    /// Chunks reduce the Overhead for bulk Transfers. Defaults to a fixed
+    /// Size of 4 KiB
+    pub fn read_chunk_size(mut self, size: ChunkSize) -> Self {
+        self.state.chunk_size = size;
+        self
+    }
+
+    /// Sets the Size of the Chunks in which Data is read from the
+    /// User-Connections on the given Port
+    pub fn port_read_chunk_size(mut self, port: u16, size: ChunkSize) -> Self {
+        self.state.port_chunk_size.insert(port, size);
+        self
+    }
+
+    /// Sets the Compression-Algorithms the Server supports, in the Order in
+    /// which they are preferred
+    ///
+    /// The first of them, that the Client supports as well, is used to
+    /// compress the Data send over its Connection. Only Clients that support
+    /// Protocol-Version 7 use Compression and by default it is disabled
+    pub fn compression(mut self, algorithms: &[Compression]) -> Self {
+        self.state.compression = algorithms.to_vec();
+        self
+    }
+
+    /// Sets the maximum Size of the Data of a single Message exchanged with
+    /// the Clients
+    ///
+    /// Data read from Users is split into Messages of at most this Size. A
+    /// Message that still exceeds it is not send and only closes its
+    /// User-Connection with an Error, while receiving such a Message fails
+    /// the Connection to the Client. Defaults to 16 MiB
+    pub fn max_message_size(mut self, bytes: usize) -> Self {
+        self.state.max_message_size = bytes;
+        self
+    }
+
+    /// Grants every Client a Session, which keeps its User-Connections open
+    /// for the given Grace-Period after losing the Connection to the Client
+    ///
+    /// If the Client reconnects within that Period, it resumes its Session
+    /// and all the Data that was lost with the old Connection is send again.
+    /// Only Clients that support Protocol-Version 5 use Sessions and Clients
+    /// that open multiple Connections (`ClientBuilder::connections`) never
+    /// get one
+    pub fn session_grace(mut self, grace: std::time::Duration) -> Self {
+        self.state.session_grace = Some(grace);
+        self
+    }
+
+    /// Sets the maximum Number of Bytes that are kept for every Session, to
+    /// send them again after the Client resumed it
+    ///
+    /// Every Message send to the Client is kept until the Client acknowledged
+    /// it. Once the kept Messages would exceed this Limit, they are dropped
+    /// and the Session fails, so the User-Connections are closed when the
+    /// Connection to the Client is lost instead of being resumed. Defaults to
+    /// 16 MiB
+    pub fn session_buffer(mut self, bytes: usize) -> Self {
+        self.state.session_buffer = bytes;
+        self
+    }
+
     /// Actually creates the Server based on the Configuration
     pub fn build(self) -> Server<M> {
+        let session_buffer = self.state.session_buffer;
         Server {
             listen_port: self.state.prev.prev.prev.port,
             port_strategy: self.state.prev.prev.strategy,
             key: self.state.prev.key,
             metrics: std::sync::Arc::new(self.state.metrics),
+            balancing: self.state.balancing,
+            port_balancing: self.state.port_balancing,
+            wait_queue: self.state.wait_queue,
+            ports: std::sync::Arc::new(std::sync::Mutex::new(BTreeMap::new())),
+            events: self.state.events,
+            access_log: self.state.access_log,
+            limits: self.state.limits,
+            client_rate_limit: self.state.client_rate_limit,
+            user_rate_limit: self.state.user_rate_limit,
+            idle_timeout: self.state.idle_timeout,
+            chunk_size: self.state.chunk_size,
+            port_chunk_size: self.state.port_chunk_size,
+            compression: self.state.compression,
+            max_message_size: self.state.max_message_size,
+            sessions: self.state.session_grace.map(|grace| {
+                std::sync::Arc::new(super::Sessions::new(grace).with_max_buffered(session_buffer))
+            }),
+            #[cfg(feature = "prometheus")]
+            metrics_endpoint: self.state.metrics_endpoint,
         }
     }
 }
@@ -1,12 +1,42 @@
-#[derive(Debug)]
+use std::net::IpAddr;
+
+use super::balancer::{Balancer, ClientInfo, RoundRobin};
+
 pub struct ClientManager<C> {
-    index: std::sync::atomic::AtomicU64,
     client_count: std::sync::atomic::AtomicU64,
     clients: std::sync::Mutex<Vec<C>>,
+    balancer: Box<dyn Balancer>,
+    /// The maximum Number of User-Connections a single Client handles
+    max_connections: Option<usize>,
+    /// Used to notify everyone waiting for a Client once one might be
+    /// available, because it was added or finished a User-Connection
+    available: tokio::sync::Notify,
+}
+
+impl<C> std::fmt::Debug for ClientManager<C>
+where
+    C: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientManager")
+            .field("client_count", &self.client_count)
+            .field("clients", &self.clients)
+            .finish()
+    }
 }
 
 pub trait Client {
     fn id(&self) -> u32;
+
+    /// The Weight of the Client in relation to the other Clients
+    fn weight(&self) -> u16 {
+        1
+    }
+
+    /// The Number of User-Connections the Client is currently handling
+    fn active_connections(&self) -> usize {
+        0
+    }
 }
 
 impl<C> Default for ClientManager<C> {
@@ -16,36 +46,102 @@ impl<C> Default for ClientManager<C> {
 }
 
 impl<C> ClientManager<C> {
-    /// Creates a new empty Client-Manager
+    /// Creates a new empty Client-Manager, that uses the RoundRobin Balancer
     pub fn new() -> Self {
+        Self::with_balancer(Box::new(RoundRobin::new()))
+    }
+
+    /// Creates a new empty Client-Manager, that uses the given Balancer to
+    /// select the Client for new User-Connections
+    pub fn with_balancer(balancer: Box<dyn Balancer>) -> Self {
         ClientManager {
-            index: std::sync::atomic::AtomicU64::new(0),
             client_count: std::sync::atomic::AtomicU64::new(0),
             clients: std::sync::Mutex::new(Vec::new()),
+            balancer,
+            max_connections: None,
+            available: tokio::sync::Notify::new(),
         }
     }
+
+    /// Limits the Number of User-Connections a single Client handles, Clients
+    /// that reached the Limit are not selected for new Connections
+    pub fn with_max_connections(mut self, max: Option<usize>) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    /// Whether or not there are currently no Clients
+    pub fn is_empty(&self) -> bool {
+        self.client_count.load(std::sync::atomic::Ordering::SeqCst) == 0
+    }
 }
 
 impl<C> ClientManager<C>
 where
     C: Clone + Client,
 {
-    /// Returns a random Client from all currently connected
-    /// Clients
-    pub fn get(&self) -> Option<C> {
+    /// Returns the Client, selected by the Balancer, that should handle
+    /// a new Connection from the given User
+    pub fn get(&self, user: &IpAddr) -> Option<C> {
+        self.get_except(user, &[])
+    }
+
+    /// Returns the Client, selected by the Balancer, that should handle
+    /// a new Connection from the given User, but never one of the Clients
+    /// whose ID is in `except`
+    pub fn get_except(&self, user: &IpAddr, except: &[u32]) -> Option<C> {
         let clients_data = self.clients.lock().unwrap();
-        if clients_data.is_empty() {
+
+        let candidates: Vec<&C> = clients_data
+            .iter()
+            .filter(|c| !except.contains(&c.id()))
+            .filter(|c| match self.max_connections {
+                Some(max) => c.active_connections() < max,
+                None => true,
+            })
+            .collect();
+        if candidates.is_empty() {
             return None;
         }
 
-        let raw_index = self.index.load(std::sync::atomic::Ordering::Relaxed);
-        let client_count = clients_data.len() as u64;
+        let infos: Vec<ClientInfo> = candidates
+            .iter()
+            .map(|c| ClientInfo {
+                id: c.id(),
+                weight: c.weight(),
+                active_connections: c.active_connections(),
+            })
+            .collect();
+
+        let index = self.balancer.select(&infos, user)?;
+        let client = candidates.get(index)?;
+        Some((*client).clone())
+    }
 
-        let index = raw_index % client_count;
-        let client = clients_data.get(index as usize)?;
-        self.index
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        Some(client.clone())
+    /// Returns a Snapshot of all the Clients currently managed
+    pub fn all(&self) -> Vec<C> {
+        self.clients.lock().unwrap().clone()
+    }
+
+    /// Returns the Client that should handle a new Connection from the given
+    /// User, like `get`, but if there is currently no Client available it
+    /// waits for up to `timeout` for a new Client to be added or for a Client
+    /// to drop below the maximum Number of Connections
+    pub async fn wait_for(&self, user: &IpAddr, timeout: std::time::Duration) -> Option<C> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // This needs to be created before checking for a Client, to not miss
+            // a Client that becomes available in between
+            let available = self.available.notified();
+
+            if let Some(client) = self.get(user) {
+                return Some(client);
+            }
+
+            if tokio::time::timeout_at(deadline, available).await.is_err() {
+                return None;
+            }
+        }
     }
 
     /// Adds a new client connection to the List of connections
@@ -62,6 +158,13 @@ where
         drop(clients_data);
         self.client_count
             .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.available.notify_waiters();
+    }
+
+    /// Notifies everyone waiting for a Client, that one of the Clients
+    /// finished a User-Connection and might therefore be selected again
+    pub fn released(&self) {
+        self.available.notify_waiters();
     }
 
     /// This is used to remove a client connection again
@@ -70,9 +173,9 @@ where
     /// * id: The ID of the Client-Connection to remove
     ///
     /// Returns:
-    /// This function returns the new number of clients managed
-    /// by this
-    pub fn remove(&self, id: u32) {
+    /// Whether or not the Client was actually removed, which is false if
+    /// it had already been removed before
+    pub fn remove(&self, id: u32) -> bool {
         let mut client_data = self.clients.lock().unwrap();
         let mut remove_index: Option<usize> = None;
         for (index, client) in client_data.iter().enumerate() {
@@ -83,15 +186,14 @@ where
         }
 
         match remove_index {
-            None => {}
+            None => false,
             Some(i) => {
                 client_data.remove(i);
                 self.client_count
                     .fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+                true
             }
-        };
-
-        drop(client_data);
+        }
     }
 
     #[cfg(test)]
@@ -105,15 +207,23 @@ where
 mod tests {
     use super::*;
 
+    use crate::server::balancer::LeastConnections;
+    use std::net::Ipv4Addr;
+
     #[derive(Debug, Clone)]
     struct TestClient {
         id: u32,
+        active: usize,
     }
 
     impl Client for TestClient {
         fn id(&self) -> u32 {
             self.id
         }
+
+        fn active_connections(&self) -> usize {
+            self.active
+        }
     }
 
     #[test]
@@ -128,7 +238,7 @@ mod tests {
         let manager = std::sync::Arc::new(ClientManager::<TestClient>::new());
         assert_eq!(0, manager.client_count());
 
-        manager.add(TestClient { id: 13 });
+        manager.add(TestClient { id: 13, active: 0 });
         assert_eq!(1, manager.client_count());
     }
 
@@ -137,11 +247,13 @@ mod tests {
         let manager = std::sync::Arc::new(ClientManager::new());
         assert_eq!(0, manager.client_count());
 
-        manager.add(TestClient { id: 123 });
+        manager.add(TestClient { id: 123, active: 0 });
         assert_eq!(1, manager.client_count());
 
-        manager.remove(123);
+        assert_eq!(true, manager.remove(123));
         assert_eq!(0, manager.client_count());
+
+        assert_eq!(false, manager.remove(123));
     }
 
     #[test]
@@ -149,11 +261,144 @@ mod tests {
         let manager = std::sync::Arc::new(ClientManager::new());
         assert_eq!(0, manager.client_count());
 
-        manager.add(TestClient { id: 123 });
+        manager.add(TestClient { id: 123, active: 0 });
         assert_eq!(1, manager.client_count());
 
-        let tmp_client = manager.get();
+        let tmp_client = manager.get(&IpAddr::V4(Ipv4Addr::LOCALHOST));
         assert_eq!(true, tmp_client.is_some());
         assert_eq!(123, tmp_client.unwrap().id());
     }
+
+    #[test]
+    fn get_client_with_balancer() {
+        let manager = ClientManager::with_balancer(Box::new(LeastConnections::new()));
+
+        manager.add(TestClient { id: 123, active: 3 });
+        manager.add(TestClient { id: 124, active: 1 });
+
+        let tmp_client = manager.get(&IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(true, tmp_client.is_some());
+        assert_eq!(124, tmp_client.unwrap().id());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_for_timeout() {
+        let manager = ClientManager::<TestClient>::new();
+
+        let result = manager
+            .wait_for(
+                &IpAddr::V4(Ipv4Addr::LOCALHOST),
+                std::time::Duration::from_secs(5),
+            )
+            .await;
+        assert_eq!(true, result.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_for_added_client() {
+        let manager = std::sync::Arc::new(ClientManager::<TestClient>::new());
+
+        let waiting = tokio::task::spawn({
+            let manager = manager.clone();
+            async move {
+                manager
+                    .wait_for(
+                        &IpAddr::V4(Ipv4Addr::LOCALHOST),
+                        std::time::Duration::from_secs(5),
+                    )
+                    .await
+            }
+        });
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        manager.add(TestClient { id: 123, active: 0 });
+
+        let result = waiting.await.unwrap();
+        assert_eq!(true, result.is_some());
+        assert_eq!(123, result.unwrap().id());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_for_released_client() {
+        let manager = std::sync::Arc::new(ClientManager::new().with_max_connections(Some(1)));
+        manager.add(TestClient { id: 123, active: 1 });
+
+        let waiting = tokio::task::spawn({
+            let manager = manager.clone();
+            async move {
+                manager
+                    .wait_for(
+                        &IpAddr::V4(Ipv4Addr::LOCALHOST),
+                        std::time::Duration::from_secs(5),
+                    )
+                    .await
+            }
+        });
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        manager.clients.lock().unwrap()[0].active = 0;
+        manager.released();
+
+        let result = waiting.await.unwrap();
+        assert_eq!(true, result.is_some());
+        assert_eq!(123, result.unwrap().id());
+    }
+
+    #[test]
+    fn get_client_except() {
+        let manager = ClientManager::new();
+
+        manager.add(TestClient { id: 123, active: 0 });
+        manager.add(TestClient { id: 124, active: 0 });
+
+        for _ in 0..4 {
+            let tmp_client = manager.get_except(&IpAddr::V4(Ipv4Addr::LOCALHOST), &[123]);
+            assert_eq!(true, tmp_client.is_some());
+            assert_eq!(124, tmp_client.unwrap().id());
+        }
+        assert_eq!(
+            true,
+            manager
+                .get_except(&IpAddr::V4(Ipv4Addr::LOCALHOST), &[123, 124])
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn get_client_max_connections() {
+        let manager = ClientManager::new().with_max_connections(Some(2));
+
+        manager.add(TestClient { id: 123, active: 2 });
+        assert_eq!(
+            true,
+            manager.get(&IpAddr::V4(Ipv4Addr::LOCALHOST)).is_none()
+        );
+        assert_eq!(false, manager.is_empty());
+
+        manager.add(TestClient { id: 124, active: 1 });
+        let tmp_client = manager.get(&IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(124, tmp_client.unwrap().id());
+    }
+
+    #[test]
+    fn all_clients() {
+        let manager = ClientManager::new();
+        assert_eq!(0, manager.all().len());
+
+        manager.add(TestClient { id: 123, active: 0 });
+        manager.add(TestClient { id: 124, active: 0 });
+
+        let ids: Vec<u32> = manager.all().iter().map(|c| c.id()).collect();
+        assert_eq!(vec![123, 124], ids);
+    }
+
+    #[test]
+    fn get_empty() {
+        let manager = ClientManager::<TestClient>::new();
+
+        assert_eq!(
+            true,
+            manager.get(&IpAddr::V4(Ipv4Addr::LOCALHOST)).is_none()
+        );
+    }
 }
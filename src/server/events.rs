@@ -0,0 +1,160 @@
+use std::net::{IpAddr, SocketAddr};
+
+use tokio::sync::broadcast;
+
+use crate::metrics::ConnectionStats;
+
+/// The Number of Events that are buffered for each Subscriber, before the
+/// oldest ones are dropped
+const DEFAULT_CAPACITY: usize = 256;
+
+/// Why a Client was disconnected from the Server
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisconnectReason {
+    /// Receiving a Message from the Client failed
+    ReceiveFailed,
+    /// Sending a Message to the Client failed
+    SendFailed,
+    /// The Client stopped responding to Pings
+    PingFailed,
+    /// The Client was kicked using the [`ServerHandle`](super::ServerHandle)
+    Kicked,
+    /// The Client reconnected to resume its Session, while its old
+    /// Connection was still open
+    Reconnected,
+}
+
+/// An Event in the Lifecycle of the Clients and User-Connections of a Server
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerEvent {
+    /// A new Client connected and completed the Handshake
+    ClientConnected {
+        /// The ID of the Client
+        client_id: u32,
+        /// The Address from which the Client connected
+        peer: SocketAddr,
+        /// The external Port for which the Client receives Connections
+        port: u16,
+    },
+    /// A Client was disconnected
+    ClientDisconnected {
+        /// The ID of the Client
+        client_id: u32,
+        /// The external Port for which the Client received Connections
+        port: u16,
+        /// Why the Client was disconnected
+        reason: DisconnectReason,
+    },
+    /// The Connection to a Client with a Session was lost, its User-Connections
+    /// are kept until it resumes the Session or the Session expires, in which
+    /// case a [`ClientDisconnected`](ServerEvent::ClientDisconnected) follows
+    ClientSuspended {
+        /// The ID of the Client
+        client_id: u32,
+        /// The external Port for which the Client received Connections
+        port: u16,
+        /// Why the Connection to the Client was lost
+        reason: DisconnectReason,
+    },
+    /// A Client reconnected and resumed its suspended Session
+    ClientResumed {
+        /// The ID of the Client
+        client_id: u32,
+        /// The Address from which the Client reconnected
+        peer: SocketAddr,
+        /// The external Port for which the Client receives Connections
+        port: u16,
+    },
+    /// A new User connected on one of the external Ports and was handed to a
+    /// Client, which is always followed by a `UserClosed` once the Connection
+    /// is closed
+    ///
+    /// Users that are rejected or never handed to a Client are not reported
+    UserConnected {
+        /// The external Port on which the User connected
+        port: u16,
+        /// The ID of the User-Connection
+        user_id: u32,
+        /// The IP from which the User connected
+        peer: IpAddr,
+    },
+    /// A User-Connection, that was handled by a Client, was closed
+    UserClosed {
+        /// The external Port on which the User connected
+        port: u16,
+        /// The ID of the Client that handled the Connection
+        client_id: u32,
+        /// The ID of the User-Connection
+        user_id: u32,
+        /// The Data transferred over the Connection and its Duration
+        stats: ConnectionStats,
+    },
+    /// A Client failed to perform the Handshake
+    HandshakeFailed {
+        /// The Address from which the Client connected
+        peer: SocketAddr,
+    },
+}
+
+/// Distributes the Server-Events to all the Subscribers
+#[derive(Debug, Clone)]
+pub(crate) struct Events {
+    tx: broadcast::Sender<ServerEvent>,
+}
+
+impl Events {
+    /// Creates a new Instance that publishes all Events on the given Sender
+    pub fn new(tx: broadcast::Sender<ServerEvent>) -> Self {
+        Self { tx }
+    }
+
+    /// Publishes the Event to all current Subscribers
+    pub fn emit(&self, event: ServerEvent) {
+        // This only fails if there are currently no Subscribers, in which case
+        // the Event can simply be dropped
+        let _ = self.tx.send(event);
+    }
+
+    /// Creates a new Receiver for all Events published after this call
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(DEFAULT_CAPACITY);
+        Self::new(tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn emit_subscribed() {
+        let events = Events::default();
+        let mut rx = events.subscribe();
+
+        events.emit(ServerEvent::HandshakeFailed {
+            peer: "127.0.0.1:1234".parse().unwrap(),
+        });
+
+        assert_eq!(
+            ServerEvent::HandshakeFailed {
+                peer: "127.0.0.1:1234".parse().unwrap(),
+            },
+            rx.recv().await.unwrap()
+        );
+    }
+
+    #[test]
+    fn emit_without_subscribers() {
+        let events = Events::default();
+
+        events.emit(ServerEvent::HandshakeFailed {
+            peer: "127.0.0.1:1234".parse().unwrap(),
+        });
+    }
+}
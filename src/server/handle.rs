@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use super::{clientmanager::ClientManager, events::Events, ServerEvent, TCPClient};
+use crate::message::{CloseCode, CloseReason};
+use crate::metrics::{ConnectionStats, Metrics};
+
+/// All the Ports of a Server together with the Clients for each of them
+pub(crate) type Ports<M> = Arc<Mutex<BTreeMap<u16, Arc<ClientManager<TCPClient<M>>>>>>;
+
+/// The Status of a single Client-Connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientStatus {
+    /// The ID of the Client
+    pub id: u32,
+    /// The Address from which the Client connected
+    pub peer: SocketAddr,
+    /// The external Port for which the Client receives Connections
+    pub port: u16,
+    /// The Number of Connections between the Client and the Server
+    pub connections: usize,
+    /// The User-Connections currently handled by the Client
+    pub users: Vec<UserStatus>,
+}
+
+/// The Status of a single User-Connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserStatus {
+    /// The ID of the User-Connection
+    pub id: u32,
+    /// The Address from which the User connected
+    pub peer: SocketAddr,
+    /// The Data transferred over the Connection so far
+    pub stats: ConnectionStats,
+}
+
+/// A Handle to inspect and manage a running Server
+///
+/// The Handle is obtained from [`Server::handle`](super::Server::handle)
+/// before starting the Server and can be cloned freely
+#[derive(Debug)]
+pub struct ServerHandle<M>
+where
+    M: Metrics,
+{
+    ports: Ports<M>,
+    events: Events,
+}
+
+impl<M> Clone for ServerHandle<M>
+where
+    M: Metrics,
+{
+    fn clone(&self) -> Self {
+        Self {
+            ports: self.ports.clone(),
+            events: self.events.clone(),
+        }
+    }
+}
+
+impl<M> ServerHandle<M>
+where
+    M: Metrics + Send + Sync + 'static,
+{
+    pub(crate) fn new(ports: Ports<M>, events: Events) -> Self {
+        Self { ports, events }
+    }
+
+    fn managers(&self) -> Vec<Arc<ClientManager<TCPClient<M>>>> {
+        self.ports.lock().unwrap().values().cloned().collect()
+    }
+
+    fn find_client(&self, client_id: u32) -> Option<TCPClient<M>> {
+        self.managers()
+            .iter()
+            .flat_map(|manager| manager.all())
+            .find(|client| client.get_id() == client_id)
+    }
+
+    /// Subscribes to the Events of the Server, see
+    /// [`Server::subscribe`](super::Server::subscribe)
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<ServerEvent> {
+        self.events.subscribe()
+    }
+
+    /// Returns all the external Ports that are currently open
+    pub fn ports(&self) -> Vec<u16> {
+        self.ports.lock().unwrap().keys().copied().collect()
+    }
+
+    /// Returns the Status of all the Clients connected for the given Port
+    pub fn clients(&self, port: u16) -> Vec<ClientStatus> {
+        let manager = match self.ports.lock().unwrap().get(&port) {
+            Some(m) => m.clone(),
+            None => return Vec::new(),
+        };
+
+        manager.all().iter().map(|c| c.status()).collect()
+    }
+
+    /// Returns the Status of the Client with the given ID
+    pub fn client(&self, client_id: u32) -> Option<ClientStatus> {
+        self.find_client(client_id).map(|c| c.status())
+    }
+
+    /// Disconnects the Client with the given ID and resets all of its
+    /// User-Connections
+    ///
+    /// Returns:
+    /// Whether or not a Client with the ID was connected
+    pub fn kick_client(&self, client_id: u32) -> bool {
+        match self.find_client(client_id) {
+            Some(client) => {
+                client.kick();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Closes a single User-Connection of the given Client
+    ///
+    /// The User-Connection is reset and the Client is notified that the
+    /// Connection was closed
+    ///
+    /// Returns:
+    /// Whether or not the User-Connection existed
+    pub fn close_user(&self, client_id: u32, user_id: u32) -> bool {
+        match self.find_client(client_id) {
+            Some(client) => client.close_user(
+                user_id,
+                CloseReason::with_text(CloseCode::Policy, "Closed by the Server"),
+            ),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::handshake;
+    use crate::message::Message;
+    use crate::metrics::Empty;
+    use crate::streams::mpsc;
+
+    fn ports_with_client(
+        port: u16,
+        client_id: u32,
+        events: Events,
+    ) -> (
+        Ports<Empty>,
+        TCPClient<Empty>,
+        tokio::sync::mpsc::UnboundedReceiver<Message>,
+    ) {
+        let manager = Arc::new(ClientManager::new());
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let client = TCPClient::new(
+            client_id,
+            "127.0.0.1:12345".parse().unwrap(),
+            &handshake::Config::new(port),
+            manager.clone(),
+            tx,
+            Arc::new(Empty::new()),
+            events,
+        );
+        manager.add(client.clone());
+
+        let mut ports = BTreeMap::new();
+        ports.insert(port, manager);
+        (Arc::new(Mutex::new(ports)), client, rx)
+    }
+
+    #[test]
+    fn list_ports_and_clients() {
+        let events = Events::default();
+        let (ports, _client, _rx) = ports_with_client(8080, 13, events.clone());
+        let handle = ServerHandle::new(ports, events);
+
+        assert_eq!(vec![8080], handle.ports());
+        assert_eq!(
+            vec![ClientStatus {
+                id: 13,
+                peer: "127.0.0.1:12345".parse().unwrap(),
+                port: 8080,
+                connections: 1,
+                users: vec![],
+            }],
+            handle.clients(8080)
+        );
+        assert_eq!(0, handle.clients(8081).len());
+        assert_eq!(true, handle.client(13).is_some());
+        assert_eq!(true, handle.client(14).is_none());
+    }
+
+    #[tokio::test]
+    async fn kick_client() {
+        let events = Events::default();
+        let (ports, _client, _rx) = ports_with_client(8080, 13, events.clone());
+        let handle = ServerHandle::new(ports, events);
+        let mut events_rx = handle.subscribe();
+
+        assert_eq!(false, handle.kick_client(14));
+        assert_eq!(true, handle.kick_client(13));
+        assert_eq!(0, handle.clients(8080).len());
+        assert_eq!(false, handle.kick_client(13));
+
+        assert_eq!(
+            ServerEvent::ClientDisconnected {
+                client_id: 13,
+                port: 8080,
+                reason: crate::server::DisconnectReason::Kicked,
+            },
+            events_rx.recv().await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn close_user() {
+        let events = Events::default();
+        let (ports, client, mut rx) = ports_with_client(8080, 13, events.clone());
+        let handle = ServerHandle::new(ports, events);
+
+        let (user_tx, mut user_rx) = mpsc::stream();
+        client.get_user_cons().set(5, user_tx);
+
+        assert_eq!(false, handle.close_user(13, 6));
+        assert_eq!(true, handle.close_user(13, 5));
+        assert_eq!(false, handle.close_user(13, 5));
+
+        let user_msg = user_rx.recv().await.unwrap();
+        assert_eq!(
+            Some(CloseReason::with_text(
+                CloseCode::Policy,
+                "Closed by the Server"
+            )),
+            user_msg.close_reason()
+        );
+
+        let client_msg = rx.recv().await.unwrap();
+        assert_eq!(5, client_msg.get_header().get_id());
+        assert_eq!(true, client_msg.is_close());
+        assert_eq!(
+            Some(CloseReason::with_text(
+                CloseCode::Policy,
+                "Closed by the Server"
+            )),
+            client_msg.close_reason()
+        );
+    }
+}
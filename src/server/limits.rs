@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+use crate::metrics::LimitKind;
+
+/// The Limits for the Number of concurrent User-Connections
+///
+/// User-Connections that would exceed any of the Limits are rejected right
+/// away. By default there are no Limits
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    per_port: Option<usize>,
+    per_client: Option<usize>,
+    per_ip: Option<usize>,
+}
+
+impl Limits {
+    /// Creates a new Configuration without any Limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the Number of concurrent User-Connections on every external Port
+    pub fn per_port(mut self, max: usize) -> Self {
+        self.per_port = Some(max);
+        self
+    }
+    /// Limits the Number of concurrent User-Connections every Client handles
+    pub fn per_client(mut self, max: usize) -> Self {
+        self.per_client = Some(max);
+        self
+    }
+    /// Limits the Number of concurrent User-Connections from a single IP on
+    /// every external Port
+    pub fn per_ip(mut self, max: usize) -> Self {
+        self.per_ip = Some(max);
+        self
+    }
+
+    /// The maximum Number of User-Connections on a single Port
+    pub fn max_per_port(&self) -> Option<usize> {
+        self.per_port
+    }
+    /// The maximum Number of User-Connections handled by a single Client
+    pub fn max_per_client(&self) -> Option<usize> {
+        self.per_client
+    }
+    /// The maximum Number of User-Connections from a single IP
+    pub fn max_per_ip(&self) -> Option<usize> {
+        self.per_ip
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    total: usize,
+    ips: HashMap<IpAddr, usize>,
+}
+
+/// Keeps track of the User-Connections on a single Port and enforces the
+/// Port- and IP-Limits for them
+#[derive(Debug)]
+pub struct PortLimiter {
+    limits: Limits,
+    counts: Arc<Mutex<Counts>>,
+}
+
+/// Counts as a single User-Connection, until it is dropped
+#[derive(Debug)]
+pub struct Permit {
+    ip: IpAddr,
+    counts: Arc<Mutex<Counts>>,
+}
+
+impl PortLimiter {
+    /// Creates a new Limiter without any active User-Connections
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            counts: Arc::new(Mutex::new(Counts::default())),
+        }
+    }
+
+    /// Tries to add a new User-Connection from the given IP
+    ///
+    /// Returns:
+    /// * Ok with the Permit for the Connection, which needs to be kept until
+    ///   the Connection is closed
+    /// * Err with the Limit that would have been exceeded
+    pub fn acquire(&self, ip: IpAddr) -> Result<Permit, LimitKind> {
+        let mut counts = self.counts.lock().unwrap();
+
+        if let Some(max) = self.limits.per_port {
+            if counts.total >= max {
+                return Err(LimitKind::Port);
+            }
+        }
+        let from_ip = counts.ips.get(&ip).copied().unwrap_or(0);
+        if let Some(max) = self.limits.per_ip {
+            if from_ip >= max {
+                return Err(LimitKind::Ip);
+            }
+        }
+
+        counts.total += 1;
+        counts.ips.insert(ip, from_ip + 1);
+
+        Ok(Permit {
+            ip,
+            counts: self.counts.clone(),
+        })
+    }
+}
+
+impl Permit {
+    /// The IP of the User, to which this Permit belongs
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut counts = match self.counts.lock() {
+            Ok(c) => c,
+            Err(e) => e.into_inner(),
+        };
+
+        counts.total -= 1;
+        if let Some(from_ip) = counts.ips.get_mut(&self.ip) {
+            *from_ip -= 1;
+            if *from_ip == 0 {
+                counts.ips.remove(&self.ip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    const FIRST: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const SECOND: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    #[test]
+    fn no_limits() {
+        let limiter = PortLimiter::new(Limits::new());
+
+        let permits: Vec<Permit> = (0..100).map(|_| limiter.acquire(FIRST).unwrap()).collect();
+        assert_eq!(100, permits.len());
+    }
+
+    #[test]
+    fn port_limit() {
+        let limiter = PortLimiter::new(Limits::new().per_port(2));
+
+        let first = limiter.acquire(FIRST).unwrap();
+        let _second = limiter.acquire(SECOND).unwrap();
+        assert_eq!(LimitKind::Port, limiter.acquire(FIRST).unwrap_err());
+
+        drop(first);
+        assert_eq!(true, limiter.acquire(FIRST).is_ok());
+    }
+
+    #[test]
+    fn ip_limit() {
+        let limiter = PortLimiter::new(Limits::new().per_ip(1).per_port(3));
+
+        let first = limiter.acquire(FIRST).unwrap();
+        assert_eq!(LimitKind::Ip, limiter.acquire(FIRST).unwrap_err());
+        let _second = limiter.acquire(SECOND).unwrap();
+
+        drop(first);
+        let _first = limiter.acquire(FIRST).unwrap();
+        assert_eq!(2, limiter.counts.lock().unwrap().ips.len());
+    }
+}
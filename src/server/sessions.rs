@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::{general::Sequencer, metrics::Metrics};
+
+use super::{events::DisconnectReason, tcpforwarder::TCPClient};
+
+/// A Client whose Connection was lost and that can still resume its Session
+struct Suspended<M>
+where
+    M: Metrics,
+{
+    client: TCPClient<M>,
+    expiry: tokio::task::JoinHandle<()>,
+}
+
+/// Keeps the Sessions of all the Clients whose Connection was lost, until
+/// they either resume them or their Grace-Period runs out
+pub(crate) struct Sessions<M>
+where
+    M: Metrics,
+{
+    grace: Duration,
+    /// The maximum Number of Bytes of unacknowledged Messages kept for every
+    /// Session
+    max_buffered: usize,
+    suspended: Mutex<HashMap<u128, Suspended<M>>>,
+}
+
+impl<M> std::fmt::Debug for Sessions<M>
+where
+    M: Metrics,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sessions")
+            .field("grace", &self.grace)
+            .field("max_buffered", &self.max_buffered)
+            .field("suspended", &self.suspended.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl<M> Sessions<M>
+where
+    M: Metrics,
+{
+    /// Creates a new empty Registry, which keeps every Session for the given
+    /// Grace-Period after losing its Connection
+    pub fn new(grace: Duration) -> Self {
+        Self {
+            grace,
+            max_buffered: crate::general::DEFAULT_MAX_BUFFERED,
+            suspended: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the maximum Number of Bytes of unacknowledged Messages kept for
+    /// every Session, after which it can no longer be resumed
+    pub fn with_max_buffered(mut self, max_buffered: usize) -> Self {
+        self.max_buffered = max_buffered;
+        self
+    }
+
+    /// How long Sessions are kept after losing their Connection
+    pub fn grace(&self) -> Duration {
+        self.grace
+    }
+
+    /// Creates the Sequencer for a new Session
+    pub fn sequencer(&self) -> Sequencer {
+        Sequencer::with_max_buffered(self.max_buffered)
+    }
+
+    /// Generates the Token for a new Session
+    pub fn token() -> u128 {
+        // A Token of 0 is used to indicate that there is no Session
+        rand::thread_rng().gen::<u128>().max(1)
+    }
+
+    /// Whether or not there is a suspended Session with the given Token, that
+    /// belongs to a Client for the given Port
+    pub fn contains(&self, token: u128, port: u16) -> bool {
+        self.suspended
+            .lock()
+            .unwrap()
+            .get(&token)
+            .map(|s| s.client.port() == port)
+            .unwrap_or(false)
+    }
+
+    /// Takes the suspended Client with the given Token out of the Registry,
+    /// to resume its Session
+    pub fn resume(&self, token: u128) -> Option<TCPClient<M>> {
+        let suspended = self.take(token)?;
+        suspended.expiry.abort();
+        Some(suspended.client)
+    }
+
+    fn take(&self, token: u128) -> Option<Suspended<M>> {
+        self.suspended.lock().unwrap().remove(&token)
+    }
+}
+
+impl<M> Sessions<M>
+where
+    M: Metrics + Send + Sync + 'static,
+{
+    /// Suspends the Session of the Client, which is then shut down for good,
+    /// if it is not resumed within the Grace-Period
+    ///
+    /// # Params:
+    /// * `token`: The Token of the Session
+    /// * `client`: The Client, whose Connection was lost
+    /// * `reason`: Why the Connection to the Client was lost
+    pub fn suspend(self: &Arc<Self>, token: u128, client: TCPClient<M>, reason: DisconnectReason) {
+        let sessions = Arc::downgrade(self);
+        let grace = self.grace;
+        let expiry = tokio::task::spawn(async move {
+            tokio::time::sleep(grace).await;
+
+            let expired = sessions.upgrade().and_then(|s| s.take(token));
+            if let Some(suspended) = expired {
+                suspended.client.expire(reason);
+            }
+        });
+
+        self.suspended
+            .lock()
+            .unwrap()
+            .insert(token, Suspended { client, expiry });
+    }
+}
@@ -1,33 +1,57 @@
 mod client;
 pub use client::TCPClient;
+mod queue;
+use queue::UserQueue;
+pub use queue::WaitQueue;
 
 use std::sync::Arc;
 use tokio::net::TcpListener;
 
 use super::clientmanager::ClientManager;
+use super::limits::{Limits, PortLimiter};
+use super::user;
+use crate::metrics::{LimitKind, Metrics};
 
 /// The TCP-Forwarder is the actual Part that accepts User-Connections
 /// and then forwards them to one of the Clients that listen on that
 /// port
-pub struct TCPForwarder {
+pub struct TCPForwarder<M>
+where
+    M: Metrics,
+{
     /// The External Port where users connect to
     user_port: u16,
     /// The Listener of the Forwarder
     listener: TcpListener,
     /// All the Clients that want to receive connections from this
     /// instance
-    clients: Arc<ClientManager<TCPClient>>,
+    clients: Arc<ClientManager<TCPClient<M>>>,
+    /// The Users waiting for a Client, if enabled
+    queue: Option<UserQueue>,
+    /// The Metrics-Collector
+    metrics: Arc<M>,
+    /// Enforces the Limits for the User-Connections on this Port
+    limiter: PortLimiter,
 }
 
-impl TCPForwarder {
+impl<M> TCPForwarder<M>
+where
+    M: Metrics + Send + Sync + 'static,
+{
     /// Creates a new Forwarder
     ///
     /// # Params:
     /// * 'port': The Public facing User-Port
     /// * 'clients': The List of Clients for this Port/Forwarder
+    /// * 'wait_queue': The Configuration for holding Users while there is no Client
+    /// * 'metrics': The Metrics-Collector
+    /// * 'limits': The Limits for the User-Connections
     pub async fn new(
         port: u16,
-        clients: Arc<ClientManager<TCPClient>>,
+        clients: Arc<ClientManager<TCPClient<M>>>,
+        wait_queue: Option<WaitQueue>,
+        metrics: Arc<M>,
+        limits: Limits,
     ) -> Result<Self, std::io::Error> {
         let bind_addr = format!("0.0.0.0:{}", port);
         let listener = TcpListener::bind(&bind_addr).await?;
@@ -36,9 +60,22 @@ impl TCPForwarder {
             user_port: port,
             listener,
             clients,
+            queue: wait_queue.map(UserQueue::new),
+            metrics,
+            limiter: PortLimiter::new(limits),
         })
     }
 
+    /// Resets a User-Connection that would exceed one of the Limits
+    fn reject_limited(&self, user_id: u32, con: tokio::net::TcpStream, limit: LimitKind) {
+        info!(
+            "[{}][{}] Rejecting Connection, reached {:?}-Limit",
+            self.user_port, user_id, limit
+        );
+        self.metrics.user_limited(self.user_port, limit);
+        user::reset(con);
+    }
+
     /// Actually starts the Forwarder
     /// This will never return
     pub async fn start(self) -> ! {
@@ -48,25 +85,53 @@ impl TCPForwarder {
 
         // Accepting User-Requests
         loop {
-            let user_socket = match self.listener.accept().await {
-                Ok((raw_socket, _)) => raw_socket,
+            let (user_socket, user_addr) = match self.listener.accept().await {
+                Ok(s) => s,
                 Err(e) => {
                     error!("[{}] Accepting Req-Connection: {}", self.user_port, e);
                     continue;
                 }
             };
 
+            id = id.wrapping_add(1);
+
+            let permit = match self.limiter.acquire(user_addr.ip()) {
+                Ok(p) => p,
+                Err(limit) => {
+                    self.reject_limited(id, user_socket, limit);
+                    continue;
+                }
+            };
+
             // Get a connect Client for this new User Connection
-            let client = match self.clients.get() {
+            let client = match self.clients.get(&user_addr.ip()) {
                 Some(c) => c,
+                // There are Clients, but all of them reached their Limit
+                None if !self.clients.is_empty() => {
+                    self.reject_limited(id, user_socket, LimitKind::Client);
+                    continue;
+                }
                 None => {
-                    error!("[{}] Could not obtain a Client-Connection", self.user_port);
+                    let queued = match self.queue.as_ref() {
+                        Some(queue) => queue.enqueue(
+                            self.user_port,
+                            id,
+                            user_socket,
+                            permit,
+                            self.clients.clone(),
+                            self.metrics.clone(),
+                        ),
+                        None => false,
+                    };
+                    if !queued {
+                        error!("[{}] Could not obtain a Client-Connection", self.user_port);
+                        self.metrics.user_rejected(self.user_port);
+                    }
                     continue;
                 }
             };
 
-            id = id.wrapping_add(1);
-            client.new_con(id, user_socket);
+            client.new_con(id, user_socket, permit);
         }
     }
 }
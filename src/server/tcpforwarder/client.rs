@@ -1,58 +1,468 @@
 use crate::{
+    accesslog::AccessLog,
     connections::Connections,
-    message::{Message, MessageHeader, MessageType},
-    server::{tcpforwarder::ClientManager, user},
+    general::{
+        ChunkSize, Pinger, RateLimit, Sequencer, Throttle, TokenBucket, MAX_BATCH, PING_INTERVAL,
+        PING_TIMEOUT,
+    },
+    handshake,
+    message::{
+        CloseCode, CloseReason, Compression, HeaderFormat, Message, MessageCodec, MessageHeader,
+        MessageType, ReadBuffer,
+    },
+    metrics::{ConnectionLabels, Metrics},
+    server::{
+        events::{DisconnectReason, Events, ServerEvent},
+        handle::{ClientStatus, UserStatus},
+        limits::Permit,
+        sessions::Sessions,
+        tcpforwarder::ClientManager,
+        user,
+    },
     streams::mpsc,
     Details,
 };
 
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use tokio::sync::oneshot;
+
+mod channels;
+use channels::Channels;
+
 mod tokio_rx;
 mod tokio_tx;
 
+/// The Time a Client has to Accept or Reject a new User-Connection
+const ACCEPT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// The Queue of Messages that should be send to the Client, which is shared
+/// by all the Client-Connections of the same Session
+pub type SendQueue = Arc<tokio::sync::Mutex<tokio::sync::mpsc::UnboundedReceiver<Message>>>;
+
+/// The Session of a Client, which allows it to resume its User-Connections
+/// after reconnecting
+struct ClientSession<M>
+where
+    M: Metrics,
+{
+    token: u128,
+    sessions: Arc<Sessions<M>>,
+    sequencer: Arc<Sequencer>,
+    queue: SendQueue,
+}
+
+impl<M> Clone for ClientSession<M>
+where
+    M: Metrics,
+{
+    fn clone(&self) -> Self {
+        Self {
+            token: self.token,
+            sessions: self.sessions.clone(),
+            sequencer: self.sequencer.clone(),
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<M> std::fmt::Debug for ClientSession<M>
+where
+    M: Metrics,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientSession").finish()
+    }
+}
+
+/// A User-Connection that is currently forwarding Data
+struct ActiveUser<M>
+where
+    M: Metrics,
+{
+    peer: SocketAddr,
+    tracker: Arc<user::UserTracker<M>>,
+}
+
+impl<M> Clone for ActiveUser<M>
+where
+    M: Metrics,
+{
+    fn clone(&self) -> Self {
+        Self {
+            peer: self.peer,
+            tracker: self.tracker.clone(),
+        }
+    }
+}
+
+impl<M> std::fmt::Debug for ActiveUser<M>
+where
+    M: Metrics,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ActiveUser")
+            .field("peer", &self.peer)
+            .finish()
+    }
+}
+
 /// This Client represents a single Connection a Client Instance
 ///
 /// All User-Connections are handled by an instance of this Struct
-#[derive(Clone, Debug)]
-pub struct TCPClient {
+#[derive(Debug)]
+pub struct TCPClient<M>
+where
+    M: Metrics,
+{
     id: u32,
+    /// The Address of the current Client-Connection
+    peer: Arc<Mutex<SocketAddr>>,
+    port: u16,
+    weight: u16,
+    protocol_version: u16,
     user_cons: Connections<mpsc::StreamWriter<Message>>,
-    client_send_queue: tokio::sync::mpsc::UnboundedSender<Message>,
+    users: Connections<ActiveUser<M>>,
+    /// The Queues of all the Client-Connections of the Client
+    channels: Arc<Channels>,
+    /// The Group of Client-Connections, if the Client uses more than one
+    group: Option<u128>,
+    client_manager: Weak<ClientManager<Self>>,
+    /// The Tasks handling the Client-Connection itself, None once the Client
+    /// was shut down
+    tasks: Arc<Mutex<Option<Vec<tokio::task::JoinHandle<()>>>>>,
+    metrics: Arc<M>,
+    events: Events,
+    access_log: Option<AccessLog>,
+    /// The Rate-Limit the Client requested in its Handshake
+    requested_rate: Option<RateLimit>,
+    /// Limits the Data send from all the Users to the Client
+    upstream: Option<Arc<TokenBucket>>,
+    /// Limits the Data send from the Client to all the Users
+    downstream: Option<Arc<TokenBucket>>,
+    /// The Rate-Limit applied to every single User-Connection
+    user_rate_limit: Option<RateLimit>,
+    /// The Time after which idle User-Connections are closed
+    idle_timeout: Option<Duration>,
+    /// The Size of the Chunks in which Data is read from User-Connections
+    chunk_size: ChunkSize,
+    /// The Compression negotiated with the Client in the Handshake
+    compression: Option<Compression>,
+    /// The maximum Size of the Data of a single Message
+    max_message_size: usize,
+    /// The Session of the Client, if it was granted one
+    session: Option<ClientSession<M>>,
+}
+
+impl<M> Clone for TCPClient<M>
+where
+    M: Metrics,
+{
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            peer: self.peer.clone(),
+            port: self.port,
+            weight: self.weight,
+            protocol_version: self.protocol_version,
+            user_cons: self.user_cons.clone(),
+            users: self.users.clone(),
+            channels: self.channels.clone(),
+            group: self.group,
+            client_manager: self.client_manager.clone(),
+            tasks: self.tasks.clone(),
+            metrics: self.metrics.clone(),
+            events: self.events.clone(),
+            access_log: self.access_log.clone(),
+            requested_rate: self.requested_rate,
+            upstream: self.upstream.clone(),
+            downstream: self.downstream.clone(),
+            user_rate_limit: self.user_rate_limit,
+            idle_timeout: self.idle_timeout,
+            chunk_size: self.chunk_size,
+            compression: self.compression,
+            max_message_size: self.max_message_size,
+            session: self.session.clone(),
+        }
+    }
+}
+
+impl<M> TCPClient<M>
+where
+    M: Metrics,
+{
+    /// The Client-ID itself
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    /// The external Port for which the Client receives Connections
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The Format of the Headers on the Client-Connections, which depends on
+    /// the Protocol-Version of the Client
+    pub fn header_format(&self) -> HeaderFormat {
+        HeaderFormat::for_version(self.protocol_version)
+    }
+
+    /// The Codec for the Messages on the Client-Connections
+    fn codec(&self) -> MessageCodec {
+        MessageCodec::new(self.header_format()).with_max_size(self.max_message_size)
+    }
+
+    /// The Group of Client-Connections of the Client, if it uses more than one
+    pub fn group(&self) -> Option<u128> {
+        self.group
+    }
+
+    /// The Queue of Messages for the first Client-Connection
+    pub fn send_queue(&self) -> tokio::sync::mpsc::UnboundedSender<Message> {
+        self.channels.primary()
+    }
+
+    /// The Token of the Session of the Client, if it was granted one
+    pub fn session_token(&self) -> Option<u128> {
+        self.session.as_ref().map(|s| s.token)
+    }
+
+    /// Whether or not the Session of the Client can still be resumed, which
+    /// is no longer the case once too much Data was not acknowledged
+    pub fn resumable(&self) -> bool {
+        self.session
+            .as_ref()
+            .map(|s| !s.sequencer.overflowed())
+            .unwrap_or(false)
+    }
+
+    /// Returns the Connections managed by this Client
+    #[cfg(test)]
+    pub fn get_user_cons(&self) -> Connections<mpsc::StreamWriter<Message>> {
+        self.user_cons.clone()
+    }
+
+    /// Registers a Task that handles the Client-Connection, which is aborted
+    /// once the Client is shut down, or right away if that already happened
+    pub fn add_task(&self, task: tokio::task::JoinHandle<()>) {
+        match self.tasks.lock().unwrap().as_mut() {
+            Some(tasks) => tasks.push(task),
+            None => task.abort(),
+        };
+    }
+
+    /// Returns the current Status of the Client and all its active
+    /// User-Connections
+    pub fn status(&self) -> ClientStatus {
+        let mut users: Vec<UserStatus> = self
+            .users
+            .entries()
+            .into_iter()
+            .map(|(id, user)| UserStatus {
+                id,
+                peer: user.peer,
+                stats: user.tracker.stats(),
+            })
+            .collect();
+        users.sort_by_key(|u| u.id);
+
+        ClientStatus {
+            id: self.id,
+            peer: *self.peer.lock().unwrap(),
+            port: self.port,
+            connections: self.channels.len(),
+            users,
+        }
+    }
 }
 
-impl TCPClient {
+impl<M> TCPClient<M>
+where
+    M: Metrics + Send + Sync + 'static,
+{
     /// Creates a new Client that is then ready to start up
     pub fn new(
         id: u32,
-        _client_manager: std::sync::Arc<ClientManager<Self>>,
+        peer: SocketAddr,
+        config: &handshake::Config,
+        client_manager: std::sync::Arc<ClientManager<Self>>,
         send_queue: tokio::sync::mpsc::UnboundedSender<Message>,
+        metrics: Arc<M>,
+        events: Events,
     ) -> Self {
         Self {
             id,
+            peer: Arc::new(Mutex::new(peer)),
+            port: config.port(),
+            weight: config.weight(),
+            protocol_version: config.protocol_version(),
             user_cons: Connections::new(),
-            client_send_queue: send_queue,
+            users: Connections::new(),
+            channels: Arc::new(Channels::new(send_queue)),
+            group: config.group(),
+            client_manager: Arc::downgrade(&client_manager),
+            tasks: Arc::new(Mutex::new(Some(Vec::new()))),
+            metrics,
+            events,
+            access_log: None,
+            requested_rate: config.rate_limit().map(RateLimit::new),
+            upstream: None,
+            downstream: None,
+            user_rate_limit: None,
+            idle_timeout: None,
+            chunk_size: ChunkSize::default(),
+            compression: None,
+            max_message_size: MessageCodec::DEFAULT_MAX_SIZE,
+            session: None,
         }
     }
 
-    /// The Client-ID itself
-    pub fn get_id(&self) -> u32 {
-        self.id
+    /// Writes an Access-Record for every User-Connection handled by this
+    /// Client to the given Log
+    pub fn with_access_log(mut self, log: Option<AccessLog>) -> Self {
+        self.access_log = log;
+        self
     }
 
-    /// Returns the Connections managed by this Client
-    pub fn get_user_cons(&self) -> Connections<mpsc::StreamWriter<Message>> {
-        self.user_cons.clone()
+    /// Limits the Bandwidth of the Client and each of its User-Connections
+    ///
+    /// The Limit for the Client is shared by all of its User-Connections and
+    /// applied in each Direction separately, if the Client requested a
+    /// stricter Limit in its Handshake that one is used instead
+    ///
+    /// Params:
+    /// * client: The Limit for the whole Client-Connection
+    /// * user: The Limit for every single User-Connection
+    pub fn with_rate_limits(mut self, client: Option<RateLimit>, user: Option<RateLimit>) -> Self {
+        let client = RateLimit::strictest(client, self.requested_rate);
+        self.upstream = client.map(|l| Arc::new(TokenBucket::new(l)));
+        self.downstream = client.map(|l| Arc::new(TokenBucket::new(l)));
+        self.user_rate_limit = user;
+        self
+    }
+
+    /// Closes User-Connections, once no Data was transferred over them for
+    /// the given Timeout
+    pub fn with_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Sets the Size of the Chunks in which Data is read from the
+    /// User-Connections
+    pub fn with_chunk_size(mut self, size: ChunkSize) -> Self {
+        self.chunk_size = size;
+        self
+    }
+
+    /// Sets the Compression negotiated with the Client, which is used for
+    /// the Data send to it and needed for the Data received from it
+    pub fn with_compression(mut self, compression: Option<Compression>) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Sets the maximum Size of the Data of a single Message exchanged with
+    /// the Client
+    pub fn with_max_message_size(mut self, size: usize) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Grants the Client a Session, which keeps its User-Connections around
+    /// for a while after losing the Client-Connection
+    ///
+    /// Params:
+    /// * token: The Token of the Session
+    /// * sessions: The Registry that keeps the Session, once it is suspended
+    /// * queue: The Queue of Messages for the Client, which is kept as well
+    pub fn with_session(
+        mut self,
+        token: u128,
+        sessions: Arc<Sessions<M>>,
+        queue: SendQueue,
+    ) -> Self {
+        self.session = Some(ClientSession {
+            token,
+            sequencer: Arc::new(sessions.sequencer()),
+            sessions,
+            queue,
+        });
+        self
+    }
+
+    /// Resumes the suspended Session of the Client on a new Client-Connection
+    ///
+    /// Params:
+    /// * peer: The Address of the new Client-Connection
+    ///
+    /// Returns:
+    /// The Queue of Messages for the Client and a Receiver, which is notified
+    /// once the Client resumed its side of the Session
+    pub fn resume(&self, peer: SocketAddr) -> Option<(SendQueue, oneshot::Receiver<()>)> {
+        let session = self.session.as_ref()?;
+        *self.peer.lock().unwrap() = peer;
+        Some((session.queue.clone(), session.sequencer.expect_resume()))
+    }
+
+    /// Adds another Client-Connection to the Client, over which some of its
+    /// User-Connections are forwarded from now on
+    ///
+    /// Params:
+    /// * queue: The Queue of Messages for the new Client-Connection
+    pub fn add_connection(&self, queue: tokio::sync::mpsc::UnboundedSender<Message>) {
+        self.channels.add(queue);
+    }
+
+    /// Suspends the Session of the Client, as it reconnected to resume it
+    /// while its old Client-Connection still seemed to be open
+    pub fn reconnected(&self) {
+        self.connection_lost(DisconnectReason::Reconnected);
+    }
+
+    /// Closes the User-Connection once it was idle for the Timeout, unless
+    /// the Connection is closed before that
+    async fn watch_idle(self, user_id: u32, tracker: Arc<user::UserTracker<M>>, timeout: Duration) {
+        if !tracker.idle().expired(timeout).await {
+            return;
+        }
+
+        let reason = CloseReason::with_text(CloseCode::Timeout, "Connection was idle");
+        if self.close_user(user_id, reason) {
+            self.metrics.user_timeout(self.port);
+        }
+    }
+
+    /// Creates the Close-Message for the given User-Connection, which only
+    /// contains the Reason if the Client supports it
+    fn close_message(protocol_version: u16, user_id: u32, reason: CloseReason) -> Message {
+        if protocol_version < 4 {
+            return Message::new(MessageHeader::new(user_id, MessageType::Close, 0), vec![]);
+        }
+        reason.into_message(user_id)
     }
 
     async fn close_user_connection(
         user_id: u32,
         client_id: u32,
+        protocol_version: u16,
         user_cons: Connections<mpsc::StreamWriter<Message>>,
         send_queue: tokio::sync::mpsc::UnboundedSender<Message>,
+        tracker: Arc<user::UserTracker<M>>,
+        reason: CloseReason,
     ) {
         user_cons.remove(user_id);
+        tracker.close(reason.clone());
 
-        let header = MessageHeader::new(user_id, MessageType::Close, 0);
-        let msg = Message::new(header, vec![0; 0]);
+        debug!(
+            "[{}][{}] Closing Connection: {}",
+            client_id, user_id, reason
+        );
+        let msg = Self::close_message(protocol_version, user_id, reason);
         match send_queue.send(msg) {
             Ok(_) => {}
             Err(e) => {
@@ -61,12 +471,195 @@ impl TCPClient {
         };
     }
 
+    /// Removes the User-Connection, which frees up its Slot for new
+    /// User-Connections that wait for a Client with less than the maximum
+    /// Number of Connections
+    fn remove_user(&self, user_id: u32) -> Option<mpsc::StreamWriter<Message>> {
+        let (_, user_con) = self.user_cons.remove(user_id)?;
+        if let Some(manager) = self.client_manager.upgrade() {
+            manager.released();
+        }
+        Some(user_con)
+    }
+
+    /// Closes the given User-Connection, which resets the Connection to the
+    /// User and notifies the Client that it was closed
+    ///
+    /// Params:
+    /// * user_id: The ID of the User-Connection to close
+    /// * reason: The Reason for closing the Connection
+    ///
+    /// Returns:
+    /// Whether or not the User-Connection existed
+    pub fn close_user(&self, user_id: u32, reason: CloseReason) -> bool {
+        let user_con = match self.remove_user(user_id) {
+            Some(c) => c,
+            None => return false,
+        };
+
+        info!("[{}][{}] Closing Connection: {}", self.id, user_id, reason);
+
+        // The Close is handled by the Task sending to the User, which resets
+        // the Connection for any Reason that is an Error
+        let close = match reason.code().is_error() {
+            true => reason.clone(),
+            false => CloseReason::with_text(
+                CloseCode::Policy,
+                reason.text().unwrap_or("Closed by the Server"),
+            ),
+        };
+        if let Err(e) = user_con.send(close.into_message(user_id)) {
+            error!("[{}][{}] Closing User-Connection: {}", self.id, user_id, e);
+        }
+
+        let msg = Self::close_message(self.protocol_version, user_id, reason);
+        if let Err(e) = self.channels.get(user_id).send(msg) {
+            error!("[{}][{}] Sending Close Message: {}", self.id, user_id, e);
+        }
+        true
+    }
+
+    /// Disconnects the Client from the Server and resets all of its
+    /// User-Connections
+    pub fn kick(&self) {
+        info!("[{}] Kicking Client", self.id);
+        self.shutdown(DisconnectReason::Kicked);
+    }
+
+    /// Removes the Client from the Server, resets all of its User-Connections
+    /// and stops all the Tasks handling the Client-Connection
+    ///
+    /// This is safe to call multiple times and from within the Tasks of the
+    /// Client, only the first call reports the Client as disconnected
+    fn shutdown(&self, reason: DisconnectReason) {
+        if let Some(manager) = self.client_manager.upgrade() {
+            if manager.remove(self.id) {
+                info!("[{}] Client disconnected: {:?}", self.id, reason);
+                self.metrics.client_disconnected(self.port);
+                self.events.emit(ServerEvent::ClientDisconnected {
+                    client_id: self.id,
+                    port: self.port,
+                    reason: reason.clone(),
+                });
+            }
+        }
+
+        self.close_all(reason);
+    }
+
+    /// Handles the Loss of the Client-Connection, which suspends the Session
+    /// of the Client, if it has one, or otherwise shuts the Client down
+    ///
+    /// While the Session is suspended, the Client receives no new
+    /// User-Connections, but its existing ones are kept open
+    fn connection_lost(&self, reason: DisconnectReason) {
+        let session = match self.session.as_ref() {
+            Some(s) => s,
+            None => return self.shutdown(reason),
+        };
+        if session.sequencer.overflowed() {
+            info!(
+                "[{}] Session can not be resumed: Too much Data was not acknowledged",
+                self.id
+            );
+            return self.shutdown(reason);
+        }
+
+        // The Client was already suspended or shut down otherwise
+        let removed = self
+            .client_manager
+            .upgrade()
+            .map(|manager| manager.remove(self.id))
+            .unwrap_or(false);
+        if !removed {
+            return;
+        }
+
+        info!("[{}] Client suspended: {:?}", self.id, reason);
+        self.metrics.client_disconnected(self.port);
+        self.events.emit(ServerEvent::ClientSuspended {
+            client_id: self.id,
+            port: self.port,
+            reason: reason.clone(),
+        });
+
+        // The Tasks for the next Client-Connection are added to the same List
+        if let Some(tasks) = self.tasks.lock().unwrap().as_mut() {
+            for task in tasks.drain(..) {
+                task.abort();
+            }
+        }
+
+        session
+            .sessions
+            .suspend(session.token, self.clone(), reason);
+    }
+
+    /// Shuts down the Client, once its suspended Session expired
+    ///
+    /// Params:
+    /// * reason: Why the Connection to the Client was originally lost
+    pub fn expire(&self, reason: DisconnectReason) {
+        info!("[{}] Session expired: {:?}", self.id, reason);
+        self.events.emit(ServerEvent::ClientDisconnected {
+            client_id: self.id,
+            port: self.port,
+            reason: reason.clone(),
+        });
+
+        self.close_all(reason);
+    }
+
+    /// Resets all the User-Connections of the Client and stops all the Tasks
+    /// handling the Client-Connection
+    fn close_all(&self, reason: DisconnectReason) {
+        // The Close is handled by the Tasks of each User-Connection, which
+        // then reset the Connection and stop
+        let close = match reason {
+            DisconnectReason::Kicked => {
+                CloseReason::with_text(CloseCode::Policy, "Client was kicked")
+            }
+            _ => CloseReason::with_text(CloseCode::Error, "Lost the Connection to the Client"),
+        };
+        for (user_id, user_con) in self.user_cons.entries() {
+            self.user_cons.remove(user_id);
+            let _ = user_con.send(close.clone().into_message(user_id));
+        }
+
+        // Aborting the current Task, when called from one of them, only takes
+        // effect once it yields again
+        let tasks = self.tasks.lock().unwrap().take();
+        for task in tasks.into_iter().flatten() {
+            task.abort();
+        }
+    }
+
     /// Adds a new user connection to this server-client
     ///
+    /// If the Client rejects the Connection, it is handed to one of the other
+    /// Clients for the same Port and if none of them accepts it either, the
+    /// Connection is reset
+    ///
     /// Params:
     /// * id: The ID of the new user connection
     /// * con: The new user connection
-    pub fn new_con(&self, user_id: u32, con: tokio::net::TcpStream) {
+    /// * permit: The Permit for the Connection, which is kept until it is closed
+    pub fn new_con(&self, user_id: u32, con: tokio::net::TcpStream, permit: Permit) {
+        self.connect(user_id, con, permit, Vec::new());
+    }
+
+    /// Params:
+    /// * id: The ID of the new user connection
+    /// * con: The new user connection
+    /// * permit: The Permit for the Connection
+    /// * tried: The IDs of the Clients that already rejected the Connection
+    fn connect(
+        &self,
+        user_id: u32,
+        con: tokio::net::TcpStream,
+        permit: Permit,
+        mut tried: Vec<u32>,
+    ) {
         let peer_addr = match con.peer_addr() {
             Ok(a) => a,
             Err(e) => {
@@ -80,106 +673,712 @@ impl TCPClient {
 
         let details = con_details.serialize();
 
+        // This needs to be registered before sending the Connect, to make sure
+        // that we dont miss the Response of the Client
+        let (tx, mut rx) = mpsc::stream();
+        self.user_cons.set(user_id, tx);
+
         // Notify the client of the new connection
         let n_con_msg = Message::new(
             MessageHeader::new(user_id, MessageType::Connect, details.len() as u64),
             details,
         );
-        if let Err(e) = self.client_send_queue.send(n_con_msg) {
+        if let Err(e) = self.channels.assign(user_id).send(n_con_msg) {
             error!(
                 "[{}][{}] Sending Connect message: {:?}",
                 self.id, user_id, e
             );
+            self.remove_user(user_id);
+            self.channels.release(user_id);
+            self.metrics.user_rejected(self.port);
             return;
         }
 
-        let (tx, rx) = mpsc::stream();
-        self.user_cons.set(user_id, tx);
+        // Older Clients dont Accept or Reject Connections
+        if self.protocol_version < 3 {
+            self.start_user(user_id, con, ip_details, rx, permit);
+            return;
+        }
+
+        let client = self.clone();
+        tokio::task::spawn(async move {
+            let response = match tokio::time::timeout(ACCEPT_TIMEOUT, rx.recv()).await {
+                Ok(Ok(msg)) => msg,
+                Ok(Err(_)) | Err(_) => {
+                    error!(
+                        "[{}][{}] Client did not accept the Connection",
+                        client.id, user_id
+                    );
+                    client.remove_user(user_id);
+                    // The Client might still accept it later on, so it needs to
+                    // be told that the Connection is gone
+                    let _ = client.channels.get(user_id).send(Self::close_message(
+                        client.protocol_version,
+                        user_id,
+                        CloseReason::with_text(CloseCode::Timeout, "Accept timed out"),
+                    ));
+                    client.channels.release(user_id);
+                    user::reset(con);
+                    client.metrics.user_rejected(client.port);
+                    return;
+                }
+            };
+
+            if *response.get_header().get_kind() == MessageType::Accept {
+                client.start_user(user_id, con, ip_details, rx, permit);
+                return;
+            }
+
+            client.remove_user(user_id);
+            client.channels.release(user_id);
+            info!(
+                "[{}][{}] Client rejected Connection: {}",
+                client.id,
+                user_id,
+                String::from_utf8_lossy(response.get_data())
+            );
 
-        let (read_con, write_con) = con.into_split();
+            tried.push(client.id);
+            let next_client = client
+                .client_manager
+                .upgrade()
+                .and_then(|manager| manager.get_except(&ip_details, &tried));
+            match next_client {
+                Some(next) => next.connect(user_id, con, permit, tried),
+                None => {
+                    user::reset(con);
+                    client.metrics.user_rejected(client.port);
+                }
+            };
+        });
+    }
+
+    /// Starts forwarding the Data between the User and the Client
+    ///
+    /// This is the only Point at which a User-Connection is reported as
+    /// connected, so that every `UserConnected` is followed by a `UserClosed`
+    fn start_user(
+        &self,
+        user_id: u32,
+        con: tokio::net::TcpStream,
+        ip: IpAddr,
+        rx: mpsc::StreamReader<Message>,
+        permit: Permit,
+    ) {
+        self.metrics.user_accepted(self.port);
+        self.events.emit(ServerEvent::UserConnected {
+            port: self.port,
+            user_id,
+            peer: ip,
+        });
+
+        // Both Tasks of the User-Connection share the same Span
+        #[cfg(feature = "trace")]
+        let span = tracing::info_span!(
+            "user",
+            client_id = self.id,
+            user_id,
+            port = self.port,
+            peer = %con
+                .peer_addr()
+                .map(|a| a.ip().to_string())
+                .unwrap_or_default()
+        );
+
+        let peer = con.peer_addr().ok();
+        let (read_con, mut write_con) = con.into_split();
+
+        let mut tracker = user::UserTracker::open(
+            ConnectionLabels {
+                client_id: self.id,
+                port: self.port,
+                connection_id: user_id,
+            },
+            self.metrics.clone(),
+        );
+        if let (Some(log), Some(peer)) = (self.access_log.clone(), peer) {
+            tracker = tracker.with_access_log(log, peer);
+        }
+        let tracker = Arc::new(tracker);
+        if let Some(peer) = peer {
+            self.users.set(
+                user_id,
+                ActiveUser {
+                    peer,
+                    tracker: tracker.clone(),
+                },
+            );
+        }
+
+        let user_bucket = |limit: RateLimit| Arc::new(TokenBucket::new(limit));
+        let recv_throttle = Throttle::new()
+            .with(self.upstream.clone())
+            .with(self.user_rate_limit.map(user_bucket));
+        let send_throttle = Throttle::new()
+            .with(self.downstream.clone())
+            .with(self.user_rate_limit.map(user_bucket));
+
+        if let Some(timeout) = self.idle_timeout {
+            tokio::task::spawn(instrument!(
+                self.clone().watch_idle(user_id, tracker.clone(), timeout),
+                span.clone()
+            ));
+        }
 
         let client_id = self.id;
-        tokio::task::spawn(user::send(client_id, user_id, write_con, rx));
+        let protocol_version = self.protocol_version;
         let cloned_cons = self.user_cons.clone();
-        let send_queue = self.client_send_queue.clone();
-        tokio::task::spawn(user::recv(
-            self.id,
-            user_id,
-            read_con,
-            self.client_send_queue.clone(),
-            Self::close_user_connection(user_id, client_id, cloned_cons, send_queue),
+        let send_queue = self.channels.get(user_id);
+        let channels = self.channels.clone();
+        let users = self.users.clone();
+        let client_manager = self.client_manager.clone();
+        let events = self.events.clone();
+        let port = self.port;
+        let close_tracker = tracker.clone();
+        let recv_task = tokio::task::spawn(instrument!(
+            user::recv(
+                self.id,
+                user_id,
+                read_con,
+                send_queue.clone(),
+                tracker.clone(),
+                recv_throttle,
+                self.chunk_size,
+                self.max_message_size,
+                move |reason| {
+                    Self::close_user_connection(
+                        user_id,
+                        client_id,
+                        protocol_version,
+                        cloned_cons,
+                        send_queue,
+                        close_tracker,
+                        reason,
+                    )
+                },
+            ),
+            span.clone()
+        ));
+        tokio::task::spawn(instrument!(
+            async move {
+                let reason = user::send(
+                    client_id,
+                    user_id,
+                    &mut write_con,
+                    rx,
+                    &tracker,
+                    send_throttle,
+                )
+                .await;
+                if let Some(reason) = reason.as_ref() {
+                    tracker.close(reason.clone());
+                }
+
+                // Connections that were closed by the Client because of an Error
+                // are reset, to also signal the Error to the User
+                match reason {
+                    Some(reason) if reason.code().is_error() => {
+                        user::reset_split(write_con, recv_task);
+                    }
+                    _ => {
+                        drop(write_con);
+                        let _ = recv_task.await;
+                    }
+                };
+
+                // Both Halves are done at this Point
+                tracker.idle().stop();
+                users.remove(user_id);
+                channels.release(user_id);
+                drop(permit);
+                // New User-Connections might be waiting for this Client to
+                // drop below its maximum Number of Connections
+                if let Some(manager) = client_manager.upgrade() {
+                    manager.released();
+                }
+                events.emit(ServerEvent::UserClosed {
+                    port,
+                    client_id,
+                    user_id,
+                    stats: tracker.stats(),
+                });
+            },
+            span
         ));
     }
 
     /// This listens to the Client-Connection and forwards the messages to the
-    /// correct User-Connections
+    /// correct User-Connections, until the Connection fails and the Client is
+    /// shut down
     ///
     /// Params:
-    /// * id: The ID of the Client
     /// * read_con: The Reader-Half of the Client-Connection
-    /// * user_cons: The User-Connections
-    /// * client_manager: The Manager for this client
-    /// * obj_pool: The Object-Pool which should be used for Messages
+    /// * queue: The Queue of messages for the same Client-Connection
+    /// * pinger: The Pinger used for the Client-Connection
     pub async fn receiver(
-        id: u32,
+        self,
         mut read_con: tokio::net::tcp::OwnedReadHalf,
-        user_cons: Connections<mpsc::StreamWriter<Message>>,
-        client_manager: std::sync::Arc<ClientManager<Self>>,
+        queue: tokio::sync::mpsc::UnboundedSender<Message>,
+        pinger: Arc<Pinger>,
     ) {
-        let mut header_buffer = [0; 13];
+        let sequencer = self.session.as_ref().map(|s| s.sequencer.clone());
+        let mut codec = self.codec();
+        let mut buffer = ReadBuffer::new();
         loop {
-            if let Err(e) =
-                tokio_rx::receive(id, &mut read_con, &user_cons, &mut header_buffer).await
+            if let Err(e) = tokio_rx::receive(
+                self.id,
+                self.port,
+                &mut read_con,
+                &self.user_cons,
+                &queue,
+                &pinger,
+                sequencer.as_deref(),
+                self.compression,
+                self.metrics.as_ref(),
+                &mut codec,
+                &mut buffer,
+            )
+            .await
             {
-                error!("[{}] Receiving Client-Message: {:?}", id, e);
-                client_manager.remove(id);
+                error!("[{}] Receiving Client-Message: {}", self.id, e);
+                self.connection_lost(DisconnectReason::ReceiveFailed);
                 return;
             }
         }
     }
 
     /// This Receives messages from users and then forwards them to the
-    /// Client-Connection
+    /// Client-Connection, until the Connection fails and the Client is shut
+    /// down
     ///
     /// Params:
-    /// * id: The ID of the Client
     /// * write_con: The Write-Half of the Client-Connection
     /// * queue: The Queue of messages to forward to the Client
-    /// * client_manager: The Client-Manager
+    /// * resumed: Notified once the Client resumed its side of the Session,
+    ///   if this Connection resumes a Session
     pub async fn sender(
-        id: u32,
+        self,
         mut write_con: tokio::net::tcp::OwnedWriteHalf,
-        mut queue: tokio::sync::mpsc::UnboundedReceiver<Message>,
-        client_manager: std::sync::Arc<ClientManager<Self>>,
+        queue: SendQueue,
+        resumed: Option<oneshot::Receiver<()>>,
     ) {
-        let mut h_data = [0; 13];
+        let mut queue = queue.lock().await;
+        let sequencer = self.session.as_ref().map(|s| s.sequencer.clone());
+        let codec = self.codec();
+        let mut batch = Vec::with_capacity(MAX_BATCH);
+        let mut head_buf = Vec::new();
+
+        if let (Some(sequencer), Some(resumed)) = (sequencer.as_ref(), resumed) {
+            if let Err(e) = sequencer
+                .resume(&mut write_con, resumed, &codec, &mut head_buf)
+                .await
+            {
+                error!("[{}] Resuming Session: {}", self.id, e);
+                self.connection_lost(DisconnectReason::SendFailed);
+                return;
+            }
+        }
+
         loop {
-            if let Err(e) = tokio_tx::send(&mut write_con, &mut queue, &mut h_data).await {
-                error!("[{}] Sending Client-Message: {:?}", id, e);
-                client_manager.remove(id);
+            if let Err(e) = tokio_tx::send(
+                self.port,
+                &mut write_con,
+                &mut queue,
+                &self.user_cons,
+                &mut batch,
+                &codec,
+                &mut head_buf,
+                sequencer.as_deref(),
+                self.compression,
+                self.metrics.as_ref(),
+            )
+            .await
+            {
+                error!("[{}] Sending Client-Message: {}", self.id, e);
+                self.connection_lost(DisconnectReason::SendFailed);
                 return;
             }
         }
     }
+
+    /// This periodically sends Pings to the Client and shuts the Client down
+    /// once it stopped responding to them
+    ///
+    /// Params:
+    /// * queue: The Queue of messages for the Client-Connection
+    /// * pinger: The Pinger used for the Client-Connection
+    pub async fn pinger(
+        self,
+        queue: tokio::sync::mpsc::UnboundedSender<Message>,
+        pinger: Arc<Pinger>,
+    ) {
+        let result = pinger.run(&queue, PING_INTERVAL, PING_TIMEOUT).await;
+
+        error!("[{}] Pinging Client: {:?}", self.id, result);
+        self.connection_lost(DisconnectReason::PingFailed);
+    }
 }
 
-impl super::super::clientmanager::Client for TCPClient {
+impl<M> super::super::clientmanager::Client for TCPClient<M>
+where
+    M: Metrics,
+{
     fn id(&self) -> u32 {
         self.get_id()
     }
+
+    fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    fn active_connections(&self) -> usize {
+        self.user_cons.len()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::metrics::Empty;
+
+    /// Creates the Client 123 for the Port 13, which is not yet added to its
+    /// ClientManager
+    fn test_client(
+        config: &handshake::Config,
+        events: Events,
+    ) -> (
+        TCPClient<Empty>,
+        Arc<ClientManager<TCPClient<Empty>>>,
+        tokio::sync::mpsc::UnboundedReceiver<Message>,
+    ) {
+        let manager = Arc::new(ClientManager::new());
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let client = TCPClient::new(
+            123,
+            "127.0.0.1:12345".parse().unwrap(),
+            config,
+            manager.clone(),
+            tx,
+            Arc::new(Empty::new()),
+            events,
+        );
+        (client, manager, rx)
+    }
+
+    /// Creates the Client 123 with the Session 7, which is already added to
+    /// its ClientManager
+    fn session_client(
+        sessions: Arc<Sessions<Empty>>,
+        events: Events,
+    ) -> (TCPClient<Empty>, Arc<ClientManager<TCPClient<Empty>>>) {
+        let (client, manager, rx) = test_client(&handshake::Config::new(13), events);
+        let client = client.with_session(7, sessions, Arc::new(tokio::sync::Mutex::new(rx)));
+        manager.add(client.clone());
+        (client, manager)
+    }
 
     #[test]
     fn new_client() {
-        let manager_arc = std::sync::Arc::new(ClientManager::new());
-        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
-
-        let client = TCPClient::new(123, manager_arc, tx);
+        let (client, _, _rx) = test_client(&handshake::Config::new(13), Events::default());
 
         assert_eq!(123, client.get_id());
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_user_closed() {
+        let (client, _, mut rx) = test_client(&handshake::Config::new(13), Events::default());
+        let (user_tx, mut user_rx) = mpsc::stream();
+        client.get_user_cons().set(5, user_tx);
+        let tracker = Arc::new(user::UserTracker::open(
+            ConnectionLabels {
+                client_id: 123,
+                port: 13,
+                connection_id: 5,
+            },
+            Arc::new(Empty::new()),
+        ));
+
+        let start = tokio::time::Instant::now();
+        let watcher = tokio::spawn(client.clone().watch_idle(
+            5,
+            tracker.clone(),
+            Duration::from_secs(30),
+        ));
+        tokio::time::sleep(Duration::from_secs(20)).await;
+        tracker.received(10);
+        watcher.await.unwrap();
+
+        assert_eq!(Duration::from_secs(50), start.elapsed());
+        let expected = CloseReason::with_text(CloseCode::Timeout, "Connection was idle");
+        assert_eq!(
+            Some(expected.clone()),
+            user_rx.recv().await.unwrap().close_reason()
+        );
+        assert_eq!(Some(expected), rx.recv().await.unwrap().close_reason());
+        assert_eq!(true, client.get_user_cons().get_clone(5).is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_watch_stopped() {
+        let (client, _, _rx) = test_client(&handshake::Config::new(13), Events::default());
+        let (user_tx, _user_rx) = mpsc::stream();
+        client.get_user_cons().set(5, user_tx);
+        let tracker = Arc::new(user::UserTracker::open(
+            ConnectionLabels {
+                client_id: 123,
+                port: 13,
+                connection_id: 5,
+            },
+            Arc::new(Empty::new()),
+        ));
+
+        tracker.idle().stop();
+        client
+            .clone()
+            .watch_idle(5, tracker, Duration::from_secs(30))
+            .await;
+
+        assert_eq!(true, client.get_user_cons().get_clone(5).is_some());
+    }
+
+    #[tokio::test]
+    async fn shutdown_closes_users_and_tasks() {
+        let events = Events::default();
+        let mut events_rx = events.subscribe();
+
+        let (client, manager_arc, _rx) = test_client(&handshake::Config::new(13), events);
+        manager_arc.add(client.clone());
+
+        // A real User-Connection, to make sure that both of its Tasks stop
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut user_side = tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server_side, _) = listener.accept().await.unwrap();
+        let limiter = crate::server::limits::PortLimiter::new(Default::default());
+        let permit = limiter
+            .acquire(server_side.peer_addr().unwrap().ip())
+            .unwrap();
+
+        let (user_tx, user_rx) = mpsc::stream();
+        client.get_user_cons().set(5, user_tx);
+        let ip = server_side.peer_addr().unwrap().ip();
+        client.start_user(5, server_side, ip, user_rx, permit);
+
+        client.shutdown(DisconnectReason::ReceiveFailed);
+
+        assert_eq!(
+            ServerEvent::UserConnected {
+                port: 13,
+                user_id: 5,
+                peer: ip,
+            },
+            events_rx.recv().await.unwrap()
+        );
+        assert_eq!(
+            ServerEvent::ClientDisconnected {
+                client_id: 123,
+                port: 13,
+                reason: DisconnectReason::ReceiveFailed,
+            },
+            events_rx.recv().await.unwrap()
+        );
+        match tokio::time::timeout(Duration::from_secs(5), events_rx.recv())
+            .await
+            .unwrap()
+            .unwrap()
+        {
+            ServerEvent::UserClosed {
+                client_id, user_id, ..
+            } => assert_eq!((123, 5), (client_id, user_id)),
+            other => panic!("Unexpected Event: {:?}", other),
+        };
+
+        let mut buf = [0; 8];
+        let read = tokio::io::AsyncReadExt::read(&mut user_side, &mut buf).await;
+        assert_eq!(
+            std::io::ErrorKind::ConnectionReset,
+            read.unwrap_err().kind()
+        );
+
+        assert_eq!(true, manager_arc.is_empty());
+        assert_eq!(true, client.get_user_cons().get_clone(5).is_none());
+        assert_eq!(true, client.status().users.is_empty());
+        // The Permit was released by the User-Connection
+        assert_eq!(true, limiter.acquire("127.0.0.1".parse().unwrap()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn add_task_after_shutdown() {
+        let (client, _, _rx) = test_client(&handshake::Config::new(13), Events::default());
+
+        // The Senders are dropped once the Tasks are aborted
+        let pending_task = || {
+            let (done_tx, done_rx) = tokio::sync::oneshot::channel::<()>();
+            let task = tokio::spawn(async move {
+                let _done = done_tx;
+                std::future::pending::<()>().await
+            });
+            (task, done_rx)
+        };
+
+        let (before, before_rx) = pending_task();
+        client.add_task(before);
+        client.shutdown(DisconnectReason::SendFailed);
+
+        let (after, after_rx) = pending_task();
+        client.add_task(after);
+
+        assert_eq!(true, before_rx.await.is_err());
+        assert_eq!(true, after_rx.await.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lost_connection_suspends_session() {
+        let events = Events::default();
+        let mut events_rx = events.subscribe();
+        let sessions = Arc::new(Sessions::new(Duration::from_secs(10)));
+
+        let (client, manager_arc) = session_client(sessions.clone(), events);
+
+        let (user_tx, user_rx) = mpsc::stream();
+        client.get_user_cons().set(5, user_tx);
+
+        client.connection_lost(DisconnectReason::ReceiveFailed);
+
+        assert_eq!(
+            ServerEvent::ClientSuspended {
+                client_id: 123,
+                port: 13,
+                reason: DisconnectReason::ReceiveFailed,
+            },
+            events_rx.recv().await.unwrap()
+        );
+        assert_eq!(true, manager_arc.is_empty());
+        assert_eq!(true, sessions.contains(7, 13));
+        assert_eq!(false, sessions.contains(7, 14));
+        // The User-Connections are kept while the Session is suspended
+        assert_eq!(true, client.get_user_cons().get_clone(5).is_some());
+
+        let resumed = sessions.resume(7).unwrap();
+        assert_eq!(
+            true,
+            resumed.resume("127.0.0.1:23456".parse().unwrap()).is_some()
+        );
+        assert_eq!(
+            "127.0.0.1:23456".parse::<SocketAddr>().unwrap(),
+            client.status().peer
+        );
+        assert_eq!(false, sessions.contains(7, 13));
+
+        // The Session no longer expires, once it was resumed
+        tokio::time::sleep(Duration::from_secs(11)).await;
+        assert_eq!(true, client.get_user_cons().get_clone(5).is_some());
+        drop(user_rx);
+    }
+
+    #[tokio::test]
+    async fn overflowed_session_not_suspended() {
+        let sessions = Arc::new(Sessions::new(Duration::from_secs(10)).with_max_buffered(4));
+
+        let (client, manager_arc) = session_client(sessions.clone(), Events::default());
+
+        let (user_tx, _user_rx) = mpsc::stream();
+        client.get_user_cons().set(5, user_tx);
+
+        client
+            .session
+            .as_ref()
+            .unwrap()
+            .sequencer
+            .sent(&Message::new(
+                MessageHeader::new(5, MessageType::Data, 5),
+                vec![1; 5],
+            ));
+        assert_eq!(false, client.resumable());
+
+        client.connection_lost(DisconnectReason::ReceiveFailed);
+
+        assert_eq!(true, manager_arc.is_empty());
+        assert_eq!(false, sessions.contains(7, 13));
+        assert_eq!(true, client.get_user_cons().get_clone(5).is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn suspended_session_expires() {
+        let events = Events::default();
+        let mut events_rx = events.subscribe();
+        let sessions = Arc::new(Sessions::new(Duration::from_secs(10)));
+
+        let (client, _manager) = session_client(sessions.clone(), events);
+
+        let (user_tx, mut user_rx) = mpsc::stream();
+        client.get_user_cons().set(5, user_tx);
+
+        client.connection_lost(DisconnectReason::PingFailed);
+        // Only the first Loss suspends the Session
+        client.connection_lost(DisconnectReason::SendFailed);
+        assert_eq!(
+            ServerEvent::ClientSuspended {
+                client_id: 123,
+                port: 13,
+                reason: DisconnectReason::PingFailed,
+            },
+            events_rx.recv().await.unwrap()
+        );
+
+        assert_eq!(
+            ServerEvent::ClientDisconnected {
+                client_id: 123,
+                port: 13,
+                reason: DisconnectReason::PingFailed,
+            },
+            events_rx.recv().await.unwrap()
+        );
+        assert_eq!(false, sessions.contains(7, 13));
+        assert_eq!(true, user_rx.recv().await.unwrap().is_close());
+        assert_eq!(true, client.get_user_cons().get_clone(5).is_none());
+    }
+
+    #[tokio::test]
+    async fn users_spread_across_connections() {
+        let (second_tx, mut second_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let (client, _, mut rx) =
+            test_client(&handshake::Config::new(13).with_group(7), Events::default());
+        client.add_connection(second_tx);
+        assert_eq!(Some(7), client.group());
+        assert_eq!(2, client.status().connections);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let limiter = crate::server::limits::PortLimiter::new(Default::default());
+        let mut user_sides = Vec::new();
+        for user_id in [1, 2] {
+            user_sides.push(
+                tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+                    .await
+                    .unwrap(),
+            );
+            let (server_side, _) = listener.accept().await.unwrap();
+            let permit = limiter
+                .acquire(server_side.peer_addr().unwrap().ip())
+                .unwrap();
+            client.new_con(user_id, server_side, permit);
+        }
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(MessageType::Connect, *first.get_header().get_kind());
+        assert_eq!(1, first.get_header().get_id());
+        let second = second_rx.recv().await.unwrap();
+        assert_eq!(MessageType::Connect, *second.get_header().get_kind());
+        assert_eq!(2, second.get_header().get_id());
+
+        // All the Messages for a User-Connection use the same Client-Connection
+        assert_eq!(
+            true,
+            client.close_user(2, CloseReason::with_text(CloseCode::Policy, "Test"))
+        );
+        assert_eq!(true, second_rx.recv().await.unwrap().is_close());
+        assert_eq!(true, rx.try_recv().is_err());
+    }
 }
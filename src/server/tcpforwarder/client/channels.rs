@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::message::Message;
+
+type Queue = tokio::sync::mpsc::UnboundedSender<Message>;
+
+/// The Queues of all the Client-Connections of a single Client
+///
+/// Every User-Connection is assigned to one of the Client-Connections, once
+/// the Client is notified about it, and all of its Messages are then send
+/// over that Connection to keep them in order
+#[derive(Debug)]
+pub struct Channels {
+    queues: Mutex<Vec<Queue>>,
+    assigned: Mutex<HashMap<u32, usize>>,
+}
+
+impl Channels {
+    /// Creates the Channels for a Client with only its first Connection
+    pub fn new(primary: Queue) -> Self {
+        Self {
+            queues: Mutex::new(vec![primary]),
+            assigned: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The Queue of the first Client-Connection
+    pub fn primary(&self) -> Queue {
+        self.queues.lock().unwrap()[0].clone()
+    }
+
+    /// Adds the Queue of another Client-Connection
+    pub fn add(&self, queue: Queue) {
+        self.queues.lock().unwrap().push(queue);
+    }
+
+    /// The Number of Client-Connections
+    pub fn len(&self) -> usize {
+        self.queues.lock().unwrap().len()
+    }
+
+    /// Assigns the User-Connection to the Client-Connection, that currently
+    /// carries the fewest User-Connections
+    ///
+    /// Returns:
+    /// The Queue of the selected Client-Connection
+    pub fn assign(&self, user_id: u32) -> Queue {
+        let queues = self.queues.lock().unwrap();
+        let mut assigned = self.assigned.lock().unwrap();
+
+        let mut load = vec![0; queues.len()];
+        for index in assigned.values() {
+            load[*index] += 1;
+        }
+        let index = (0..queues.len()).min_by_key(|i| load[*i]).unwrap_or(0);
+
+        assigned.insert(user_id, index);
+        queues[index].clone()
+    }
+
+    /// The Queue of the Client-Connection for the User-Connection, which is
+    /// the first one if it was never assigned
+    pub fn get(&self, user_id: u32) -> Queue {
+        let queues = self.queues.lock().unwrap();
+        let index = self
+            .assigned
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .copied()
+            .unwrap_or(0);
+        queues[index].clone()
+    }
+
+    /// Releases the User-Connection, once it was closed
+    pub fn release(&self, user_id: u32) {
+        self.assigned.lock().unwrap().remove(&user_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{MessageHeader, MessageType};
+
+    fn msg(id: u32) -> Message {
+        Message::new(MessageHeader::new(id, MessageType::EOF, 0), vec![])
+    }
+
+    #[test]
+    fn single_connection() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let channels = Channels::new(tx);
+
+        channels.assign(3).send(msg(3)).unwrap();
+        channels.get(3).send(msg(3)).unwrap();
+        channels.get(4).send(msg(4)).unwrap();
+
+        assert_eq!(1, channels.len());
+        assert_eq!(Ok(msg(3)), rx.try_recv());
+        assert_eq!(Ok(msg(3)), rx.try_recv());
+        assert_eq!(Ok(msg(4)), rx.try_recv());
+    }
+
+    #[test]
+    fn spread_connections() {
+        let (first_tx, mut first_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (second_tx, mut second_rx) = tokio::sync::mpsc::unbounded_channel();
+        let channels = Channels::new(first_tx);
+        channels.add(second_tx);
+
+        channels.assign(1).send(msg(1)).unwrap();
+        channels.assign(2).send(msg(2)).unwrap();
+        channels.get(2).send(msg(2)).unwrap();
+
+        assert_eq!(2, channels.len());
+        assert_eq!(Ok(msg(1)), first_rx.try_recv());
+        assert_eq!(Ok(msg(2)), second_rx.try_recv());
+        assert_eq!(Ok(msg(2)), second_rx.try_recv());
+
+        // The released Connection frees up its Client-Connection again
+        channels.release(2);
+        channels.assign(3).send(msg(3)).unwrap();
+        assert_eq!(Ok(msg(3)), second_rx.try_recv());
+    }
+}
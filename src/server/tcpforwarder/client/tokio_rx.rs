@@ -1,6 +1,10 @@
 use crate::connections::Connections;
-use crate::general::ConnectionReader;
-use crate::message::{Message, MessageHeader, MessageType};
+use crate::general::{ConnectionReader, Pinger, Sequencer};
+use crate::message::{
+    decompress, CloseCode, CloseReason, CodecError, Compression, Message, MessageCodec,
+    MessageHeader, MessageType, ReadBuffer,
+};
+use crate::metrics::Metrics;
 use crate::streams::mpsc;
 
 #[cfg(test)]
@@ -8,42 +12,107 @@ use crate::general::mocks::MockReader;
 
 #[derive(Debug)]
 pub enum ReceiveError {
-    ReadingCon(std::io::Error),
-    ParsingHeader([u8; 13]),
+    ReadingCon(CodecError),
 }
 
-impl From<std::io::Error> for ReceiveError {
-    fn from(other: std::io::Error) -> Self {
+impl std::fmt::Display for ReceiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReceiveError::ReadingCon(e) => write!(f, "Reading the Message: {}", e),
+        }
+    }
+}
+
+impl From<CodecError> for ReceiveError {
+    fn from(other: CodecError) -> Self {
         Self::ReadingCon(other)
     }
 }
 
+/// Records a Message of a User-Connection, once it was read completely, and
+/// sends an Ack for it if one is due
+fn acknowledge(
+    id: u32,
+    header: &MessageHeader,
+    sequencer: Option<&Sequencer>,
+    send_queue: &tokio::sync::mpsc::UnboundedSender<Message>,
+) {
+    if let Some(ack) = sequencer.and_then(|s| s.received(header)) {
+        if let Err(e) = send_queue.send(ack) {
+            error!("[{}] Sending Ack: {}", id, e);
+        }
+    }
+}
+
 /// Receives a single Message from the Client-Connection
-pub async fn receive<C>(
+#[allow(clippy::too_many_arguments)]
+pub async fn receive<C, M>(
     id: u32,
+    port: u16,
     read_con: &mut C,
     user_cons: &Connections<mpsc::StreamWriter<Message>>,
-    header_buf: &mut [u8; 13],
+    send_queue: &tokio::sync::mpsc::UnboundedSender<Message>,
+    pinger: &Pinger,
+    sequencer: Option<&Sequencer>,
+    compression: Option<Compression>,
+    metrics: &M,
+    codec: &mut MessageCodec,
+    buf: &mut ReadBuffer,
 ) -> Result<(), ReceiveError>
 where
     C: ConnectionReader + Send,
+    M: Metrics,
 {
-    read_con.read_full(header_buf).await?;
-
-    let header = match MessageHeader::deserialize(header_buf) {
-        Some(h) => h,
-        None => return Err(ReceiveError::ParsingHeader(*header_buf)),
-    };
+    let msg = read_con.read_msg(codec, buf).await?;
+    let header = msg.get_header().clone();
 
     match header.get_kind() {
-        MessageType::Data => {}
+        MessageType::Data | MessageType::Accept | MessageType::Reject => {}
         MessageType::Close => {
-            user_cons.remove(header.get_id());
+            let user_id = header.get_id();
+            acknowledge(id, &header, sequencer, send_queue);
+
+            // The Close is forwarded to the User-Stream, so that the Reason can
+            // decide how the User-Connection gets closed
+            if let Some(reason) = msg.close_reason() {
+                debug!("[{}][{}] Client closed Connection: {}", id, user_id, reason);
+                metrics.received_close(&reason);
+
+                if let Some(stream) = user_cons.get_clone(user_id) {
+                    let _ = stream.send(msg);
+                }
+            }
+
+            user_cons.remove(user_id);
             return Ok(());
         }
         MessageType::Heartbeat => {
             return Ok(());
         }
+        MessageType::Ack => {
+            if let Some(sequencer) = sequencer {
+                sequencer.acked(header.get_id(), msg.get_data());
+            }
+            return Ok(());
+        }
+        MessageType::Resume => {
+            if let Some(sequencer) = sequencer {
+                sequencer.resumed();
+            }
+            return Ok(());
+        }
+        MessageType::Ping => {
+            if let Err(e) = send_queue.send(Pinger::pong(msg.into_data())) {
+                error!("[{}] Sending Pong: {}", id, e);
+            }
+            return Ok(());
+        }
+        MessageType::Pong => {
+            if let Some(rtt) = pinger.received_pong(msg.get_data()) {
+                metrics.rtt(rtt);
+            }
+            return Ok(());
+        }
         _ => {
             error!(
                 "[{}][{}] Unexpected Operation: {:?}",
@@ -51,31 +120,45 @@ where
                 header.get_id(),
                 header.get_kind()
             );
-            read_con.drain(header.get_length() as usize).await;
             return Ok(());
         }
     };
 
     let user_id = header.get_id();
+    acknowledge(id, &header, sequencer, send_queue);
 
     // Forwarding the message to the actual user
     let stream = match user_cons.get_clone(user_id) {
         Some(s) => s,
-        None => {
-            // Removes this message and drain all the Data belonging to this message
-            // as well
-            read_con.drain(header.get_length() as usize).await;
+        None => return Ok(()),
+    };
+
+    let wire_length = codec.size(&header);
+    let msg = match decompress(compression, header, msg.into_data(), codec.max_size()) {
+        Ok(m) => m,
+        Err(e) => {
+            // Only the single User-Connection is closed, as the Connection to
+            // the Client itself is still intact
+            error!("[{}][{}] Decompressing Message: {}", id, user_id, e);
+            let reason = CloseReason::with_text(CloseCode::Error, e.to_string());
+            user_cons.remove(user_id);
+            let _ = stream.send(reason.clone().into_message(user_id));
+            if let Err(e) = send_queue.send(reason.into_message(user_id)) {
+                error!("[{}][{}] Sending Close Message: {}", id, user_id, e);
+            }
             return Ok(());
         }
     };
+    let length = msg.get_header().get_length();
 
-    let body_length = header.get_length() as usize;
-    let mut body_buf = vec![0; body_length];
-    if let Err(e) = read_con.read_full(&mut body_buf).await {
-        error!("[{}][{}] Reading Body from Client: {}", id, user_id, e);
+    metrics.received_msg();
+    metrics.recv_bytes(length);
+    metrics.recv_wire_bytes(wire_length as u64);
+    if *msg.get_header().get_kind() == MessageType::Data {
+        metrics.user_send_bytes(port, length);
     }
 
-    if let Err(e) = stream.send(Message::new(header, body_buf)) {
+    if let Err(e) = stream.send(msg) {
         error!("[{}][{}] Adding to User-Queue: {}", id, user_id, e);
     }
     Ok(())
@@ -84,6 +167,8 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::message::HeaderFormat;
+    use crate::metrics::Empty;
 
     #[tokio::test]
     async fn data_message() {
@@ -91,7 +176,7 @@ mod tests {
         let user_id = 15;
         let mut mock_con = MockReader::new();
         let user_cons = Connections::new();
-        let mut header_buf = [0u8; 13];
+        let mut codec = MessageCodec::new(HeaderFormat::V1);
 
         // Adding the test Message to the Connection
         mock_con.add_message(Message::new(
@@ -103,7 +188,21 @@ mod tests {
         let (client_tx, mut client_rx) = mpsc::stream();
         user_cons.set(user_id, client_tx);
 
-        let recv_result = receive(id, &mut mock_con, &user_cons, &mut header_buf).await;
+        let (queue_tx, _queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        let recv_result = receive(
+            id,
+            8080,
+            &mut mock_con,
+            &user_cons,
+            &queue_tx,
+            &Pinger::new(),
+            None,
+            None,
+            &Empty::new(),
+            &mut codec,
+            &mut ReadBuffer::new(),
+        )
+        .await;
 
         assert_eq!(true, recv_result.is_ok());
         assert_eq!(
@@ -114,4 +213,331 @@ mod tests {
             client_rx.recv().await
         );
     }
+
+    #[tokio::test]
+    async fn ping_message() {
+        let id = 13;
+        let mut mock_con = MockReader::new();
+        let user_cons = Connections::new();
+        let mut codec = MessageCodec::new(HeaderFormat::V1);
+
+        mock_con.add_message(Message::new(
+            MessageHeader::new(0, MessageType::Ping, 8),
+            vec![1; 8],
+        ));
+
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        let recv_result = receive(
+            id,
+            8080,
+            &mut mock_con,
+            &user_cons,
+            &queue_tx,
+            &Pinger::new(),
+            None,
+            None,
+            &Empty::new(),
+            &mut codec,
+            &mut ReadBuffer::new(),
+        )
+        .await;
+
+        assert_eq!(true, recv_result.is_ok());
+        assert_eq!(
+            Some(Message::new(
+                MessageHeader::new(0, MessageType::Pong, 8),
+                vec![1; 8]
+            )),
+            queue_rx.recv().await
+        );
+    }
+
+    #[tokio::test]
+    async fn close_message_with_reason() {
+        let id = 13;
+        let user_id = 15;
+        let mut mock_con = MockReader::new();
+        let user_cons = Connections::new();
+        let mut codec = MessageCodec::new(HeaderFormat::V1);
+
+        let close =
+            || CloseReason::with_text(CloseCode::ConnectFailed, "refused").into_message(user_id);
+        mock_con.add_message(close());
+
+        let (client_tx, mut client_rx) = mpsc::stream();
+        user_cons.set(user_id, client_tx);
+
+        let (queue_tx, _queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        let recv_result = receive(
+            id,
+            8080,
+            &mut mock_con,
+            &user_cons,
+            &queue_tx,
+            &Pinger::new(),
+            None,
+            None,
+            &Empty::new(),
+            &mut codec,
+            &mut ReadBuffer::new(),
+        )
+        .await;
+
+        assert_eq!(true, recv_result.is_ok());
+        assert_eq!(true, user_cons.get_clone(user_id).is_none());
+        assert_eq!(Ok(close()), client_rx.recv().await);
+    }
+
+    #[tokio::test]
+    async fn sequenced_messages() {
+        let user_id = 15;
+        let mut mock_con = MockReader::new();
+        let user_cons = Connections::new();
+        let mut codec = MessageCodec::new(HeaderFormat::V1);
+
+        mock_con.add_message(Message::new(
+            MessageHeader::new(user_id, MessageType::Data, 10),
+            vec![7; 10],
+        ));
+        mock_con.add_message(CloseReason::new(CloseCode::Normal).into_message(user_id));
+        mock_con.add_message(Sequencer::ack(user_id, 1));
+        mock_con.add_message(Message::new(
+            MessageHeader::new(0, MessageType::Resume, 0),
+            vec![],
+        ));
+
+        let sequencer = Sequencer::new();
+        sequencer.sent(&Message::new(
+            MessageHeader::new(user_id, MessageType::Data, 1),
+            vec![1],
+        ));
+        let resumed = sequencer.expect_resume();
+
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        for _ in 0..4 {
+            let recv_result = receive(
+                13,
+                8080,
+                &mut mock_con,
+                &user_cons,
+                &queue_tx,
+                &Pinger::new(),
+                Some(&sequencer),
+                None,
+                &Empty::new(),
+                &mut codec,
+                &mut ReadBuffer::new(),
+            )
+            .await;
+            assert_eq!(true, recv_result.is_ok());
+        }
+
+        assert_eq!(Some(Sequencer::ack(user_id, 2)), queue_rx.recv().await);
+        assert_eq!(0, sequencer.unacked().len());
+        assert_eq!(Ok(()), resumed.await);
+    }
+
+    #[tokio::test]
+    async fn data_message_metrics() {
+        #[derive(Debug, Default)]
+        struct PortMetrics {
+            send: std::sync::Mutex<Vec<(u16, u64)>>,
+        }
+        impl Metrics for PortMetrics {
+            fn user_send_bytes(&self, port: u16, send: u64) {
+                self.send.lock().unwrap().push((port, send));
+            }
+        }
+
+        let user_id = 15;
+        let mut mock_con = MockReader::new();
+        let user_cons = Connections::new();
+        let mut codec = MessageCodec::new(HeaderFormat::V1);
+
+        mock_con.add_message(Message::new(
+            MessageHeader::new(user_id, MessageType::Data, 10),
+            vec![7; 10],
+        ));
+
+        let (client_tx, _client_rx) = mpsc::stream();
+        user_cons.set(user_id, client_tx);
+
+        let metrics = PortMetrics::default();
+        let (queue_tx, _queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        let recv_result = receive(
+            13,
+            8080,
+            &mut mock_con,
+            &user_cons,
+            &queue_tx,
+            &Pinger::new(),
+            None,
+            None,
+            &metrics,
+            &mut codec,
+            &mut ReadBuffer::new(),
+        )
+        .await;
+
+        assert_eq!(true, recv_result.is_ok());
+        assert_eq!(vec![(8080, 10)], *metrics.send.lock().unwrap());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[tokio::test]
+    async fn compressed_data_message() {
+        let user_id = 15;
+        let mut mock_con = MockReader::new();
+        let user_cons = Connections::new();
+        let mut codec = MessageCodec::new(HeaderFormat::V1);
+
+        let msg = Message::new(
+            MessageHeader::new(user_id, MessageType::Data, 256),
+            vec![7; 256],
+        );
+        mock_con
+            .add_message(Compression::Zstd.compress(msg.clone(), MessageCodec::DEFAULT_MAX_SIZE));
+        mock_con
+            .add_message(Compression::Zstd.compress(msg.clone(), MessageCodec::DEFAULT_MAX_SIZE));
+
+        let (client_tx, mut client_rx) = mpsc::stream();
+        user_cons.set(user_id, client_tx);
+
+        let (queue_tx, _queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        let recv_result = receive(
+            13,
+            8080,
+            &mut mock_con,
+            &user_cons,
+            &queue_tx,
+            &Pinger::new(),
+            None,
+            Some(Compression::Zstd),
+            &Empty::new(),
+            &mut codec,
+            &mut ReadBuffer::new(),
+        )
+        .await;
+
+        assert_eq!(true, recv_result.is_ok());
+        assert_eq!(Ok(msg), client_rx.recv().await);
+
+        // Compressed Messages can only be received once it was negotiated,
+        // otherwise only the User-Connection is closed
+        let recv_result = receive(
+            13,
+            8080,
+            &mut mock_con,
+            &user_cons,
+            &queue_tx,
+            &Pinger::new(),
+            None,
+            None,
+            &Empty::new(),
+            &mut codec,
+            &mut ReadBuffer::new(),
+        )
+        .await;
+        assert_eq!(true, recv_result.is_ok());
+        assert_eq!(
+            Some(CloseCode::Error),
+            client_rx
+                .recv()
+                .await
+                .ok()
+                .and_then(|m| m.close_reason())
+                .map(|r| r.code())
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[tokio::test]
+    async fn corrupt_compressed_message() {
+        let mut mock_con = MockReader::new();
+        let user_cons = Connections::new();
+        let mut codec = MessageCodec::new(HeaderFormat::V1);
+
+        let valid = Message::new(MessageHeader::new(16, MessageType::Data, 256), vec![7; 256]);
+        mock_con.add_message(Message::new(
+            MessageHeader::new(15, MessageType::Data, 16).with_compressed(true),
+            vec![0xff; 16],
+        ));
+        mock_con
+            .add_message(Compression::Zstd.compress(valid.clone(), MessageCodec::DEFAULT_MAX_SIZE));
+
+        let (corrupt_tx, mut corrupt_rx) = mpsc::stream();
+        user_cons.set(15, corrupt_tx);
+        let (valid_tx, mut valid_rx) = mpsc::stream();
+        user_cons.set(16, valid_tx);
+
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        for _ in 0..2 {
+            let recv_result = receive(
+                13,
+                8080,
+                &mut mock_con,
+                &user_cons,
+                &queue_tx,
+                &Pinger::new(),
+                None,
+                Some(Compression::Zstd),
+                &Empty::new(),
+                &mut codec,
+                &mut ReadBuffer::new(),
+            )
+            .await;
+            assert_eq!(true, recv_result.is_ok());
+        }
+
+        // Only the User-Connection of the corrupt Message is closed on both
+        // Sides
+        let close = |msg: Option<Message>| msg.and_then(|m| m.close_reason()).map(|r| r.code());
+        assert_eq!(Some(CloseCode::Error), close(corrupt_rx.recv().await.ok()));
+        assert_eq!(Some(CloseCode::Error), close(queue_rx.recv().await));
+        assert_eq!(true, user_cons.get_clone(15).is_none());
+
+        assert_eq!(Ok(valid), valid_rx.recv().await);
+        assert_eq!(true, user_cons.get_clone(16).is_some());
+    }
+
+    #[tokio::test]
+    async fn compact_headers() {
+        let user_id = 300;
+        let mut mock_con = MockReader::new();
+        let user_cons = Connections::new();
+        let mut codec = MessageCodec::new(HeaderFormat::V2);
+
+        let msg = || {
+            Message::new(
+                MessageHeader::new(user_id, MessageType::Data, 200),
+                vec![7; 200],
+            )
+        };
+        mock_con.add_formatted(msg(), HeaderFormat::V2);
+        mock_con.add_formatted(msg(), HeaderFormat::V2);
+
+        let (client_tx, mut client_rx) = mpsc::stream();
+        user_cons.set(user_id, client_tx);
+
+        let (queue_tx, _queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        for _ in 0..2 {
+            let recv_result = receive(
+                13,
+                8080,
+                &mut mock_con,
+                &user_cons,
+                &queue_tx,
+                &Pinger::new(),
+                None,
+                None,
+                &Empty::new(),
+                &mut codec,
+                &mut ReadBuffer::new(),
+            )
+            .await;
+
+            assert_eq!(true, recv_result.is_ok());
+            assert_eq!(Ok(msg()), client_rx.recv().await);
+        }
+    }
 }
@@ -1,31 +1,283 @@
-use crate::{general::ConnectionWriter, message::Message};
+use crate::{
+    connections::Connections,
+    general::{recv_batch, reject_invalid, ConnectionWriter, Sequencer},
+    message::{original_length, CodecError, Compression, Message, MessageCodec, MessageType},
+    metrics::Metrics,
+    streams::mpsc,
+};
 
 #[derive(Debug)]
 pub enum SendError {
     QueueReceive,
-    IO(std::io::Error),
+    Encoding(CodecError),
 }
 
-impl From<std::io::Error> for SendError {
-    fn from(other: std::io::Error) -> Self {
-        Self::IO(other)
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::QueueReceive => write!(f, "The Queue has been closed"),
+            SendError::Encoding(e) => write!(f, "Writing the Messages: {}", e),
+        }
     }
 }
 
-pub async fn send<C>(
+impl From<CodecError> for SendError {
+    fn from(other: CodecError) -> Self {
+        Self::Encoding(other)
+    }
+}
+
+/// Sends all the Messages, that are waiting in the Queue, to the
+/// Client-Connection at once
+///
+/// Params:
+/// * port: The Port of the Client, used for the Metrics
+/// * write_con: The Connection to the Client
+/// * queue: The Queue of Messages for the Client
+/// * user_cons: The User-Connections of the Client, which are closed if their
+///   Messages can not be send
+/// * batch: The Buffer for the Messages, which is empty again afterwards
+/// * codec: The Codec for the Messages on the Connection
+/// * head_buf: The Buffer used for serializing the Headers
+/// * sequencer: Keeps track of the Messages of the Session, if there is one
+/// * compression: The Compression negotiated with the Client, if any
+/// * metrics: The Metrics-Collector to use
+#[allow(clippy::too_many_arguments)]
+pub async fn send<C, M>(
+    port: u16,
     write_con: &mut C,
     queue: &mut tokio::sync::mpsc::UnboundedReceiver<Message>,
-    header_buf: &mut [u8; 13],
+    user_cons: &Connections<mpsc::StreamWriter<Message>>,
+    batch: &mut Vec<Message>,
+    codec: &MessageCodec,
+    head_buf: &mut Vec<u8>,
+    sequencer: Option<&Sequencer>,
+    compression: Option<Compression>,
+    metrics: &M,
 ) -> Result<(), SendError>
 where
     C: ConnectionWriter + Send,
+    M: Metrics,
 {
-    let msg = match queue.recv().await {
-        Some(m) => m,
-        None => return Err(SendError::QueueReceive),
-    };
+    batch.clear();
+    if !recv_batch(queue, batch).await {
+        return Err(SendError::QueueReceive);
+    }
+    reject_invalid(batch, codec, user_cons);
+    // This needs to be recorded before writing, as the Messages would be lost
+    // if the Task is stopped while writing them
+    if let Some(sequencer) = sequencer {
+        for msg in batch.iter() {
+            sequencer.sent(msg);
+        }
+    }
+    if let Some(compression) = compression {
+        for msg in batch.iter_mut() {
+            *msg = compression.compress(msg.clone(), codec.max_size());
+        }
+    }
 
-    write_con.write_msg(&msg, header_buf).await?;
+    write_con.write_msgs(batch, codec, head_buf).await?;
+
+    for msg in batch.drain(..) {
+        let length = original_length(&msg);
+        metrics.send_msg();
+        metrics.send_bytes(length);
+        metrics.send_wire_bytes(codec.size(msg.get_header()) as u64);
+        if *msg.get_header().get_kind() == MessageType::Data {
+            metrics.user_recv_bytes(port, length);
+        }
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::mocks;
+    use crate::message::{HeaderFormat, MessageHeader};
+    use crate::metrics::Empty;
+
+    #[tokio::test]
+    async fn send_waiting_messages() {
+        let mut mock_connection = mocks::MockWriter::new();
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut batch = Vec::new();
+        let mut head_buf = Vec::new();
+        let sequencer = Sequencer::new();
+
+        let data = || Message::new(MessageHeader::new(12, MessageType::Data, 2), vec![2; 2]);
+        queue_tx.send(data()).unwrap();
+        queue_tx.send(data()).unwrap();
+
+        assert_eq!(
+            true,
+            send(
+                80,
+                &mut mock_connection,
+                &mut queue_rx,
+                &Connections::new(),
+                &mut batch,
+                &MessageCodec::new(HeaderFormat::V1),
+                &mut head_buf,
+                Some(&sequencer),
+                None,
+                &Empty::new()
+            )
+            .await
+            .is_ok()
+        );
+
+        assert_eq!(true, batch.is_empty());
+        assert_eq!(4, mock_connection.chunks().len());
+        assert_eq!(vec![data(), data()], sequencer.unacked());
+    }
+
+    #[tokio::test]
+    async fn send_invalid_message() {
+        let mut mock_connection = mocks::MockWriter::new();
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        let sequencer = Sequencer::new();
+        let codec = MessageCodec::new(HeaderFormat::V2).with_max_size(128);
+        let user_cons = Connections::new();
+        let (user_tx, mut user_rx) = mpsc::stream();
+        user_cons.set(13, user_tx);
+
+        let data = || Message::new(MessageHeader::new(12, MessageType::Data, 2), vec![2; 2]);
+        queue_tx.send(data()).unwrap();
+        queue_tx
+            .send(Message::new(
+                MessageHeader::new(13, MessageType::Data, 129),
+                vec![2; 129],
+            ))
+            .unwrap();
+
+        assert_eq!(
+            true,
+            send(
+                80,
+                &mut mock_connection,
+                &mut queue_rx,
+                &user_cons,
+                &mut Vec::new(),
+                &codec,
+                &mut Vec::new(),
+                Some(&sequencer),
+                None,
+                &Empty::new()
+            )
+            .await
+            .is_ok()
+        );
+
+        // Only the User-Connection of the invalid Message is closed, which the
+        // Session would also resend instead of the invalid Message
+        let unacked = sequencer.unacked();
+        assert_eq!(2, unacked.len());
+        assert_eq!(true, unacked.contains(&data()));
+        let close = unacked
+            .iter()
+            .find(|m| m.get_header().get_id() == 13)
+            .unwrap();
+        assert_eq!(
+            Some(crate::message::CloseCode::Error),
+            close.close_reason().map(|r| r.code())
+        );
+
+        // The User-Connection is also closed on the Server
+        assert_eq!(true, user_cons.get_clone(13).is_none());
+        assert_eq!(
+            Some(crate::message::CloseCode::Error),
+            user_rx
+                .recv()
+                .await
+                .ok()
+                .and_then(|m| m.close_reason())
+                .map(|r| r.code())
+        );
+    }
+
+    #[tokio::test]
+    async fn send_closed_queue() {
+        let mut mock_connection = mocks::MockWriter::new();
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        drop(queue_tx);
+
+        assert_eq!(
+            true,
+            send(
+                80,
+                &mut mock_connection,
+                &mut queue_rx,
+                &Connections::new(),
+                &mut Vec::new(),
+                &MessageCodec::new(HeaderFormat::V2),
+                &mut Vec::new(),
+                None,
+                None,
+                &Empty::new()
+            )
+            .await
+            .is_err()
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[tokio::test]
+    async fn send_compressed() {
+        #[derive(Debug, Default)]
+        struct ByteMetrics {
+            send: std::sync::Mutex<Vec<(u64, u64)>>,
+        }
+        impl Metrics for ByteMetrics {
+            fn send_bytes(&self, send: u64) {
+                self.send.lock().unwrap().push((send, 0));
+            }
+            fn send_wire_bytes(&self, send: u64) {
+                self.send.lock().unwrap().last_mut().unwrap().1 = send;
+            }
+        }
+
+        let mut mock_connection = mocks::MockWriter::new();
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+        let sequencer = Sequencer::new();
+        let metrics = ByteMetrics::default();
+
+        let data = || Message::new(MessageHeader::new(12, MessageType::Data, 256), vec![2; 256]);
+        queue_tx.send(data()).unwrap();
+
+        assert_eq!(
+            true,
+            send(
+                80,
+                &mut mock_connection,
+                &mut queue_rx,
+                &Connections::new(),
+                &mut Vec::new(),
+                &MessageCodec::new(HeaderFormat::V2),
+                &mut Vec::new(),
+                Some(&sequencer),
+                Some(Compression::Zstd),
+                &metrics
+            )
+            .await
+            .is_ok()
+        );
+
+        let (header, _) = HeaderFormat::V2
+            .deserialize(&mock_connection.chunks()[0])
+            .unwrap();
+        assert_eq!(true, header.is_compressed());
+        // The Session still keeps the original Message, in case it has to be
+        // send again
+        assert_eq!(vec![data()], sequencer.unacked());
+        assert_eq!(
+            vec![(
+                256,
+                (HeaderFormat::V2.size(&header) as u64) + header.get_length()
+            )],
+            *metrics.send.lock().unwrap()
+        );
+    }
+}
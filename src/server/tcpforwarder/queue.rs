@@ -0,0 +1,221 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use super::{ClientManager, TCPClient};
+use crate::metrics::Metrics;
+use crate::server::limits::Permit;
+
+/// The Configuration for holding User-Connections while there is no Client
+/// available to handle them, for example while a Client is reconnecting
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaitQueue {
+    size: usize,
+    timeout: Duration,
+}
+
+impl WaitQueue {
+    /// Creates a new Configuration
+    ///
+    /// # Params:
+    /// * `size`: The maximum Number of Users that can wait at the same time on a single Port
+    /// * `timeout`: How long a single User waits for a Client before the Connection is closed
+    pub fn new(size: usize, timeout: Duration) -> Self {
+        Self { size, timeout }
+    }
+
+    /// The maximum Number of Users that can wait at the same time
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    /// How long a single User waits for a Client
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+/// The Users of a single Forwarder that are currently waiting for a Client
+#[derive(Debug)]
+pub struct UserQueue {
+    config: WaitQueue,
+    waiting: Arc<AtomicUsize>,
+}
+
+impl UserQueue {
+    /// Creates a new empty Queue
+    pub fn new(config: WaitQueue) -> Self {
+        Self {
+            config,
+            waiting: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// The Number of Users currently waiting
+    #[cfg(test)]
+    fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::SeqCst)
+    }
+
+    /// Holds the User-Connection until a Client becomes available and then
+    /// hands it over to that Client. The Connection is closed if no Client
+    /// became available within the configured Timeout.
+    ///
+    /// The Permit for the Connection is kept while the User is waiting and then
+    /// handed over to the Client as well
+    ///
+    /// # Returns
+    /// * `true` if the User was added to the Queue
+    /// * `false` if the Queue was already full and the Connection was closed
+    pub fn enqueue<M>(
+        &self,
+        port: u16,
+        user_id: u32,
+        con: tokio::net::TcpStream,
+        permit: Permit,
+        clients: Arc<ClientManager<TCPClient<M>>>,
+        metrics: Arc<M>,
+    ) -> bool
+    where
+        M: Metrics + Send + Sync + 'static,
+    {
+        let size = self.config.size;
+        let previous =
+            match self
+                .waiting
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+                    if current < size {
+                        Some(current + 1)
+                    } else {
+                        None
+                    }
+                }) {
+                Ok(p) => p,
+                Err(_) => return false,
+            };
+        metrics.queue_depth(port, previous + 1);
+
+        let user_ip = permit.ip();
+        let waiting = self.waiting.clone();
+        let timeout = self.config.timeout;
+        tokio::task::spawn(async move {
+            let client = clients.wait_for(&user_ip, timeout).await;
+            let previous = waiting.fetch_sub(1, Ordering::SeqCst);
+            metrics.queue_depth(port, previous - 1);
+
+            match client {
+                Some(c) => c.new_con(user_id, con, permit),
+                None => {
+                    error!(
+                        "[{}][{}] No Client became available within {:?}",
+                        port, user_id, timeout
+                    );
+                    metrics.user_rejected(port);
+                }
+            };
+        });
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Empty;
+    use crate::server::limits::{Limits, PortLimiter};
+    use std::net::IpAddr;
+
+    #[derive(Debug, Default)]
+    struct QueueMetrics {
+        rejected: AtomicUsize,
+        depths: std::sync::Mutex<Vec<usize>>,
+    }
+
+    impl Metrics for QueueMetrics {
+        fn user_rejected(&self, _port: u16) {
+            self.rejected.fetch_add(1, Ordering::SeqCst);
+        }
+        fn queue_depth(&self, _port: u16, depth: usize) {
+            self.depths.lock().unwrap().push(depth);
+        }
+    }
+
+    async fn user_connection(listener: &tokio::net::TcpListener) -> tokio::net::TcpStream {
+        let addr = listener.local_addr().unwrap();
+        let _user = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (con, _) = listener.accept().await.unwrap();
+        con
+    }
+
+    #[tokio::test]
+    async fn rejects_when_full() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let clients = Arc::new(ClientManager::new());
+        let queue = UserQueue::new(WaitQueue::new(1, Duration::from_secs(10)));
+        let user_ip = IpAddr::from([127, 0, 0, 1]);
+        let limiter = PortLimiter::new(Limits::new());
+
+        let metrics = Arc::new(Empty::new());
+
+        let con = user_connection(&listener).await;
+        assert_eq!(
+            true,
+            queue.enqueue(
+                13,
+                1,
+                con,
+                limiter.acquire(user_ip).unwrap(),
+                clients.clone(),
+                metrics.clone()
+            )
+        );
+        assert_eq!(1, queue.waiting());
+
+        let con = user_connection(&listener).await;
+        assert_eq!(
+            false,
+            queue.enqueue(
+                13,
+                2,
+                con,
+                limiter.acquire(user_ip).unwrap(),
+                clients,
+                metrics
+            )
+        );
+        assert_eq!(1, queue.waiting());
+    }
+
+    #[tokio::test]
+    async fn removed_after_timeout() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let clients = Arc::new(ClientManager::new());
+        let queue = UserQueue::new(WaitQueue::new(1, Duration::from_millis(10)));
+        let user_ip = IpAddr::from([127, 0, 0, 1]);
+        let limiter = PortLimiter::new(Limits::new());
+
+        let metrics = Arc::new(QueueMetrics::default());
+
+        let con = user_connection(&listener).await;
+        assert_eq!(
+            true,
+            queue.enqueue(
+                13,
+                1,
+                con,
+                limiter.acquire(user_ip).unwrap(),
+                clients,
+                metrics.clone()
+            )
+        );
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(0, queue.waiting());
+        assert_eq!(1, metrics.rejected.load(Ordering::SeqCst));
+        assert_eq!(vec![1, 0], *metrics.depths.lock().unwrap());
+    }
+}
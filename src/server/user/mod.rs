@@ -1,5 +1,9 @@
 mod recv;
+mod reset;
 mod send;
+mod tracker;
 
 pub use recv::recv;
+pub use reset::{reset, reset_split};
 pub use send::send;
+pub use tracker::UserTracker;
@@ -1,7 +1,9 @@
-use crate::general::ConnectionReader;
-use crate::message::{Message, MessageHeader, MessageType};
+use std::sync::Arc;
 
-const BUFFER_SIZE: usize = 4096;
+use crate::general::{ChunkSize, ChunkSizer, ConnectionReader, Throttle};
+use crate::message::{CloseCode, CloseReason, Message, MessageHeader, MessageType, ReadBuffer};
+use crate::metrics::Metrics;
+use crate::server::user::UserTracker;
 
 /// Reads from a new User-Connection and sends it to the client
 ///
@@ -10,45 +12,66 @@ const BUFFER_SIZE: usize = 4096;
 /// * id: The ID of the user-connection
 /// * con: The User-Connection
 /// * send_queue: The Queue for requests going out to the Client
-/// * user_cons: The User-Connections belonging to this Client
-pub async fn recv<F, C>(
+/// * tracker: The Tracker for this User-Connection
+/// * throttle: Limits the Rate at which Data is read from the User
+/// * chunk_size: The Size of the Chunks in which Data is read from the User
+/// * max_size: The maximum Size of the Data of a single Message
+/// * close_user: Closes the Connection with the given Reason, once the User
+///   is done
+#[allow(clippy::too_many_arguments)]
+pub async fn recv<F, Fut, C, M>(
     client_id: u32,
     user_id: u32,
     mut con: C,
     send_queue: tokio::sync::mpsc::UnboundedSender<Message>,
+    tracker: Arc<UserTracker<M>>,
+    throttle: Throttle,
+    chunk_size: ChunkSize,
+    max_size: usize,
     close_user: F,
 ) where
     C: ConnectionReader + Send,
-    F: std::future::Future<Output = ()>,
+    M: Metrics,
+    F: FnOnce(CloseReason) -> Fut,
+    Fut: std::future::Future<Output = ()>,
 {
+    let mut reason = CloseReason::new(CloseCode::Normal);
+    let mut buf = ReadBuffer::new();
+    let mut sizer = ChunkSizer::new(chunk_size);
+
     // Reads and forwards all the data from the socket to the client
     loop {
-        let mut buf = vec![0; BUFFER_SIZE];
-
         // Try to read data from the user
         //
         // this may still fail with `WouldBlock` if the readiness event is
         // a false positive.
-        match con.read(&mut buf).await {
-            Ok(n) => {
-                let message_type = if n > 0 {
-                    MessageType::Data
-                } else {
-                    MessageType::EOF
-                };
+        match con.read_into(&mut buf, sizer.current()).await {
+            Ok(data) => {
+                let n = data.len();
+                sizer.record(n);
 
-                buf.truncate(n);
+                tracker.received(n as u64);
 
-                // Package the Users-Data in a new custom-message
-                let header = MessageHeader::new(user_id, message_type, n as u64);
-                let msg = Message::new(header, buf);
+                // Waits until the Data is allowed to be forwarded, which
+                // also stops reading any more Data from the User until then
+                throttle.acquire(n as u64).await;
 
-                // Puts the message in the queue to be send to the client
-                if let Err(e) = send_queue.send(msg) {
+                // Package the Users-Data in new custom-messages, that each
+                // fit into a single Message of the Codec, and puts them in
+                // the queue to be send to the client
+                let queued = if n > 0 {
+                    Message::split_data(user_id, data, max_size)
+                        .try_for_each(|msg| send_queue.send(msg))
+                } else {
+                    let header = MessageHeader::new(user_id, MessageType::EOF, 0);
+                    send_queue.send(Message::new(header, data))
+                };
+                if let Err(e) = queued {
                     error!(
                         "[{}][{}] Forwarding message to client: {}",
                         client_id, user_id, e
                     );
+                    reason = CloseReason::with_text(CloseCode::Error, e.to_string());
                     break;
                 }
 
@@ -59,28 +82,35 @@ pub async fn recv<F, C>(
             Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
             Err(e) => {
                 error!("[{}][{}] Reading from User-Con: {}", client_id, user_id, e);
+                reason = CloseReason::with_text(CloseCode::Error, e.to_string());
                 break;
             }
         }
     }
 
     // This then actually closes the Connection
-    close_user.await;
+    close_user(reason).await;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::general::mocks::MockReader;
+    use crate::message::MessageCodec;
+    use crate::metrics::{ConnectionLabels, Empty};
 
     #[tokio::test]
     async fn valid_read() {
         let mut reader = MockReader::new();
-        reader.add_bytes(&vec![0, 1, 2, 3, 4, 5, 6]);
+        reader.add_bytes(&[0, 1, 2, 3, 4, 5, 6]);
         reader.close();
 
         let called = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
-        async fn close_con(tmp: std::sync::Arc<std::sync::atomic::AtomicBool>) {
+        async fn close_con(
+            tmp: std::sync::Arc<std::sync::atomic::AtomicBool>,
+            reason: CloseReason,
+        ) {
+            assert_eq!(CloseReason::new(CloseCode::Normal), reason);
             tmp.store(true, std::sync::atomic::Ordering::SeqCst);
         }
 
@@ -88,12 +118,24 @@ mod tests {
 
         let client_id = 12;
         let user_id = 5;
+        let tracker = UserTracker::open(
+            ConnectionLabels {
+                client_id,
+                port: 8080,
+                connection_id: user_id,
+            },
+            Arc::new(Empty::new()),
+        );
         recv(
             client_id,
             user_id,
             reader,
             queue_tx,
-            close_con(called.clone()),
+            Arc::new(tracker),
+            Throttle::new(),
+            ChunkSize::default(),
+            MessageCodec::DEFAULT_MAX_SIZE,
+            |reason| close_con(called.clone(), reason),
         )
         .await;
 
@@ -113,4 +155,45 @@ mod tests {
         );
         assert_eq!(true, called.load(std::sync::atomic::Ordering::SeqCst));
     }
+
+    #[tokio::test]
+    async fn adaptive_read() {
+        let mut reader = MockReader::new();
+        reader.add_bytes(&[1; 14]);
+        reader.close();
+
+        let (queue_tx, mut queue_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let tracker = UserTracker::open(
+            ConnectionLabels {
+                client_id: 12,
+                port: 8080,
+                connection_id: 5,
+            },
+            Arc::new(Empty::new()),
+        );
+        recv(
+            12,
+            5,
+            reader,
+            queue_tx,
+            Arc::new(tracker),
+            Throttle::new(),
+            ChunkSize::adaptive(2, 8),
+            MessageCodec::DEFAULT_MAX_SIZE,
+            |_| async {},
+        )
+        .await;
+
+        // Every Read filled the entire Chunk, so it kept growing
+        for length in [2, 4, 8] {
+            assert_eq!(
+                Some(Message::new(
+                    MessageHeader::new(5, MessageType::Data, length as u64),
+                    vec![1; length]
+                )),
+                queue_rx.recv().await
+            );
+        }
+    }
 }
@@ -0,0 +1,33 @@
+/// Closes the User-Connection with a RST instead of a FIN, which signals to
+/// the User that the Connection was aborted instead of being finished normally
+pub fn reset(con: tokio::net::TcpStream) {
+    if let Err(e) = con.set_linger(Some(std::time::Duration::from_secs(0))) {
+        error!("Setting Linger for Reset: {}", e);
+    }
+    drop(con);
+}
+
+/// Closes a User-Connection, that was already split into its two Halves, with
+/// a RST instead of a FIN
+///
+/// Params:
+/// * write_con: The Write-Half of the User-Connection
+/// * recv_task: The Task that is still reading from the Read-Half of the
+///   User-Connection
+pub fn reset_split(
+    write_con: tokio::net::tcp::OwnedWriteHalf,
+    recv_task: tokio::task::JoinHandle<()>,
+) {
+    if let Err(e) = write_con
+        .as_ref()
+        .set_linger(Some(std::time::Duration::from_secs(0)))
+    {
+        error!("Setting Linger for Reset: {}", e);
+    }
+
+    // The Socket is only closed once both Halves are dropped, which is when
+    // the RST is actually send. Forgetting the Write-Half makes sure that no
+    // FIN is send before that
+    recv_task.abort();
+    write_con.forget();
+}
@@ -1,18 +1,31 @@
-use crate::general::ConnectionWriter;
-use crate::message::Message;
+use crate::general::{ConnectionWriter, Throttle};
+use crate::message::{CloseReason, Message};
+use crate::metrics::Metrics;
+use crate::server::user::UserTracker;
 use crate::streams::{error::RecvError, mpsc};
 
-/// Returns:
-/// * `true` if everything worked fine
-/// * `false` if an error was encountered
-async fn send_single<C>(
+/// The Outcome of forwarding a single Message to the User
+#[derive(Debug, PartialEq)]
+enum Forwarded {
+    /// The Message was send to the User
+    Sent,
+    /// The Queue was closed or the User-Connection failed
+    Stopped,
+    /// The Client closed the Connection, with the given Reason
+    Closed(Option<CloseReason>),
+}
+
+async fn send_single<C, M>(
     client_id: u32,
     user_id: u32,
     con: &mut C,
     queue: &mut mpsc::StreamReader<Message>,
-) -> bool
+    tracker: &UserTracker<M>,
+    throttle: &Throttle,
+) -> Forwarded
 where
     C: ConnectionWriter + Send,
+    M: Metrics,
 {
     let msg = match queue.recv().await {
         Ok(m) => m,
@@ -20,16 +33,22 @@ where
             if e != RecvError::Closed {
                 error!("[{}][{}] Receiving from Queue: {}", client_id, user_id, e);
             }
-            return false;
+            return Forwarded::Stopped;
         }
     };
 
+    if msg.is_close() {
+        return Forwarded::Closed(msg.close_reason());
+    }
+
     let data = msg.get_data();
+    throttle.acquire(data.len() as u64).await;
     if let Err(e) = con.write_full(data).await {
         error!("[{}][{}] Sending to User: {}", client_id, user_id, e);
-        return false;
+        return Forwarded::Stopped;
     }
-    true
+    tracker.sent(data.len() as u64);
+    Forwarded::Sent
 }
 
 /// Reads messages from the Client for this User and sends them to the User
@@ -39,18 +58,30 @@ where
 /// * user_id: The ID of the User for this connection
 /// * con: The User-Connection
 /// * queue: The Queue for messages that need to be send to the user
-pub async fn send<C>(
+/// * tracker: The Tracker for this User-Connection
+/// * throttle: Limits the Rate at which Data is send to the User
+///
+/// Returns:
+/// The Reason with which the Client closed the Connection, if it was closed
+/// by the Client and the Client provided a Reason
+pub async fn send<C, M>(
     client_id: u32,
     user_id: u32,
-    mut con: C,
+    con: &mut C,
     mut queue: mpsc::StreamReader<Message>,
-) where
+    tracker: &UserTracker<M>,
+    throttle: Throttle,
+) -> Option<CloseReason>
+where
     C: ConnectionWriter + Send,
+    M: Metrics,
 {
     loop {
-        if !send_single(client_id, user_id, &mut con, &mut queue).await {
-            return;
-        }
+        match send_single(client_id, user_id, con, &mut queue, tracker, &throttle).await {
+            Forwarded::Sent => {}
+            Forwarded::Stopped => return None,
+            Forwarded::Closed(reason) => return reason,
+        };
     }
 }
 
@@ -59,9 +90,21 @@ mod tests {
     use super::*;
     use crate::{
         general::mocks::MockWriter,
-        message::{MessageHeader, MessageType},
+        message::{CloseCode, MessageHeader, MessageType},
+        metrics::{ConnectionLabels, Empty},
     };
 
+    fn tracker() -> UserTracker<Empty> {
+        UserTracker::open(
+            ConnectionLabels {
+                client_id: 1,
+                port: 8080,
+                connection_id: 10,
+            },
+            std::sync::Arc::new(Empty::new()),
+        )
+    }
+
     #[tokio::test]
     async fn valid_send_single() {
         let mut mock_writer = MockWriter::new();
@@ -75,10 +118,48 @@ mod tests {
             .unwrap();
 
         assert_eq!(
-            true,
-            send_single(1, 10, &mut mock_writer, &mut queue_rx).await
+            Forwarded::Sent,
+            send_single(
+                1,
+                10,
+                &mut mock_writer,
+                &mut queue_rx,
+                &tracker(),
+                &Throttle::new()
+            )
+            .await
         );
 
         assert_eq!(vec![vec![0, 1, 2, 3, 4]], mock_writer.chunks());
     }
+
+    #[tokio::test]
+    async fn send_closed_with_reason() {
+        let mut mock_writer = MockWriter::new();
+        let (queue_tx, queue_rx) = mpsc::stream();
+
+        queue_tx
+            .send(Message::new(
+                MessageHeader::new(10, MessageType::Data, 2),
+                vec![0, 1],
+            ))
+            .unwrap();
+        queue_tx
+            .send(CloseReason::new(CloseCode::Error).into_message(10))
+            .unwrap();
+
+        assert_eq!(
+            Some(CloseReason::new(CloseCode::Error)),
+            send(
+                1,
+                10,
+                &mut mock_writer,
+                queue_rx,
+                &tracker(),
+                Throttle::new()
+            )
+            .await
+        );
+        assert_eq!(vec![vec![0, 1]], mock_writer.chunks());
+    }
 }
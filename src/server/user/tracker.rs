@@ -0,0 +1,218 @@
+use std::net::SocketAddr;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::SystemTime;
+
+use tokio::time::Instant;
+
+use crate::accesslog::{AccessLog, AccessRecord};
+use crate::general::IdleTimer;
+use crate::message::{CloseCode, CloseReason};
+use crate::metrics::{ConnectionLabels, ConnectionStats, Metrics};
+
+/// Keeps track of the Data transferred over a single User-Connection and
+/// reports the Connection as closed once it is dropped, which also writes the
+/// Access-Record for the Connection, if enabled
+///
+/// Both Halves of a User-Connection share a single Tracker, so the Connection
+/// is only reported as closed once both of them are done
+#[derive(Debug)]
+pub struct UserTracker<M>
+where
+    M: Metrics,
+{
+    labels: ConnectionLabels,
+    start: Instant,
+    started_at: SystemTime,
+    recv_bytes: AtomicU64,
+    send_bytes: AtomicU64,
+    reason: Mutex<Option<CloseReason>>,
+    idle: IdleTimer,
+    access_log: Option<(AccessLog, SocketAddr)>,
+    metrics: Arc<M>,
+}
+
+impl<M> UserTracker<M>
+where
+    M: Metrics,
+{
+    /// Creates a new Tracker and reports the Connection as opened
+    pub fn open(labels: ConnectionLabels, metrics: Arc<M>) -> Self {
+        metrics.connection_opened(&labels);
+
+        Self {
+            labels,
+            start: Instant::now(),
+            started_at: SystemTime::now(),
+            recv_bytes: AtomicU64::new(0),
+            send_bytes: AtomicU64::new(0),
+            reason: Mutex::new(None),
+            idle: IdleTimer::new(),
+            access_log: None,
+            metrics,
+        }
+    }
+
+    /// Writes an Access-Record for the Connection to the Log, once it is
+    /// closed
+    ///
+    /// Params:
+    /// * log: The Access-Log to write to
+    /// * peer: The Address of the User
+    pub fn with_access_log(mut self, log: AccessLog, peer: SocketAddr) -> Self {
+        self.access_log = Some((log, peer));
+        self
+    }
+
+    /// Records the Reason for closing the Connection, only the first Reason
+    /// is kept
+    pub fn close(&self, reason: CloseReason) {
+        let mut current = self.reason.lock().unwrap();
+        if current.is_none() {
+            *current = Some(reason);
+        }
+    }
+
+    /// Records Data that was received from the User
+    pub fn received(&self, bytes: u64) {
+        self.recv_bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.idle.touch();
+    }
+    /// Records Data that was send to the User
+    pub fn sent(&self, bytes: u64) {
+        self.send_bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.idle.touch();
+    }
+
+    /// The Timer for the last Activity on the Connection
+    pub fn idle(&self) -> &IdleTimer {
+        &self.idle
+    }
+
+    /// The current Statistics of the Connection
+    pub fn stats(&self) -> ConnectionStats {
+        ConnectionStats {
+            duration: self.start.elapsed(),
+            recv_bytes: self.recv_bytes.load(Ordering::Relaxed),
+            send_bytes: self.send_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<M> Drop for UserTracker<M>
+where
+    M: Metrics,
+{
+    fn drop(&mut self) {
+        let stats = self.stats();
+        self.metrics.connection_closed(&self.labels, &stats);
+
+        if let Some((log, peer)) = self.access_log.as_ref() {
+            let reason = match self.reason.get_mut() {
+                Ok(r) => r.take(),
+                Err(e) => e.into_inner().take(),
+            };
+
+            log.log(&AccessRecord {
+                port: self.labels.port,
+                user_ip: peer.ip(),
+                user_port: Some(peer.port()),
+                client_id: Some(self.labels.client_id),
+                connection_id: self.labels.connection_id,
+                start: self.started_at,
+                end: self.started_at + stats.duration,
+                recv_bytes: stats.recv_bytes,
+                send_bytes: stats.send_bytes,
+                reason: reason.unwrap_or_else(|| CloseReason::new(CloseCode::Normal)),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[derive(Debug, Default)]
+    struct LifecycleMetrics {
+        opened: Mutex<Vec<ConnectionLabels>>,
+        closed: Mutex<Vec<(ConnectionLabels, ConnectionStats)>>,
+    }
+
+    impl Metrics for LifecycleMetrics {
+        fn connection_opened(&self, labels: &ConnectionLabels) {
+            self.opened.lock().unwrap().push(labels.clone());
+        }
+        fn connection_closed(&self, labels: &ConnectionLabels, stats: &ConnectionStats) {
+            self.closed
+                .lock()
+                .unwrap()
+                .push((labels.clone(), stats.clone()));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn writes_access_record() {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let labels = ConnectionLabels {
+            client_id: 1,
+            port: 8080,
+            connection_id: 13,
+        };
+
+        let tracker = UserTracker::open(labels, Arc::new(crate::metrics::Empty::new()))
+            .with_access_log(
+                AccessLog::new(crate::accesslog::Format::Json, output.clone()),
+                "127.0.0.1:5000".parse().unwrap(),
+            );
+        tracker.received(10);
+        tracker.close(CloseReason::with_text(CloseCode::Error, "first"));
+        tracker.close(CloseReason::new(CloseCode::Normal));
+        tokio::time::advance(Duration::from_secs(3)).await;
+        drop(tracker);
+
+        let written = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        assert_eq!(1, written.lines().count());
+        assert_eq!(true, written.contains("\"connection_id\":13"));
+        assert_eq!(true, written.contains("\"duration_ms\":3000"));
+        assert_eq!(true, written.contains("\"recv_bytes\":10"));
+        assert_eq!(true, written.contains("\"close_text\":\"first\""));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_lifecycle() {
+        let metrics = Arc::new(LifecycleMetrics::default());
+        let labels = ConnectionLabels {
+            client_id: 1,
+            port: 8080,
+            connection_id: 13,
+        };
+
+        let tracker = UserTracker::open(labels.clone(), metrics.clone());
+        assert_eq!(vec![labels.clone()], *metrics.opened.lock().unwrap());
+        assert_eq!(0, metrics.closed.lock().unwrap().len());
+
+        tracker.received(10);
+        tracker.sent(5);
+        tracker.sent(7);
+        tokio::time::advance(Duration::from_secs(3)).await;
+        drop(tracker);
+
+        assert_eq!(
+            vec![(
+                labels,
+                ConnectionStats {
+                    duration: Duration::from_secs(3),
+                    recv_bytes: 10,
+                    send_bytes: 12,
+                }
+            )],
+            *metrics.closed.lock().unwrap()
+        );
+    }
+}
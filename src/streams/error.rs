@@ -1,3 +1,5 @@
+use crate::message::CloseReason;
+
 /// The Error that could be returned when trying to send something
 #[derive(Debug, PartialEq)]
 pub enum SendError {
@@ -28,16 +30,22 @@ impl<T> From<tokio::sync::mpsc::error::SendError<T>> for SendError {
 }
 
 /// The Error that could be returned when trying to read from a stream
+///
+/// Further Reasons for a Stream to end may be added in the future
 #[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub enum RecvError {
     /// The Stream was already closed
     Closed,
+    /// The Connection was closed by the other side with the given Reason
+    ClosedWith(CloseReason),
 }
 
 impl std::fmt::Display for RecvError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match *self {
+        match self {
             RecvError::Closed => write!(f, "The Channel has been closed"),
+            RecvError::ClosedWith(reason) => write!(f, "The Connection was closed: {}", reason),
         }
     }
 }